@@ -3,9 +3,6 @@
 use std::io::Read;
 use std::{env, process};
 
-use sgmlish::transforms::Transform;
-use sgmlish::{SgmlEvent, SgmlFragment};
-
 fn main() {
     if let Err(err) = run() {
         eprintln!("🛑 {}", err);
@@ -26,7 +23,7 @@ fn run() -> sgmlish::Result<()> {
         })
         .build();
 
-    let sgml = if let Some(path) = env::args_os().skip(1).next() {
+    let sgml = if let Some(path) = env::args_os().nth(1) {
         std::fs::read_to_string(path).unwrap()
     } else {
         let mut buffer = String::new();
@@ -60,49 +57,8 @@ fn run() -> sgmlish::Result<()> {
     }
 
     println!("ℹ️  Pretty-printed:");
-    let fragment = reindent(normalized);
-    println!("{}", fragment);
+    println!("{}", sgmlish::fmt::format(&normalized));
     println!();
 
     Ok(())
 }
-
-fn reindent(fragment: SgmlFragment) -> SgmlFragment {
-    let mut transform = Transform::new();
-    let mut indent_level = 0;
-
-    fn indent(level: usize) -> SgmlEvent<'static> {
-        let mut s = "\n".to_owned();
-        for _ in 0..level {
-            s.push_str("  ");
-        }
-        SgmlEvent::Character(s.into())
-    }
-
-    let mut keep_same_line = 0;
-    for (i, event) in fragment.iter().enumerate().skip(1) {
-        if keep_same_line > 0 {
-            keep_same_line -= 1;
-            continue;
-        }
-        match event {
-            SgmlEvent::OpenStartTag { .. }
-            | SgmlEvent::Character(_)
-            | SgmlEvent::ProcessingInstruction(_)
-            | SgmlEvent::MarkupDeclaration { .. }
-            | SgmlEvent::MarkedSection { .. } => transform.insert_at(i, indent(indent_level)),
-            SgmlEvent::CloseStartTag => match &fragment.as_slice()[i + 1..] {
-                [SgmlEvent::EndTag { .. }, ..] => keep_same_line = 1,
-                [SgmlEvent::Character(_), SgmlEvent::EndTag { .. }, ..] => keep_same_line = 2,
-                _ => indent_level += 1,
-            },
-            SgmlEvent::EndTag { .. } => {
-                indent_level -= 1;
-                transform.insert_at(i, indent(indent_level));
-            }
-            SgmlEvent::Attribute { .. } | SgmlEvent::XmlCloseEmptyElement => {}
-        }
-    }
-
-    transform.apply(fragment)
-}
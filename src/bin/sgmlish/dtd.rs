@@ -0,0 +1,878 @@
+//! A subset of DTD support, for validating documents against their element and
+//! attribute declarations.
+//!
+//! Element, attribute list and parameter entity declarations are read, along with
+//! marked sections resolved through parameter entities. Other declarations (general
+//! entities, notations, short references) are ignored. Names are compared without
+//! regard to case, as in the reference concrete syntax.
+
+use std::collections::{BTreeSet, HashMap};
+
+use sgmlish::entities;
+
+use crate::tree::{Element, Node};
+use crate::CliError;
+
+/// Parameter entities are expanded at most this many times within a declaration.
+const MAX_EXPANSION_DEPTH: usize = 16;
+
+/// The declarations relevant for validating documents.
+#[derive(Debug, Default)]
+pub struct Dtd {
+    elements: HashMap<String, ElementDecl>,
+    attributes: HashMap<String, Vec<AttributeDecl>>,
+    parameter_entities: HashMap<String, String>,
+}
+
+#[derive(Clone, Debug)]
+struct ElementDecl {
+    content: ContentSpec,
+    inclusions: Vec<String>,
+    exclusions: Vec<String>,
+}
+
+#[derive(Clone, Debug)]
+enum ContentSpec {
+    Empty,
+    Any,
+    /// `CDATA` or `RCDATA`: text only.
+    Text,
+    Model(Model),
+}
+
+/// A content model, as in `(HEAD, (P | LIST)*)`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Model {
+    Element(String),
+    PcData,
+    Seq(Vec<Model>),
+    Or(Vec<Model>),
+    And(Vec<Model>),
+    Opt(Box<Model>),
+    Star(Box<Model>),
+    Plus(Box<Model>),
+}
+
+#[derive(Clone, Debug)]
+struct AttributeDecl {
+    name: String,
+    /// The allowed values, for attributes declared with a name group.
+    values: Option<Vec<String>>,
+    required: bool,
+}
+
+impl Dtd {
+    /// Reads declarations from the given text, like an internal subset or a DTD file.
+    ///
+    /// Declarations read earlier take precedence, as in SGML, so the internal subset
+    /// should be read before the external DTD.
+    pub fn read(&mut self, text: &str) -> Result<(), CliError> {
+        self.read_declarations(text, 0).map_err(CliError::Dtd)
+    }
+
+    /// Validates a document tree, returning a description of each problem found.
+    ///
+    /// If `doctype` is given, the document element must have that name.
+    pub fn validate(&self, roots: &[Node], doctype: Option<&str>) -> Vec<String> {
+        let mut errors = vec![];
+        let mut elements = roots.iter().filter_map(|node| match node {
+            Node::Element(element) => Some(element),
+            Node::Text(_) => None,
+        });
+        match (elements.next(), doctype) {
+            (None, _) => errors.push("no document element".to_owned()),
+            (Some(root), doctype) => {
+                if let Some(doctype) = doctype {
+                    if !root.name.eq_ignore_ascii_case(doctype) {
+                        errors.push(format!(
+                            "document element is <{}>, but the document type is {}",
+                            root.name, doctype
+                        ));
+                    }
+                }
+                self.validate_element(root, &root.name, &[], &[], &mut errors);
+            }
+        }
+        if elements.next().is_some() {
+            errors.push("more than one document element".to_owned());
+        }
+        errors
+    }
+
+    fn validate_element(
+        &self,
+        element: &Element,
+        path: &str,
+        inclusions: &[&str],
+        exclusions: &[&str],
+        errors: &mut Vec<String>,
+    ) {
+        let name = element.name.to_uppercase();
+        if exclusions.contains(&name.as_str()) {
+            errors.push(format!("at {}: element is excluded here", path));
+        }
+        let decl = match self.elements.get(&name) {
+            Some(decl) => decl,
+            None => {
+                errors.push(format!("at {}: element is not declared", path));
+                return;
+            }
+        };
+        self.validate_attributes(element, &name, path, errors);
+
+        let mut inclusions = inclusions.to_vec();
+        inclusions.extend(decl.inclusions.iter().map(String::as_str));
+        let mut exclusions = exclusions.to_vec();
+        exclusions.extend(decl.exclusions.iter().map(String::as_str));
+
+        let has_text = element.children.iter().any(|node| match node {
+            Node::Text(text) => !text.trim().is_empty(),
+            Node::Element(_) => false,
+        });
+        // Included elements may appear anywhere, so they are not part of the model
+        let children = element
+            .children
+            .iter()
+            .filter_map(|node| match node {
+                Node::Element(child) => Some(child.name.to_uppercase()),
+                Node::Text(_) => None,
+            })
+            .filter(|child| !inclusions.contains(&child.as_str()))
+            .collect::<Vec<_>>();
+
+        match &decl.content {
+            ContentSpec::Any => {}
+            ContentSpec::Empty if !element.children.is_empty() => {
+                errors.push(format!("at {}: element is declared EMPTY", path));
+            }
+            ContentSpec::Empty => {}
+            ContentSpec::Text if !children.is_empty() => {
+                errors.push(format!("at {}: element only allows text", path));
+            }
+            ContentSpec::Text => {}
+            ContentSpec::Model(model) => {
+                if has_text && !model.allows_text() {
+                    errors.push(format!("at {}: text is not allowed here", path));
+                }
+                if !model.matches(&children) {
+                    errors.push(format!(
+                        "at {}: content ({}) does not match the model {}",
+                        path,
+                        children.join(", "),
+                        model
+                    ));
+                }
+            }
+        }
+
+        for child in &element.children {
+            if let Node::Element(child) = child {
+                let child_path = format!("{}/{}", path, child.name);
+                self.validate_element(child, &child_path, &inclusions, &exclusions, errors);
+            }
+        }
+    }
+
+    fn validate_attributes(
+        &self,
+        element: &Element,
+        name: &str,
+        path: &str,
+        errors: &mut Vec<String>,
+    ) {
+        let decls = self
+            .attributes
+            .get(name)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let mut seen = vec![];
+        for (attr_name, value) in &element.attributes {
+            let attr_name = attr_name.to_uppercase();
+            let decl = match value {
+                Some(_) => decls.iter().find(|decl| decl.name == attr_name),
+                // A value without a name (`<OPTION SELECTED>`) belongs to whichever
+                // attribute accepts it
+                None => decls.iter().find(|decl| {
+                    decl.values
+                        .as_ref()
+                        .map_or(false, |values| values.contains(&attr_name))
+                }),
+            };
+            let decl = match decl {
+                Some(decl) => decl,
+                None => {
+                    errors.push(format!(
+                        "at {}: attribute {} is not declared",
+                        path, attr_name
+                    ));
+                    continue;
+                }
+            };
+            if let (Some(values), Some(value)) = (&decl.values, value) {
+                if !values.contains(&value.to_uppercase()) {
+                    errors.push(format!(
+                        "at {}: attribute {} must be one of {}, found {:?}",
+                        path,
+                        decl.name,
+                        values.join(" | "),
+                        value
+                    ));
+                }
+            }
+            seen.push(decl.name.as_str());
+        }
+        for decl in decls {
+            if decl.required && !seen.contains(&decl.name.as_str()) {
+                errors.push(format!("at {}: attribute {} is required", path, decl.name));
+            }
+        }
+    }
+
+    fn read_declarations(&mut self, text: &str, depth: usize) -> Result<(), String> {
+        if depth > MAX_EXPANSION_DEPTH {
+            return Err("parameter entities nested too deeply".to_owned());
+        }
+        let mut rest = text;
+        loop {
+            rest = rest.trim_start();
+            if rest.is_empty() {
+                return Ok(());
+            }
+            if let Some(reference) = rest.strip_prefix('%') {
+                // A parameter entity reference between declarations
+                let end = reference
+                    .find(|c: char| !is_name_char(c))
+                    .unwrap_or(reference.len());
+                let name = &reference[..end];
+                let replacement = self.parameter_entity(name)?.to_owned();
+                self.read_declarations(&replacement, depth + 1)?;
+                rest = reference[end..]
+                    .strip_prefix(';')
+                    .unwrap_or(&reference[end..]);
+            } else if rest.starts_with("<![") {
+                rest = self.read_marked_section(rest, depth)?;
+            } else if rest.starts_with("<?") {
+                let end = rest
+                    .find('>')
+                    .ok_or("unterminated processing instruction")?;
+                rest = &rest[end + 1..];
+            } else if let Some(declaration) = rest.strip_prefix("<!") {
+                let (body, after) = split_declaration(declaration)?;
+                self.read_declaration(&body)?;
+                rest = after;
+            } else {
+                let line = rest.lines().next().unwrap_or_default();
+                return Err(format!("unexpected text: {}", line));
+            }
+        }
+    }
+
+    fn read_marked_section<'t>(&mut self, text: &'t str, depth: usize) -> Result<&'t str, String> {
+        let keywords_end = text[3..].find('[').ok_or("unterminated marked section")? + 3;
+        let keywords = self.expand(&text[3..keywords_end])?.to_uppercase();
+        let content = &text[keywords_end + 1..];
+
+        // Find the matching end, skipping over nested sections
+        let mut nesting = 0usize;
+        let mut i = 0;
+        let end = loop {
+            let rest = &content[i..];
+            if rest.is_empty() {
+                return Err("unterminated marked section".to_owned());
+            } else if rest.starts_with("<![") {
+                nesting += 1;
+                i += 3;
+            } else if rest.starts_with("]]>") {
+                if nesting == 0 {
+                    break i;
+                }
+                nesting -= 1;
+                i += 3;
+            } else {
+                i += rest.chars().next().map_or(1, char::len_utf8);
+            }
+        };
+
+        if !keywords
+            .split_whitespace()
+            .any(|keyword| keyword == "IGNORE")
+        {
+            self.read_declarations(&content[..end], depth + 1)?;
+        }
+        Ok(&content[end + 3..])
+    }
+
+    fn read_declaration(&mut self, body: &str) -> Result<(), String> {
+        let keyword_end = body.find(|c: char| !is_name_char(c)).unwrap_or(body.len());
+        let keyword = body[..keyword_end].to_uppercase();
+        let body = &body[keyword_end..];
+        match keyword.as_str() {
+            // Comment declaration
+            "" => Ok(()),
+            "ENTITY" => self.read_entity(body),
+            // Element and attribute names ignore case; entity names do not
+            "ELEMENT" => {
+                let body = self.expand(body)?.to_uppercase();
+                self.read_element(&body)
+                    .map_err(|err| format!("<!ELEMENT{}>: {}", body, err))
+            }
+            "ATTLIST" => {
+                let body = self.expand(body)?.to_uppercase();
+                self.read_attlist(&body)
+                    .map_err(|err| format!("<!ATTLIST{}>: {}", body, err))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn read_entity(&mut self, body: &str) -> Result<(), String> {
+        let mut tokens = Tokens::new(body)?;
+        if tokens.next_if(&Token::Punct('%')).is_none() {
+            // General entities are left to the parser options
+            return Ok(());
+        }
+        let name = match tokens.next() {
+            Some(Token::Name(name)) => name,
+            _ => return Err(format!("<!ENTITY{}>: expected a name", body)),
+        };
+        let text = match tokens.next() {
+            Some(Token::Literal(text)) => text,
+            // External entities cannot be resolved, and count as empty
+            _ => String::new(),
+        };
+        // The first declaration of an entity is the one that counts
+        self.parameter_entities.entry(name).or_insert(text);
+        Ok(())
+    }
+
+    fn read_element(&mut self, body: &str) -> Result<(), String> {
+        let mut tokens = Tokens::new(body)?;
+        let names = tokens.name_or_group()?;
+
+        // Tag minimization (`- O`), which does not affect validation
+        let is_minimization = |token: Option<&Token>| {
+            matches!(token, Some(Token::Punct('-')))
+                || matches!(token, Some(Token::Name(name)) if name == "O")
+        };
+        if is_minimization(tokens.peek(0)) && is_minimization(tokens.peek(1)) {
+            tokens.next();
+            tokens.next();
+        }
+
+        let content = match tokens.next() {
+            Some(Token::Name(keyword)) => match keyword.as_str() {
+                "EMPTY" => ContentSpec::Empty,
+                "ANY" => ContentSpec::Any,
+                "CDATA" | "RCDATA" => ContentSpec::Text,
+                _ => return Err(format!("unexpected {}", keyword)),
+            },
+            Some(Token::Punct('(')) => ContentSpec::Model(tokens.model_group()?),
+            _ => return Err("expected declared content or a content model".to_owned()),
+        };
+
+        let mut exclusions = vec![];
+        let mut inclusions = vec![];
+        while let Some(Token::Punct(sign @ ('-' | '+'))) = tokens.next() {
+            let group = match tokens.next() {
+                Some(Token::Punct('(')) => tokens.name_group()?,
+                _ => return Err("expected a name group".to_owned()),
+            };
+            match sign {
+                '-' => exclusions.extend(group),
+                _ => inclusions.extend(group),
+            }
+        }
+
+        for name in names {
+            self.elements.entry(name).or_insert_with(|| ElementDecl {
+                content: content.clone(),
+                inclusions: inclusions.clone(),
+                exclusions: exclusions.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    fn read_attlist(&mut self, body: &str) -> Result<(), String> {
+        let mut tokens = Tokens::new(body)?;
+        let names = tokens.name_or_group()?;
+        let mut decls = vec![];
+        while let Some(token) = tokens.next() {
+            let name = match token {
+                Token::Name(name) => name,
+                token => return Err(format!("expected an attribute name, found {:?}", token)),
+            };
+            let values = match tokens.next() {
+                Some(Token::Punct('(')) => Some(tokens.name_group()?),
+                Some(Token::Name(keyword)) if keyword == "NOTATION" => {
+                    tokens.next();
+                    Some(tokens.name_group()?)
+                }
+                Some(Token::Name(_)) => None,
+                _ => return Err(format!("expected a declared value for {}", name)),
+            };
+            let required = match tokens.next() {
+                Some(Token::Name(default)) if default == "#REQUIRED" => true,
+                Some(Token::Name(default)) if default == "#FIXED" => {
+                    tokens.next();
+                    false
+                }
+                Some(Token::Name(_)) | Some(Token::Literal(_)) => false,
+                _ => return Err(format!("expected a default value for {}", name)),
+            };
+            decls.push(AttributeDecl {
+                name,
+                values,
+                required,
+            });
+        }
+        for name in names {
+            self.attributes.entry(name).or_insert_with(|| decls.clone());
+        }
+        Ok(())
+    }
+
+    fn parameter_entity(&self, name: &str) -> Result<&str, String> {
+        self.parameter_entities
+            .get(name)
+            .map(String::as_str)
+            .ok_or_else(|| format!("undefined parameter entity: %{};", name))
+    }
+
+    /// Expands all parameter entity references in the text, including nested ones.
+    fn expand(&self, text: &str) -> Result<String, String> {
+        let mut text = text.to_owned();
+        for _ in 0..MAX_EXPANSION_DEPTH {
+            let expanded = entities::expand_parameter_entities(&text, |name| {
+                self.parameter_entities.get(name)
+            })
+            .map_err(|err| format!("undefined parameter entity: %{};", err.entity))?;
+            if expanded == text {
+                return Ok(text);
+            }
+            text = expanded.into_owned();
+        }
+        Err("parameter entities nested too deeply".to_owned())
+    }
+}
+
+impl Model {
+    fn allows_text(&self) -> bool {
+        match self {
+            Model::PcData => true,
+            Model::Element(_) => false,
+            Model::Seq(models) | Model::Or(models) | Model::And(models) => {
+                models.iter().any(Model::allows_text)
+            }
+            Model::Opt(model) | Model::Star(model) | Model::Plus(model) => model.allows_text(),
+        }
+    }
+
+    /// Checks whether the sequence of element names is accepted by the model.
+    fn matches(&self, names: &[String]) -> bool {
+        self.ends(names, 0).contains(&names.len())
+    }
+
+    /// Returns all positions where a match of the model starting at `start` may end.
+    fn ends(&self, names: &[String], start: usize) -> BTreeSet<usize> {
+        match self {
+            Model::Element(name) => names
+                .get(start)
+                .filter(|found| *found == name)
+                .map(|_| start + 1)
+                .into_iter()
+                .collect(),
+            // Text is checked separately, so it matches without consuming elements
+            Model::PcData => std::iter::once(start).collect(),
+            Model::Seq(models) => {
+                models
+                    .iter()
+                    .fold(std::iter::once(start).collect(), |positions, model| {
+                        positions
+                            .into_iter()
+                            .flat_map(|position| model.ends(names, position))
+                            .collect()
+                    })
+            }
+            Model::Or(models) => models
+                .iter()
+                .flat_map(|model| model.ends(names, start))
+                .collect(),
+            Model::And(models) => {
+                let models = models.iter().collect::<Vec<_>>();
+                and_ends(&models, names, start)
+            }
+            Model::Opt(model) => {
+                let mut positions = model.ends(names, start);
+                positions.insert(start);
+                positions
+            }
+            Model::Star(model) => repeat_ends(model, names, std::iter::once(start).collect()),
+            Model::Plus(model) => repeat_ends(model, names, model.ends(names, start)),
+        }
+    }
+}
+
+/// All members of an `&` group, in any order.
+fn and_ends(models: &[&Model], names: &[String], start: usize) -> BTreeSet<usize> {
+    if models.is_empty() {
+        return std::iter::once(start).collect();
+    }
+    let mut positions = BTreeSet::new();
+    for (i, model) in models.iter().enumerate() {
+        let mut rest = models.to_vec();
+        rest.remove(i);
+        for position in model.ends(names, start) {
+            positions.extend(and_ends(&rest, names, position));
+        }
+    }
+    positions
+}
+
+/// Repeats a model as many times as possible from the given positions.
+fn repeat_ends(model: &Model, names: &[String], mut positions: BTreeSet<usize>) -> BTreeSet<usize> {
+    let mut pending = positions.iter().copied().collect::<Vec<_>>();
+    while let Some(position) = pending.pop() {
+        for end in model.ends(names, position) {
+            if positions.insert(end) {
+                pending.push(end);
+            }
+        }
+    }
+    positions
+}
+
+impl std::fmt::Display for Model {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let group = |f: &mut std::fmt::Formatter, models: &[Model], connector| {
+            f.write_str("(")?;
+            for (i, model) in models.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(connector)?;
+                }
+                write!(f, "{}", model)?;
+            }
+            f.write_str(")")
+        };
+        match self {
+            Model::Element(name) => f.write_str(name),
+            Model::PcData => f.write_str("#PCDATA"),
+            Model::Seq(models) => group(f, models, ", "),
+            Model::Or(models) => group(f, models, " | "),
+            Model::And(models) => group(f, models, " & "),
+            Model::Opt(model) => write!(f, "{}?", model),
+            Model::Star(model) => write!(f, "{}*", model),
+            Model::Plus(model) => write!(f, "{}+", model),
+        }
+    }
+}
+
+/// Splits the text after `<!` into the declaration body, without comments,
+/// and the text after its closing `>`.
+fn split_declaration(text: &str) -> Result<(String, &str), String> {
+    let mut body = String::new();
+    let mut rest = text;
+    loop {
+        let next = rest
+            .find(['>', '"', '\'', '-', '['])
+            .ok_or("unterminated declaration")?;
+        body.push_str(&rest[..next]);
+        rest = &rest[next..];
+        match rest.chars().next() {
+            Some('>') => return Ok((body, &rest[1..])),
+            Some(quote @ ('"' | '\'')) => {
+                let end = rest[1..].find(quote).ok_or("unterminated literal")? + 2;
+                body.push_str(&rest[..end]);
+                rest = &rest[end..];
+            }
+            Some('-') if rest.starts_with("--") => {
+                let end = rest[2..].find("--").ok_or("unterminated comment")? + 4;
+                body.push(' ');
+                rest = &rest[end..];
+            }
+            Some('[') => {
+                return Err("declaration subsets are only supported in the document".to_owned())
+            }
+            _ => {
+                body.push('-');
+                rest = &rest[1..];
+            }
+        }
+    }
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '.' | '-' | '_' | ':' | '#')
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Token {
+    Name(String),
+    Literal(String),
+    Punct(char),
+}
+
+struct Tokens {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Tokens {
+    fn new(text: &str) -> Result<Self, String> {
+        let mut tokens = vec![];
+        let mut chars = text.char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                c if c.is_whitespace() => {}
+                '"' | '\'' => {
+                    let end = text[i + 1..].find(c).ok_or("unterminated literal")? + i + 1;
+                    tokens.push(Token::Literal(text[i + 1..end].to_owned()));
+                    while chars.next_if(|&(j, _)| j <= end).is_some() {}
+                }
+                c if is_name_char(c) && c != '-' => {
+                    let mut end = text.len();
+                    while let Some(&(j, c)) = chars.peek() {
+                        if !is_name_char(c) {
+                            end = j;
+                            break;
+                        }
+                        chars.next();
+                    }
+                    tokens.push(Token::Name(text[i..end].to_owned()));
+                }
+                c => tokens.push(Token::Punct(c)),
+            }
+        }
+        Ok(Tokens {
+            tokens,
+            position: 0,
+        })
+    }
+
+    fn peek(&self, offset: usize) -> Option<&Token> {
+        self.tokens.get(self.position + offset)
+    }
+
+    fn next_if(&mut self, expected: &Token) -> Option<Token> {
+        if self.peek(0) == Some(expected) {
+            self.next()
+        } else {
+            None
+        }
+    }
+
+    /// A single name, or a group of names (`(A | B)`).
+    fn name_or_group(&mut self) -> Result<Vec<String>, String> {
+        match self.next() {
+            Some(Token::Name(name)) => Ok(vec![name]),
+            Some(Token::Punct('(')) => self.name_group(),
+            _ => Err("expected a name or a name group".to_owned()),
+        }
+    }
+
+    /// The rest of a group of names, after the opening `(`.
+    fn name_group(&mut self) -> Result<Vec<String>, String> {
+        let mut names = vec![];
+        loop {
+            match self.next() {
+                Some(Token::Name(name)) => names.push(name),
+                _ => return Err("expected a name in group".to_owned()),
+            }
+            match self.next() {
+                Some(Token::Punct(')')) => return Ok(names),
+                Some(Token::Punct('|' | ',' | '&')) => {}
+                _ => return Err("expected a connector or ')'".to_owned()),
+            }
+        }
+    }
+
+    /// The rest of a model group, after the opening `(`, with its occurrence indicator.
+    fn model_group(&mut self) -> Result<Model, String> {
+        let mut models = vec![];
+        let mut connector = None;
+        loop {
+            let model = match self.next() {
+                Some(Token::Name(name)) if name == "#PCDATA" => Model::PcData,
+                Some(Token::Name(name)) => self.occurrence(Model::Element(name)),
+                Some(Token::Punct('(')) => self.model_group()?,
+                _ => return Err("expected a name or a group in model".to_owned()),
+            };
+            models.push(model);
+            match self.next() {
+                Some(Token::Punct(')')) => break,
+                Some(Token::Punct(c @ ('|' | ',' | '&'))) => match connector {
+                    Some(connector) if connector != c => {
+                        return Err("mixed connectors in model group".to_owned())
+                    }
+                    _ => connector = Some(c),
+                },
+                _ => return Err("expected a connector or ')'".to_owned()),
+            }
+        }
+        let group = match connector {
+            Some('|') => Model::Or(models),
+            Some('&') => Model::And(models),
+            _ => Model::Seq(models),
+        };
+        Ok(self.occurrence(group))
+    }
+
+    fn occurrence(&mut self, model: Model) -> Model {
+        // `+(` starts inclusions rather than being an occurrence indicator
+        let indicator = match (self.peek(0), self.peek(1)) {
+            (Some(Token::Punct('+')), Some(Token::Punct('('))) => return model,
+            (Some(Token::Punct(c @ ('?' | '*' | '+'))), _) => *c,
+            _ => return model,
+        };
+        self.next();
+        match indicator {
+            '?' => Model::Opt(Box::new(model)),
+            '*' => Model::Star(Box::new(model)),
+            _ => Model::Plus(Box::new(model)),
+        }
+    }
+}
+
+impl Iterator for Tokens {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+}
+
+/// Finds the document type name and internal subset in a `DOCTYPE` declaration body.
+pub fn split_doctype(body: &str) -> (&str, Option<&str>) {
+    let body = body.trim_start();
+    let name_end = body.find(|c: char| !is_name_char(c)).unwrap_or(body.len());
+    let name = &body[..name_end];
+
+    let mut quote = None;
+    let mut subset_start = None;
+    let mut depth = 0usize;
+    for (i, c) in body.char_indices().skip(name_end) {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '[') => {
+                if depth == 0 && subset_start.is_none() {
+                    subset_start = Some(i + 1);
+                }
+                depth += 1;
+            }
+            (None, ']') => {
+                depth = depth.saturating_sub(1);
+                if depth == 0 {
+                    if let Some(start) = subset_start {
+                        return (name, Some(&body[start..i]));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    (name, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DTD: &str = r#"
+        <!-- A small DTD -->
+        <!ENTITY % inline "B | I" -- phrase elements -->
+        <!ENTITY % draft "IGNORE">
+        <!ELEMENT DOC - - (TITLE, (P | LIST)*, (AUTHOR & DATE)?) +(NOTE)>
+        <!ELEMENT TITLE - O (#PCDATA)>
+        <!ELEMENT P - O (#PCDATA | %inline;)* -(P)>
+        <!ELEMENT (%inline;) - - (#PCDATA)>
+        <!ELEMENT LIST - - (ITEM+)>
+        <!ELEMENT (ITEM | AUTHOR | DATE | NOTE) - O CDATA>
+        <!ELEMENT BR - O EMPTY>
+        <![ %draft; [ <!ELEMENT DOC - - ANY> ]]>
+        <!ATTLIST DOC
+            STATUS (DRAFT | FINAL) #REQUIRED
+            ID     CDATA           #IMPLIED>
+        <!ATTLIST LIST COMPACT (COMPACT) #IMPLIED>
+    "#;
+
+    fn validate(input: &str) -> Vec<String> {
+        let mut dtd = Dtd::default();
+        dtd.read(DTD).unwrap();
+        let fragment = sgmlish::parse(input).unwrap();
+        let roots = crate::tree::build(fragment).unwrap();
+        dtd.validate(&roots, Some("DOC"))
+    }
+
+    #[test]
+    fn test_valid_document() {
+        let errors = validate(concat!(
+            "<DOC STATUS=final><TITLE>T</TITLE><P>a <B>b</B> <I>c</I></P>",
+            "<NOTE>n</NOTE><LIST COMPACT><ITEM>1</ITEM><ITEM>2</ITEM></LIST>",
+            "<DATE>d</DATE><AUTHOR>a</AUTHOR></DOC>",
+        ));
+        assert_eq!(errors, Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_invalid_content() {
+        let errors = validate(concat!(
+            "<DOC><P>text</P><LIST></LIST><P><P>nested</P></P><X></X>",
+            "<DATE>d</DATE>oops</DOC>",
+        ));
+        assert_eq!(
+            errors,
+            vec![
+                "at DOC: attribute STATUS is required",
+                "at DOC: text is not allowed here",
+                "at DOC: content (P, LIST, P, X, DATE) does not match the model \
+                 (TITLE, (P | LIST)*, (AUTHOR & DATE)?)",
+                "at DOC/LIST: content () does not match the model (ITEM+)",
+                "at DOC/P: content (P) does not match the model (#PCDATA | B | I)*",
+                "at DOC/P/P: element is excluded here",
+                "at DOC/X: element is not declared",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_attributes() {
+        let errors = validate("<DOC STATUS=wip LANG=en><TITLE>T</TITLE></DOC>");
+        assert_eq!(
+            errors,
+            vec![
+                r#"at DOC: attribute STATUS must be one of DRAFT | FINAL, found "wip""#,
+                "at DOC: attribute LANG is not declared",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_doctype_name() {
+        let mut dtd = Dtd::default();
+        dtd.read(DTD).unwrap();
+        let roots = crate::tree::build(sgmlish::parse("<TITLE>T</TITLE>").unwrap()).unwrap();
+        assert_eq!(
+            dtd.validate(&roots, Some("DOC")),
+            vec!["document element is <TITLE>, but the document type is DOC"]
+        );
+    }
+
+    #[test]
+    fn test_undefined_parameter_entity() {
+        let mut dtd = Dtd::default();
+        assert!(dtd.read("<!ELEMENT A - - (%missing;)>").is_err());
+    }
+
+    #[test]
+    fn test_split_doctype() {
+        assert_eq!(split_doctype("HTML"), ("HTML", None));
+        assert_eq!(
+            split_doctype(r#"DOC SYSTEM "a[b" [ <!ELEMENT DOC - - (#PCDATA)> ]"#),
+            ("DOC", Some(" <!ELEMENT DOC - - (#PCDATA)> "))
+        );
+    }
+}
@@ -0,0 +1,162 @@
+//! Command-line interface for parsing, converting and querying SGML documents.
+//!
+//! Run `sgmlish --help` for usage.
+
+use std::io::{self, Read, Write};
+use std::{env, fs, process};
+
+use sgmlish::transforms::normalize_end_tags;
+use sgmlish::SgmlEvent;
+
+use crate::dtd::Dtd;
+use crate::options::{Command, Input, Invocation, Options};
+
+mod dtd;
+mod options;
+mod output;
+mod tree;
+
+/// The error type for anything that may fail when running a command.
+#[derive(Debug, thiserror::Error)]
+pub enum CliError {
+    /// Invalid command-line arguments.
+    #[error("{0}")]
+    Usage(String),
+    /// The input could not be read, or the output could not be written.
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    /// The document could not be parsed or normalized.
+    #[error(transparent)]
+    Sgml(#[from] sgmlish::Error),
+    /// The document contains something the command cannot handle.
+    #[error("{0}")]
+    Unsupported(String),
+    /// The DTD could not be found or read.
+    #[error("DTD: {0}")]
+    Dtd(String),
+    /// The document does not conform to its DTD.
+    #[error("document is not valid:\n  {}", .0.join("\n  "))]
+    Invalid(Vec<String>),
+}
+
+impl From<sgmlish::transforms::NormalizationError> for CliError {
+    fn from(err: sgmlish::transforms::NormalizationError) -> Self {
+        CliError::Sgml(err.into())
+    }
+}
+
+fn main() {
+    let (command, options, inputs) = match options::parse_args(env::args_os().skip(1)) {
+        Ok(Invocation::Run {
+            command,
+            options,
+            inputs,
+        }) => (command, options, inputs),
+        Ok(Invocation::Help) => {
+            print!("{}", options::USAGE);
+            return;
+        }
+        Err(err) => {
+            eprintln!("sgmlish: {}", err);
+            eprintln!("Try 'sgmlish --help' for more information.");
+            process::exit(2);
+        }
+    };
+
+    let mut failed = false;
+    for input in &inputs {
+        if let Err(err) = run(&command, &options, input) {
+            match input {
+                Input::Stdin => eprintln!("sgmlish: {}", err),
+                Input::File(path) => eprintln!("sgmlish: {}: {}", path.display(), err),
+            }
+            failed = true;
+        }
+    }
+    if failed {
+        process::exit(1);
+    }
+}
+
+fn run(command: &Command, options: &Options, input: &Input) -> Result<(), CliError> {
+    let sgml = match input {
+        Input::Stdin => {
+            let mut buffer = String::new();
+            io::stdin().read_to_string(&mut buffer)?;
+            buffer
+        }
+        Input::File(path) => fs::read_to_string(path)?,
+    };
+    let fragment = options.parser_builder().parse(&sgml)?;
+
+    let stdout = io::stdout();
+    let mut out = io::BufWriter::new(stdout.lock());
+    match command {
+        Command::Parse => {
+            for event in &fragment {
+                writeln!(out, "{:?}", event)?;
+            }
+        }
+        Command::Fmt => {
+            let formatter = options.formatter_builder().build();
+            writeln!(out, "{}", formatter.format(&normalize_end_tags(fragment)?))?
+        }
+        Command::Normalize => writeln!(out, "{}", normalize_end_tags(fragment)?)?,
+        Command::ToXml => {
+            output::write_xml(&mut out, &tree::build(normalize_end_tags(fragment)?)?)?
+        }
+        Command::ToJson => {
+            output::write_json(&mut out, &tree::build(normalize_end_tags(fragment)?)?)?
+        }
+        Command::Check => {
+            tree::build(normalize_end_tags(fragment)?)?;
+        }
+        Command::Validate => validate(options, fragment)?,
+        Command::Query(path) => {
+            let roots = tree::build(normalize_end_tags(fragment)?)?;
+            for element in tree::query(&roots, path) {
+                writeln!(out, "{}", element.text())?;
+            }
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn validate(options: &Options, fragment: sgmlish::SgmlFragment) -> Result<(), CliError> {
+    let doctype = fragment.iter().find_map(|event| match event {
+        SgmlEvent::MarkupDeclaration { keyword, body }
+            if keyword.eq_ignore_ascii_case("DOCTYPE") =>
+        {
+            let (name, subset) = dtd::split_doctype(body);
+            Some((name.to_owned(), subset.map(str::to_owned)))
+        }
+        _ => None,
+    });
+    let (name, subset) = match doctype {
+        Some((name, subset)) => (Some(name), subset),
+        None => (None, None),
+    };
+
+    let mut dtd = Dtd::default();
+    if let Some(subset) = &subset {
+        dtd.read(subset)?;
+    }
+    match &options.dtd {
+        Some(path) => dtd.read(&fs::read_to_string(path)?)?,
+        None if subset.is_none() => {
+            return Err(CliError::Dtd(
+                "the document has no internal subset; pass the DTD with --dtd".to_owned(),
+            ))
+        }
+        None => {}
+    }
+
+    let roots = tree::build(normalize_end_tags(fragment)?)?;
+    let errors = dtd.validate(&roots, name.as_deref());
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(CliError::Invalid(errors))
+    }
+}
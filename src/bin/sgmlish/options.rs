@@ -0,0 +1,531 @@
+//! Command-line argument handling.
+
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+use sgmlish::fmt::{AttributeWrapping, FormatterBuilder};
+use sgmlish::parser::{
+    MarkedSectionHandling, NameNormalization, ParserBuilder, WhitespaceHandling,
+};
+use sgmlish::transforms::{
+    DropMarkupDeclarations, DropProcessingInstructions, NormalizeEndTags, NormalizeNames, Pipeline,
+    RenameElements,
+};
+
+use crate::CliError;
+
+pub const USAGE: &str = "\
+Usage: sgmlish <COMMAND> [OPTIONS] [FILE]...
+
+Reads each FILE (or standard input, when no files are given or FILE is `-`)
+and processes it according to COMMAND.
+
+Commands:
+  parse                 Print the parsed event stream, one event per line
+  fmt                   Insert implied end tags and pretty-print the document
+  normalize             Insert implied end tags and print the document
+  to-xml                Convert the document to XML
+  to-json               Convert the document to JSON
+  check                 Check that the document is well-formed: it parses, and its
+                        tags are balanced once implied end tags are inserted
+  validate              Validate the document against its DTD: element content,
+                        attribute values and required attributes are checked.
+                        The DTD is read from the internal subset of the document's
+                        `<!DOCTYPE ...>` declaration, and from `--dtd` if given
+  query <PATH>          Print the text of every element matching PATH,
+                        e.g. `OFX/BANKMSGSRSV1/*/STMTRS/CURDEF`
+
+Parser options:
+      --lowercase-names             Normalize tag and attribute names to lowercase
+      --uppercase-names             Normalize tag and attribute names to uppercase
+      --keep-whitespace             Do not trim whitespace surrounding text
+      --record-boundaries           Keep whitespace, except for line breaks that SGML
+                                    ignores (after start tags, before end tags)
+      --marked-sections <MODE>      How to handle marked sections:
+                                    `keep`, `parsed`, `cdata` (default) or `all`
+      --entities <SET>              Expand a predefined set of entities:
+                                    `xml` (lt, gt, amp, quot, apos) or `ofx` (xml + nbsp)
+      --entity <NAME=VALUE>         Define an entity; may be repeated
+      --parameter-entity <NAME=VALUE>
+                                    Define a parameter entity; may be repeated
+      --ignore-markup-declarations  Drop markup declarations (`<!DOCTYPE ...>`)
+      --ignore-processing-instructions
+                                    Drop processing instructions (`<?...>`)
+      --keep-comments               Keep comments (`<!-- ... -->`)
+      --function-character <NAME=CHAR>
+                                    Define a function character for `&#NAME;` references,
+                                    as a single character or a decimal code point;
+                                    may be repeated
+      --pass <PASS>                 Transform the document after parsing; may be repeated,
+                                    and passes run in order: `normalize-end-tags`,
+                                    `lowercase-names`, `uppercase-names`,
+                                    `drop-markup-declarations` or
+                                    `drop-processing-instructions`
+      --rename-element <FROM=TO>    Rename elements after parsing, as a pass running
+                                    in order with `--pass`; may be repeated
+
+Validation options (for `validate`):
+      --dtd <FILE>                  Read the DTD from FILE; declarations in the document's
+                                    internal subset take precedence
+
+Formatting options (for `fmt`):
+      --indent <N>                  Indent with N spaces (default: 2), or tabs if N is `tab`
+      --wrap-attributes <WIDTH>     Place attributes on separate lines when a start tag
+                                    would be wider than WIDTH (`0` to always wrap)
+      --omit-end-tags               Omit end tags of elements without child elements
+
+  -h, --help                        Print this help
+";
+
+/// The action to be performed on each input.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Command {
+    Parse,
+    Fmt,
+    Normalize,
+    ToXml,
+    ToJson,
+    Check,
+    Validate,
+    Query(String),
+}
+
+/// Where a document should be read from.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Input {
+    Stdin,
+    File(PathBuf),
+}
+
+/// All options passed through the command line.
+#[derive(Debug, Default)]
+pub struct Options {
+    pub name_normalization: NameNormalization,
+    pub whitespace_handling: WhitespaceHandling,
+    pub marked_section_handling: MarkedSectionHandling,
+    pub entities: HashMap<String, String>,
+    pub parameter_entities: HashMap<String, String>,
+    pub ignore_markup_declarations: bool,
+    pub ignore_processing_instructions: bool,
+    pub keep_comments: bool,
+    pub function_characters: Vec<(String, char)>,
+    pub passes: Vec<Pass>,
+    pub dtd: Option<PathBuf>,
+    pub indent: Option<String>,
+    pub attribute_wrapping: AttributeWrapping,
+    pub omit_end_tags: bool,
+}
+
+/// A transform applied to documents after parsing.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Pass {
+    NormalizeEndTags,
+    NormalizeNames(NameNormalization),
+    DropMarkupDeclarations,
+    DropProcessingInstructions,
+    RenameElement(String, String),
+}
+
+/// The result of parsing the command line.
+#[derive(Debug)]
+pub enum Invocation {
+    Help,
+    Run {
+        command: Command,
+        options: Box<Options>,
+        inputs: Vec<Input>,
+    },
+}
+
+impl Options {
+    /// Creates a parser builder reflecting these options.
+    pub fn parser_builder(&self) -> ParserBuilder {
+        let entities = self.entities.clone();
+        let parameter_entities = self.parameter_entities.clone();
+        let builder = ParserBuilder::new()
+            .name_normalization(self.name_normalization)
+            .whitespace_handling(self.whitespace_handling)
+            .marked_section_handling(self.marked_section_handling)
+            .expand_entities(move |entity| entities.get(entity).cloned())
+            .expand_parameter_entities(move |entity| parameter_entities.get(entity).cloned())
+            .ignore_markup_declarations(self.ignore_markup_declarations)
+            .ignore_processing_instructions(self.ignore_processing_instructions)
+            .keep_comments(self.keep_comments)
+            .pipeline(self.pipeline());
+        self.function_characters
+            .iter()
+            .fold(builder, |builder, (name, c)| {
+                builder.function_character(name, *c)
+            })
+    }
+
+    /// Creates the pipeline of transforms selected with `--pass` and `--rename-element`.
+    fn pipeline(&self) -> Pipeline {
+        self.passes
+            .iter()
+            .fold(Pipeline::new(), |pipeline, pass| match pass {
+                Pass::NormalizeEndTags => pipeline.then(NormalizeEndTags),
+                Pass::NormalizeNames(normalization) => {
+                    pipeline.then(NormalizeNames(*normalization))
+                }
+                Pass::DropMarkupDeclarations => pipeline.then(DropMarkupDeclarations),
+                Pass::DropProcessingInstructions => pipeline.then(DropProcessingInstructions),
+                Pass::RenameElement(from, to) => {
+                    pipeline.then(RenameElements::new().rename(from.clone(), to.clone()))
+                }
+            })
+    }
+
+    /// Creates a formatter builder reflecting these options.
+    pub fn formatter_builder(&self) -> FormatterBuilder {
+        let builder = FormatterBuilder::new()
+            .attribute_wrapping(self.attribute_wrapping)
+            .omit_end_tags(self.omit_end_tags);
+        match &self.indent {
+            Some(indent) => builder.indent(indent.clone()),
+            None => builder,
+        }
+    }
+
+    fn add_entity_set(&mut self, set: &str) -> Result<(), CliError> {
+        let entities: &[(&str, &str)] = match set {
+            "xml" => XML_ENTITIES,
+            "ofx" => {
+                self.entities.insert("nbsp".to_owned(), "\u{a0}".to_owned());
+                XML_ENTITIES
+            }
+            _ => return Err(CliError::Usage(format!("unknown entity set: {}", set))),
+        };
+        self.entities.extend(
+            entities
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string())),
+        );
+        Ok(())
+    }
+}
+
+const XML_ENTITIES: &[(&str, &str)] = &[
+    ("lt", "<"),
+    ("gt", ">"),
+    ("amp", "&"),
+    ("quot", "\""),
+    ("apos", "'"),
+];
+
+/// Parses the given command-line arguments (excluding the program name).
+pub fn parse_args<I>(args: I) -> Result<Invocation, CliError>
+where
+    I: IntoIterator<Item = OsString>,
+{
+    let mut args = args.into_iter();
+    let mut options = Options::default();
+    let mut command = None;
+    let mut inputs = vec![];
+    let mut only_inputs = false;
+
+    while let Some(arg) = args.next() {
+        let arg = match arg.into_string() {
+            Ok(arg) => arg,
+            // Non-UTF-8 arguments can only be file names
+            Err(path) if command.is_some() => {
+                inputs.push(Input::File(path.into()));
+                continue;
+            }
+            Err(arg) => {
+                return Err(CliError::Usage(format!(
+                    "invalid argument: {}",
+                    arg.to_string_lossy()
+                )))
+            }
+        };
+
+        if only_inputs || arg == "-" || !arg.starts_with('-') {
+            if command.is_none() {
+                command = Some(match arg.as_str() {
+                    "parse" => Command::Parse,
+                    "fmt" => Command::Fmt,
+                    "normalize" => Command::Normalize,
+                    "to-xml" => Command::ToXml,
+                    "to-json" => Command::ToJson,
+                    "check" => Command::Check,
+                    "validate" => Command::Validate,
+                    "query" => Command::Query(
+                        next_string(&mut args)
+                            .ok_or_else(|| CliError::Usage("query: missing PATH".to_owned()))?,
+                    ),
+                    "help" => return Ok(Invocation::Help),
+                    _ => return Err(CliError::Usage(format!("unknown command: {}", arg))),
+                });
+            } else if arg == "-" {
+                inputs.push(Input::Stdin);
+            } else {
+                inputs.push(Input::File(arg.into()));
+            }
+            continue;
+        }
+
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag, Some(value.to_owned())),
+            _ => (arg.as_str(), None),
+        };
+        let value = |args: &mut I::IntoIter| {
+            inline_value
+                .clone()
+                .or_else(|| next_string(args))
+                .ok_or_else(|| CliError::Usage(format!("{}: missing value", flag)))
+        };
+
+        match flag {
+            "--" => only_inputs = true,
+            "-h" | "--help" => return Ok(Invocation::Help),
+            "--lowercase-names" => options.name_normalization = NameNormalization::ToLowercase,
+            "--uppercase-names" => options.name_normalization = NameNormalization::ToUppercase,
+            "--keep-whitespace" => options.whitespace_handling = WhitespaceHandling::Keep,
+            "--record-boundaries" => {
+                options.whitespace_handling = WhitespaceHandling::RecordBoundaries
+            }
+            "--marked-sections" => {
+                options.marked_section_handling = match value(&mut args)?.as_str() {
+                    "keep" => MarkedSectionHandling::KeepUnmodified,
+                    "cdata" => MarkedSectionHandling::AcceptOnlyCharacterData,
+                    "all" => MarkedSectionHandling::ExpandAll,
+                    "parsed" => MarkedSectionHandling::KeepParsed,
+                    mode => {
+                        return Err(CliError::Usage(format!(
+                            "unknown marked section mode: {}",
+                            mode
+                        )))
+                    }
+                }
+            }
+            "--entities" => options.add_entity_set(&value(&mut args)?)?,
+            "--entity" => {
+                let (name, value) = split_definition(flag, &value(&mut args)?)?;
+                options.entities.insert(name, value);
+            }
+            "--parameter-entity" => {
+                let (name, value) = split_definition(flag, &value(&mut args)?)?;
+                options.parameter_entities.insert(name, value);
+            }
+            "--ignore-markup-declarations" => options.ignore_markup_declarations = true,
+            "--ignore-processing-instructions" => options.ignore_processing_instructions = true,
+            "--keep-comments" => options.keep_comments = true,
+            "--function-character" => {
+                let (name, value) = split_definition(flag, &value(&mut args)?)?;
+                options
+                    .function_characters
+                    .push((name, parse_character(flag, &value)?));
+            }
+            "--pass" => options.passes.push(match value(&mut args)?.as_str() {
+                "normalize-end-tags" => Pass::NormalizeEndTags,
+                "lowercase-names" => Pass::NormalizeNames(NameNormalization::ToLowercase),
+                "uppercase-names" => Pass::NormalizeNames(NameNormalization::ToUppercase),
+                "drop-markup-declarations" => Pass::DropMarkupDeclarations,
+                "drop-processing-instructions" => Pass::DropProcessingInstructions,
+                pass => return Err(CliError::Usage(format!("unknown pass: {}", pass))),
+            }),
+            "--rename-element" => {
+                let (from, to) = split_definition(flag, &value(&mut args)?)?;
+                options.passes.push(Pass::RenameElement(from, to));
+            }
+            "--dtd" => options.dtd = Some(value(&mut args)?.into()),
+            "--indent" => {
+                options.indent = Some(match value(&mut args)?.as_str() {
+                    "tab" => "\t".to_owned(),
+                    n => " ".repeat(parse_number(flag, n)?),
+                })
+            }
+            "--wrap-attributes" => {
+                options.attribute_wrapping = match parse_number(flag, &value(&mut args)?)? {
+                    0 => AttributeWrapping::Always,
+                    width => AttributeWrapping::MaxWidth(width),
+                }
+            }
+            "--omit-end-tags" => options.omit_end_tags = true,
+            _ => return Err(CliError::Usage(format!("unknown option: {}", flag))),
+        }
+    }
+
+    let command = command.ok_or_else(|| CliError::Usage("missing command".to_owned()))?;
+    if inputs.is_empty() {
+        inputs.push(Input::Stdin);
+    }
+
+    Ok(Invocation::Run {
+        command,
+        options: Box::new(options),
+        inputs,
+    })
+}
+
+fn next_string(args: &mut impl Iterator<Item = OsString>) -> Option<String> {
+    args.next().map(|arg| arg.to_string_lossy().into_owned())
+}
+
+fn parse_number(flag: &str, value: &str) -> Result<usize, CliError> {
+    value
+        .parse()
+        .map_err(|_| CliError::Usage(format!("{}: expected a number, got {:?}", flag, value)))
+}
+
+/// Parses a character given either literally (`é`) or as a decimal code point (`233`).
+fn parse_character(flag: &str, value: &str) -> Result<char, CliError> {
+    let mut chars = value.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(c),
+        _ => value.parse().ok().and_then(char::from_u32).ok_or_else(|| {
+            CliError::Usage(format!(
+                "{}: expected a character or a code point, got {:?}",
+                flag, value
+            ))
+        }),
+    }
+}
+
+fn split_definition(flag: &str, definition: &str) -> Result<(String, String), CliError> {
+    match definition.split_once('=') {
+        Some((name, value)) if !name.is_empty() => Ok((name.to_owned(), value.to_owned())),
+        _ => Err(CliError::Usage(format!(
+            "{}: expected NAME=VALUE, got {:?}",
+            flag, definition
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Invocation, CliError> {
+        parse_args(args.iter().map(OsString::from))
+    }
+
+    fn run(args: &[&str]) -> (Command, Options, Vec<Input>) {
+        match parse(args).unwrap() {
+            Invocation::Run {
+                command,
+                options,
+                inputs,
+            } => (command, *options, inputs),
+            Invocation::Help => panic!("unexpected help"),
+        }
+    }
+
+    #[test]
+    fn test_parse_args_defaults() {
+        let (command, options, inputs) = run(&["parse"]);
+        assert_eq!(command, Command::Parse);
+        assert_eq!(options.whitespace_handling, WhitespaceHandling::Trim);
+        assert_eq!(options.name_normalization, NameNormalization::Unchanged);
+        assert_eq!(
+            options.marked_section_handling,
+            MarkedSectionHandling::AcceptOnlyCharacterData
+        );
+        assert_eq!(inputs, vec![Input::Stdin]);
+    }
+
+    #[test]
+    fn test_parse_args_options() {
+        let (command, options, inputs) = run(&[
+            "query",
+            "A/B",
+            "--uppercase-names",
+            "--keep-whitespace",
+            "--marked-sections=all",
+            "--entities",
+            "xml",
+            "--entity",
+            "eacute=é",
+            "--parameter-entity=draft=IGNORE",
+            "--indent=tab",
+            "--wrap-attributes",
+            "0",
+            "a.sgml",
+            "-",
+        ]);
+        assert_eq!(command, Command::Query("A/B".to_owned()));
+        assert_eq!(options.name_normalization, NameNormalization::ToUppercase);
+        assert_eq!(options.whitespace_handling, WhitespaceHandling::Keep);
+        assert_eq!(
+            options.marked_section_handling,
+            MarkedSectionHandling::ExpandAll
+        );
+        assert_eq!(options.entities.get("amp").map(String::as_str), Some("&"));
+        assert_eq!(
+            options.entities.get("eacute").map(String::as_str),
+            Some("é")
+        );
+        assert_eq!(
+            options.parameter_entities.get("draft").map(String::as_str),
+            Some("IGNORE")
+        );
+        assert_eq!(options.indent.as_deref(), Some("\t"));
+        assert_eq!(options.attribute_wrapping, AttributeWrapping::Always);
+        assert_eq!(inputs, vec![Input::File("a.sgml".into()), Input::Stdin]);
+    }
+
+    #[test]
+    fn test_parse_args_validate() {
+        let (command, options, inputs) = run(&[
+            "validate",
+            "--dtd=doc.dtd",
+            "--function-character",
+            "ZWSP=8203",
+            "--function-character=TAB=\t",
+            "--pass",
+            "normalize-end-tags",
+            "--rename-element=P=PARA",
+            "--pass=lowercase-names",
+            "doc.sgml",
+        ]);
+        assert_eq!(command, Command::Validate);
+        assert_eq!(options.dtd, Some("doc.dtd".into()));
+        assert_eq!(
+            options.function_characters,
+            vec![("ZWSP".to_owned(), '\u{200b}'), ("TAB".to_owned(), '\t')]
+        );
+        assert_eq!(
+            options.passes,
+            vec![
+                Pass::NormalizeEndTags,
+                Pass::RenameElement("P".to_owned(), "PARA".to_owned()),
+                Pass::NormalizeNames(NameNormalization::ToLowercase),
+            ]
+        );
+        assert_eq!(inputs, vec![Input::File("doc.sgml".into())]);
+    }
+
+    #[test]
+    fn test_parse_args_errors() {
+        assert!(matches!(parse(&[]), Err(CliError::Usage(_))));
+        assert!(matches!(parse(&["frobnicate"]), Err(CliError::Usage(_))));
+        assert!(matches!(parse(&["query"]), Err(CliError::Usage(_))));
+        assert!(matches!(
+            parse(&["parse", "--entity", "foo"]),
+            Err(CliError::Usage(_))
+        ));
+        assert!(matches!(
+            parse(&["parse", "--entities", "html"]),
+            Err(CliError::Usage(_))
+        ));
+        assert!(matches!(
+            parse(&["parse", "--bogus"]),
+            Err(CliError::Usage(_))
+        ));
+        assert!(matches!(
+            parse(&["parse", "--function-character", "RE=ab"]),
+            Err(CliError::Usage(_))
+        ));
+        assert!(matches!(
+            parse(&["parse", "--pass", "frobnicate"]),
+            Err(CliError::Usage(_))
+        ));
+        assert!(matches!(
+            parse(&["fmt", "--indent", "x"]),
+            Err(CliError::Usage(_))
+        ));
+        assert!(matches!(parse(&["parse", "--help"]), Ok(Invocation::Help)));
+    }
+}
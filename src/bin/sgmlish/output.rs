@@ -0,0 +1,212 @@
+//! Conversion of document trees into other formats.
+
+use std::io::{self, Write};
+
+use crate::tree::{Element, Node};
+
+const INDENT: &str = "  ";
+
+/// Writes the nodes as an XML document.
+///
+/// Elements containing only other elements are indented;
+/// elements with any text content are written inline, as to not change their content.
+/// Attributes without a value are written as `name="name"`.
+pub fn write_xml<W: Write>(out: &mut W, roots: &[Node]) -> io::Result<()> {
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    for node in roots {
+        write_xml_node(out, node, Some(0))?;
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+fn write_xml_node<W: Write>(out: &mut W, node: &Node, level: Option<usize>) -> io::Result<()> {
+    let element = match node {
+        Node::Text(text) => return write_xml_escaped(out, text, false),
+        Node::Element(element) => element,
+    };
+
+    write!(out, "<{}", element.name)?;
+    for (name, value) in &element.attributes {
+        write!(out, " {}=\"", name)?;
+        write_xml_escaped(out, value.as_deref().unwrap_or(name), true)?;
+        write!(out, "\"")?;
+    }
+    if element.children.is_empty() {
+        return write!(out, "/>");
+    }
+    write!(out, ">")?;
+
+    let child_level = level.filter(|_| is_element_only(element)).map(|n| n + 1);
+    for child in &element.children {
+        if let Some(child_level) = child_level {
+            writeln!(out)?;
+            write_indent(out, child_level)?;
+        }
+        write_xml_node(out, child, child_level)?;
+    }
+    if let (Some(level), Some(_)) = (level, child_level) {
+        writeln!(out)?;
+        write_indent(out, level)?;
+    }
+    write!(out, "</{}>", element.name)
+}
+
+fn write_xml_escaped<W: Write>(out: &mut W, text: &str, attribute: bool) -> io::Result<()> {
+    for c in text.chars() {
+        match c {
+            '&' => out.write_all(b"&amp;")?,
+            '<' => out.write_all(b"&lt;")?,
+            '>' => out.write_all(b"&gt;")?,
+            '"' if attribute => out.write_all(b"&quot;")?,
+            c => write!(out, "{}", c)?,
+        }
+    }
+    Ok(())
+}
+
+/// Writes the nodes as a JSON array.
+///
+/// Elements are represented as objects with `name`, `attributes` and `children` keys;
+/// text is represented as strings.
+pub fn write_json<W: Write>(out: &mut W, roots: &[Node]) -> io::Result<()> {
+    write_json_nodes(out, roots, 0)?;
+    writeln!(out)
+}
+
+fn write_json_nodes<W: Write>(out: &mut W, nodes: &[Node], level: usize) -> io::Result<()> {
+    if nodes.is_empty() {
+        return write!(out, "[]");
+    }
+    write!(out, "[")?;
+    for (i, node) in nodes.iter().enumerate() {
+        if i > 0 {
+            write!(out, ",")?;
+        }
+        writeln!(out)?;
+        write_indent(out, level + 1)?;
+        match node {
+            Node::Text(text) => write_json_string(out, text)?,
+            Node::Element(element) => write_json_element(out, element, level + 1)?,
+        }
+    }
+    writeln!(out)?;
+    write_indent(out, level)?;
+    write!(out, "]")
+}
+
+fn write_json_element<W: Write>(out: &mut W, element: &Element, level: usize) -> io::Result<()> {
+    writeln!(out, "{{")?;
+    write_indent(out, level + 1)?;
+    write!(out, "\"name\": ")?;
+    write_json_string(out, &element.name)?;
+    writeln!(out, ",")?;
+
+    write_indent(out, level + 1)?;
+    write!(out, "\"attributes\": {{")?;
+    for (i, (name, value)) in element.attributes.iter().enumerate() {
+        if i > 0 {
+            write!(out, ", ")?;
+        }
+        write_json_string(out, name)?;
+        write!(out, ": ")?;
+        match value {
+            Some(value) => write_json_string(out, value)?,
+            None => write!(out, "null")?,
+        }
+    }
+    writeln!(out, "}},")?;
+
+    write_indent(out, level + 1)?;
+    write!(out, "\"children\": ")?;
+    write_json_nodes(out, &element.children, level + 1)?;
+    writeln!(out)?;
+    write_indent(out, level)?;
+    write!(out, "}}")
+}
+
+fn write_json_string<W: Write>(out: &mut W, s: &str) -> io::Result<()> {
+    write!(out, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(out, "\\\"")?,
+            '\\' => write!(out, "\\\\")?,
+            '\n' => write!(out, "\\n")?,
+            '\r' => write!(out, "\\r")?,
+            '\t' => write!(out, "\\t")?,
+            c if c.is_control() => write!(out, "\\u{:04x}", c as u32)?,
+            c => write!(out, "{}", c)?,
+        }
+    }
+    write!(out, "\"")
+}
+
+fn is_element_only(element: &Element) -> bool {
+    element
+        .children
+        .iter()
+        .all(|child| matches!(child, Node::Element(_)))
+}
+
+fn write_indent<W: Write>(out: &mut W, level: usize) -> io::Result<()> {
+    for _ in 0..level {
+        out.write_all(INDENT.as_bytes())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tree;
+
+    fn convert(input: &str, f: fn(&mut Vec<u8>, &[Node]) -> io::Result<()>) -> String {
+        let fragment = sgmlish::parse(input).unwrap();
+        let fragment = sgmlish::transforms::normalize_end_tags(fragment).unwrap();
+        let roots = tree::build(fragment).unwrap();
+        let mut out = vec![];
+        f(&mut out, &roots).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_write_xml() {
+        assert_eq!(
+            convert(
+                r#"<LIST CHECKED><ITEM NAME='a"b'>x &#60; y<ITEM>z<BR></LIST>"#,
+                write_xml,
+            ),
+            concat!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+                "<LIST CHECKED=\"CHECKED\">\n",
+                "  <ITEM NAME=\"a&quot;b\">x &lt; y</ITEM>\n",
+                "  <ITEM>z</ITEM>\n",
+                "  <BR/>\n",
+                "</LIST>\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_write_json() {
+        assert_eq!(
+            convert(r#"<A HREF="x" NOHREF>"quoted"<BR></A>"#, write_json),
+            concat!(
+                "[\n",
+                "  {\n",
+                "    \"name\": \"A\",\n",
+                "    \"attributes\": {\"HREF\": \"x\", \"NOHREF\": null},\n",
+                "    \"children\": [\n",
+                "      \"\\\"quoted\\\"\",\n",
+                "      {\n",
+                "        \"name\": \"BR\",\n",
+                "        \"attributes\": {},\n",
+                "        \"children\": []\n",
+                "      }\n",
+                "    ]\n",
+                "  }\n",
+                "]\n",
+            )
+        );
+    }
+}
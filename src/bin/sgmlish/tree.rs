@@ -0,0 +1,172 @@
+//! A minimal element tree, for commands that need to look at documents hierarchically.
+
+use std::borrow::Cow;
+
+use sgmlish::{SgmlEvent, SgmlFragment};
+
+use crate::CliError;
+
+/// A node in the document tree.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Node<'a> {
+    Element(Element<'a>),
+    Text(Cow<'a, str>),
+}
+
+/// An element and all its content.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Element<'a> {
+    pub name: Cow<'a, str>,
+    pub attributes: Vec<(Cow<'a, str>, Option<Cow<'a, str>>)>,
+    pub children: Vec<Node<'a>>,
+}
+
+impl<'a> Element<'a> {
+    /// Returns the text content of this element and all its descendants.
+    pub fn text(&self) -> String {
+        let mut out = String::new();
+        self.collect_text(&mut out);
+        out
+    }
+
+    fn collect_text(&self, out: &mut String) {
+        for child in &self.children {
+            match child {
+                Node::Element(element) => element.collect_text(out),
+                Node::Text(text) => out.push_str(text),
+            }
+        }
+    }
+}
+
+/// Builds a tree from a fragment with balanced tags.
+///
+/// Markup declarations, processing instructions and comments are dropped;
+/// unexpanded marked sections are rejected.
+pub fn build(fragment: SgmlFragment) -> Result<Vec<Node>, CliError> {
+    let mut stack: Vec<Element> = vec![];
+    let mut roots = vec![];
+
+    for event in fragment {
+        let node = match event {
+            SgmlEvent::OpenStartTag { name } => {
+                stack.push(Element {
+                    name,
+                    attributes: vec![],
+                    children: vec![],
+                });
+                continue;
+            }
+            SgmlEvent::Attribute { name, value } => {
+                if let Some(element) = stack.last_mut() {
+                    element.attributes.push((name, value));
+                }
+                continue;
+            }
+            SgmlEvent::CloseStartTag
+            | SgmlEvent::MarkupDeclaration { .. }
+            | SgmlEvent::ProcessingInstruction(_)
+            | SgmlEvent::Comment(_) => continue,
+            SgmlEvent::MarkedSection { .. } | SgmlEvent::ParsedMarkedSection { .. } => {
+                return Err(CliError::Unsupported(
+                    "unexpanded marked sections; try `--marked-sections all`".to_owned(),
+                ))
+            }
+            SgmlEvent::XmlCloseEmptyElement | SgmlEvent::EndTag { .. } => match stack.pop() {
+                Some(element) => match &event {
+                    SgmlEvent::EndTag { name } if !name.is_empty() && *name != element.name => {
+                        return Err(CliError::Unsupported(format!(
+                            "mismatched end tag: expected </{}>, found {}",
+                            element.name, event
+                        )));
+                    }
+                    _ => Node::Element(element),
+                },
+                None => {
+                    return Err(CliError::Unsupported(format!(
+                        "unpaired end tag: {}",
+                        event
+                    )));
+                }
+            },
+            SgmlEvent::Character(text) => Node::Text(text),
+            event => {
+                return Err(CliError::Unsupported(format!(
+                    "unsupported event: {:?}",
+                    event
+                )))
+            }
+        };
+        match stack.last_mut() {
+            Some(parent) => parent.children.push(node),
+            None => roots.push(node),
+        }
+    }
+
+    if let Some(element) = stack.pop() {
+        return Err(CliError::Unsupported(format!(
+            "unclosed element: <{}>",
+            element.name
+        )));
+    }
+    Ok(roots)
+}
+
+/// Finds all elements matching a `/`-separated path of element names,
+/// where `*` matches any name.
+pub fn query<'t, 'a>(roots: &'t [Node<'a>], path: &str) -> Vec<&'t Element<'a>> {
+    let mut current = roots
+        .iter()
+        .filter_map(|node| match node {
+            Node::Element(element) => Some(element),
+            Node::Text(_) => None,
+        })
+        .collect::<Vec<_>>();
+    let mut steps = path.split('/').filter(|step| !step.is_empty()).peekable();
+
+    while let Some(step) = steps.next() {
+        current.retain(|element| step == "*" || element.name == step);
+        if steps.peek().is_some() {
+            current = current
+                .into_iter()
+                .flat_map(|element| &element.children)
+                .filter_map(|node| match node {
+                    Node::Element(element) => Some(element),
+                    Node::Text(_) => None,
+                })
+                .collect();
+        }
+    }
+
+    current
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_and_query() {
+        let fragment =
+            sgmlish::parse("<A X=1><B>one</B><C><B>two</B></C><B>three</B></A>").unwrap();
+        let roots = build(fragment).unwrap();
+        assert_eq!(roots.len(), 1);
+
+        let texts = |path| {
+            query(&roots, path)
+                .into_iter()
+                .map(Element::text)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(texts("A/B"), vec!["one", "three"]);
+        assert_eq!(texts("/A/*/B"), vec!["two"]);
+        assert_eq!(texts("A"), vec!["onetwothree"]);
+        assert!(texts("B").is_empty());
+    }
+
+    #[test]
+    fn test_build_unbalanced() {
+        let fragment = sgmlish::parse("<A><B>one</A>").unwrap();
+        assert!(build(fragment).is_err());
+    }
+}
@@ -9,16 +9,56 @@ use serde::de::{self, IntoDeserializer, Unexpected};
 use serde::Deserializer;
 
 use crate::de::buffer::CowBuffer;
+use crate::marked_sections::MarkedSectionContent;
+use crate::transforms::WhitespacePolicy;
 use crate::{SgmlEvent, SgmlFragment};
 
 mod buffer;
 
+/// The prefix of struct fields that only receive attributes, as in `#[serde(rename = "@ID")]`.
+///
+/// Along with [`TEXT_KEY`] and [`VALUE_KEY`], this is the naming convention for mapping
+/// elements to structs, meant to be followed both when reading and writing documents.
+pub const ATTRIBUTE_PREFIX: &str = "@";
+
+/// The name of the struct field receiving the text of an element alongside its
+/// attributes and child elements, and of the enum variant for text in mixed content.
+pub const TEXT_KEY: &str = "$text";
+
+/// The name of the struct field receiving the entire content of an element,
+/// either its text or, for mixed content, a sequence of its elements and text.
+pub const VALUE_KEY: &str = "$value";
+
 /// Deserializes an instance of type `T` from the given [`SgmlFragment`].
 ///
 /// Before invoking, make sure the content is *tag-valid* and consistently cased.
 /// That means all start tags must have a matching end tag with identical case,
 /// in a consistent hierarchy.
 ///
+/// Markup declarations (like `<!DOCTYPE>`) and processing instructions (`<?example>`)
+/// are rejected, unless skipped through [`DeserializerOptions`], or captured by
+/// a struct with a `$declarations` or `$pi` field, respectively.
+/// These fields receive a sequence of strings with the markup found in the element
+/// (and, for the root element, before it) that was not captured by an inner struct.
+///
+/// Mixed content, with both text and elements (as in `Hello <B>world</B>!`), can be
+/// deserialized into a `$value` sequence of enums, in document order: elements become
+/// variants with the same name, and text runs become the `$text` variant.
+///
+/// Attributes and child elements share the same names. When both may appear with the
+/// same name, fields can be renamed with an `@` prefix to only receive the attribute
+/// (`#[serde(rename = "@ID")]`), and a `$text` field receives the element's text
+/// alongside its child elements and attributes. The same convention is meant to be
+/// followed when writing documents out; see [`ATTRIBUTE_PREFIX`], [`TEXT_KEY`] and
+/// [`VALUE_KEY`].
+///
+/// Flattened fields (`#[serde(flatten)]`), internally tagged enums (`#[serde(tag = "...")]`)
+/// and untagged enums are buffered by serde before reaching their fields, so their text
+/// is interpreted without knowing the target type, and kept as a string by default.
+/// As numbers cannot be recognized per field there, numeric fields in these contexts
+/// need either [`from_text`], or [`DeserializerOptions::infer_numbers`] for the whole
+/// document.
+///
 /// # Example
 ///
 /// ```rust
@@ -70,8 +110,354 @@ pub fn from_fragment<'de, T>(fragment: SgmlFragment<'de>) -> Result<T, Deseriali
 where
     T: de::Deserialize<'de>,
 {
-    let mut reader = SgmlDeserializer::from_fragment(fragment)?;
-    T::deserialize(&mut reader)
+    from_fragment_with_options(fragment, DeserializerOptions::default())
+}
+
+/// Deserializes an instance of type `T` from the given [`SgmlFragment`],
+/// using the given options.
+///
+/// See [`from_fragment`] for the requirements on the content.
+pub fn from_fragment_with_options<'de, T>(
+    fragment: SgmlFragment<'de>,
+    options: DeserializerOptions,
+) -> Result<T, DeserializationError>
+where
+    T: de::Deserialize<'de>,
+{
+    let mut reader = SgmlDeserializer::from_fragment_with_options(fragment, options)?;
+    let root = match reader.peek() {
+        Ok(SgmlEvent::OpenStartTag { name }) => Some(name.to_string()),
+        _ => None,
+    };
+    let value = T::deserialize(&mut reader).map_err(|err| match root {
+        Some(root) => err.at(&root),
+        None => err,
+    })?;
+    reader.end()?;
+    Ok(value)
+}
+
+/// Deserializes a value from its text with [`FromStr`](std::str::FromStr),
+/// for use with `#[serde(deserialize_with = "sgmlish::de::from_text")]`.
+///
+/// Inside flattened structs and internally tagged or untagged enums, text reaches
+/// fields as a string, or as a number if it looks like one and
+/// [`DeserializerOptions::infer_numbers`] is enabled. This accepts either form,
+/// so numeric fields work without inferring numbers, and string fields keep working
+/// when numbers are inferred.
+///
+/// # Example
+///
+/// ```rust
+/// use serde::Deserialize;
+///
+/// #[derive(Debug, Deserialize)]
+/// struct Transaction {
+///     #[serde(flatten)]
+///     amount: Amount,
+/// }
+///
+/// #[derive(Debug, Deserialize)]
+/// struct Amount {
+///     #[serde(deserialize_with = "sgmlish::de::from_text")]
+///     cents: u32,
+/// }
+///
+/// # fn main() -> sgmlish::Result<()> {
+/// let sgml = sgmlish::parse("<transaction><cents>700</cents></transaction>")?;
+/// let transaction = sgmlish::from_fragment::<Transaction>(sgml)?;
+/// assert_eq!(transaction.amount.cents, 700);
+/// # Ok(())
+/// # }
+/// ```
+pub fn from_text<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: de::Deserializer<'de>,
+    T: std::str::FromStr,
+    T::Err: fmt::Display,
+{
+    struct TextVisitor<T>(std::marker::PhantomData<T>);
+
+    impl<'de, T> de::Visitor<'de> for TextVisitor<T>
+    where
+        T: std::str::FromStr,
+        T::Err: fmt::Display,
+    {
+        type Value = T;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("text")
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<T, E> {
+            v.parse().map_err(E::custom)
+        }
+
+        fn visit_bool<E: de::Error>(self, v: bool) -> Result<T, E> {
+            self.visit_str(&v.to_string())
+        }
+
+        fn visit_i64<E: de::Error>(self, v: i64) -> Result<T, E> {
+            self.visit_str(&v.to_string())
+        }
+
+        fn visit_u64<E: de::Error>(self, v: u64) -> Result<T, E> {
+            self.visit_str(&v.to_string())
+        }
+
+        fn visit_f64<E: de::Error>(self, v: f64) -> Result<T, E> {
+            self.visit_str(&v.to_string())
+        }
+    }
+
+    deserializer.deserialize_any(TextVisitor(std::marker::PhantomData))
+}
+
+/// Options for customizing deserialization.
+///
+/// Options can also be set with a fluent interface; see [`DeserializerBuilder`].
+#[derive(Clone, Debug)]
+pub struct DeserializerOptions {
+    /// Skips markup declarations (like `<!DOCTYPE>` or `<!USEMAP>`) found anywhere
+    /// in the content, instead of rejecting them.
+    ///
+    /// To drop them before they ever reach the deserializer, see
+    /// [`ParserBuilder::ignore_markup_declarations`](crate::parser::ParserBuilder::ignore_markup_declarations).
+    pub skip_markup_declarations: bool,
+    /// Skips processing instructions (`<?example>`) found anywhere in the content,
+    /// instead of rejecting them.
+    pub skip_processing_instructions: bool,
+    /// Skips marked sections, instead of rejecting them.
+    ///
+    /// The parsed content of `INCLUDE` sections kept with
+    /// [`MarkedSectionHandling::KeepParsed`](crate::parser::MarkedSectionHandling::KeepParsed)
+    /// is deserialized in place of the section; any other section is dropped entirely.
+    pub skip_marked_sections: bool,
+    /// Matches element and attribute names against struct fields without regard to case.
+    ///
+    /// End tags are also matched to their start tags without regard to case.
+    /// Names are only compared against the fields of structs;
+    /// keys in maps are kept as they appear in the content.
+    pub case_insensitive_keys: bool,
+    /// Values accepted as `true` for booleans, compared without regard to case.
+    ///
+    /// Attributes without a value (`<OPTION SELECTED>`) are always accepted as `true`.
+    pub true_values: Vec<String>,
+    /// Values accepted as `false` for booleans, compared without regard to case.
+    pub false_values: Vec<String>,
+    /// Deserializes empty elements and empty attribute values as `None` for optional fields.
+    pub empty_as_none: bool,
+    /// Trims whitespace surrounding the text of elements.
+    pub trim_text: bool,
+    /// Rejects elements that do not match any field when deserializing structs.
+    ///
+    /// Structs with [`#[serde(flatten)]`](https://serde.rs/field-attrs.html#flatten) fields
+    /// are deserialized as maps, so they are never checked.
+    pub strict: bool,
+    /// Recognizes numbers in text when the target type is not known in advance.
+    ///
+    /// This is the case for flattened structs, internally tagged enums and untagged
+    /// enums, which serde buffers before deserializing their fields.
+    /// Only integers and decimals written in their shortest form (like `-12` or `1.5`,
+    /// but not `012` or `1.50`) are recognized; everything else remains a string.
+    /// Fields whose type is known beforehand are not affected.
+    ///
+    /// Disabled by default, as string fields in the same contexts then fail to
+    /// deserialize numeric-looking text, unless they use [`from_text`].
+    pub infer_numbers: bool,
+    /// Applies per-element whitespace handling to the text before deserializing.
+    ///
+    /// This is how fields can have their whitespace preserved while other text is
+    /// trimmed; see [`WhitespacePolicy`] for the requirements on the fragment.
+    pub whitespace_policy: Option<WhitespacePolicy>,
+    /// Presents all attributes with an `@` prefix (like `@ID`), so they can be told
+    /// apart from child elements with the same name.
+    ///
+    /// Struct fields named with the prefix (`#[serde(rename = "@ID")]`) always receive
+    /// the attribute; this is only needed for maps and flattened structs, which do not
+    /// declare their fields.
+    pub prefix_attributes: bool,
+}
+
+impl Default for DeserializerOptions {
+    fn default() -> Self {
+        DeserializerOptions {
+            skip_markup_declarations: false,
+            skip_processing_instructions: false,
+            skip_marked_sections: false,
+            case_insensitive_keys: false,
+            true_values: vec!["1".to_owned(), "true".to_owned()],
+            false_values: vec!["0".to_owned(), "false".to_owned()],
+            empty_as_none: false,
+            trim_text: false,
+            strict: false,
+            infer_numbers: false,
+            whitespace_policy: None,
+            prefix_attributes: false,
+        }
+    }
+}
+
+impl DeserializerOptions {
+    /// Creates a new builder for deserializer options.
+    pub fn builder() -> DeserializerBuilder {
+        DeserializerBuilder::new()
+    }
+}
+
+/// A fluent interface for configuring deserializers.
+///
+/// # Example
+///
+/// ```rust
+/// use serde::Deserialize;
+///
+/// #[derive(Debug, Deserialize)]
+/// struct Account {
+///     acctid: String,
+///     nickname: Option<String>,
+///     closed: bool,
+/// }
+///
+/// # fn main() -> sgmlish::Result<()> {
+/// let sgml = sgmlish::parse("<ACCOUNT><ACCTID>123</ACCTID><NICKNAME></NICKNAME><CLOSED>N</CLOSED></ACCOUNT>")?;
+/// let account = sgmlish::de::DeserializerOptions::builder()
+///     .case_insensitive_keys(true)
+///     .boolean_spellings(&["Y"], &["N"])
+///     .empty_as_none(true)
+///     .deserialize::<Account>(sgml)?;
+///
+/// assert_eq!(account.acctid, "123");
+/// assert_eq!(account.nickname, None);
+/// assert!(!account.closed);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct DeserializerBuilder {
+    options: DeserializerOptions,
+}
+
+impl DeserializerBuilder {
+    /// Creates a new builder.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Skips markup declarations instead of rejecting them.
+    ///
+    /// See [`DeserializerOptions::skip_markup_declarations`].
+    pub fn skip_markup_declarations(mut self, skip: bool) -> Self {
+        self.options.skip_markup_declarations = skip;
+        self
+    }
+
+    /// Skips processing instructions instead of rejecting them.
+    ///
+    /// See [`DeserializerOptions::skip_processing_instructions`].
+    pub fn skip_processing_instructions(mut self, skip: bool) -> Self {
+        self.options.skip_processing_instructions = skip;
+        self
+    }
+
+    /// Skips marked sections instead of rejecting them.
+    ///
+    /// See [`DeserializerOptions::skip_marked_sections`].
+    pub fn skip_marked_sections(mut self, skip: bool) -> Self {
+        self.options.skip_marked_sections = skip;
+        self
+    }
+
+    /// Matches names against struct fields without regard to case.
+    ///
+    /// See [`DeserializerOptions::case_insensitive_keys`].
+    pub fn case_insensitive_keys(mut self, case_insensitive: bool) -> Self {
+        self.options.case_insensitive_keys = case_insensitive;
+        self
+    }
+
+    /// Accepts additional values as `true` and `false` for booleans,
+    /// besides the default `1`/`true` and `0`/`false`.
+    pub fn boolean_spellings(mut self, true_values: &[&str], false_values: &[&str]) -> Self {
+        let to_owned = |values: &[&str]| {
+            values
+                .iter()
+                .map(|&value| value.to_owned())
+                .collect::<Vec<_>>()
+        };
+        self.options.true_values.extend(to_owned(true_values));
+        self.options.false_values.extend(to_owned(false_values));
+        self
+    }
+
+    /// Deserializes empty elements as `None`.
+    ///
+    /// See [`DeserializerOptions::empty_as_none`].
+    pub fn empty_as_none(mut self, empty_as_none: bool) -> Self {
+        self.options.empty_as_none = empty_as_none;
+        self
+    }
+
+    /// Trims whitespace surrounding the text of elements.
+    ///
+    /// See [`DeserializerOptions::trim_text`].
+    pub fn trim_text(mut self, trim: bool) -> Self {
+        self.options.trim_text = trim;
+        self
+    }
+
+    /// Rejects elements that do not match any struct field.
+    ///
+    /// See [`DeserializerOptions::strict`].
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.options.strict = strict;
+        self
+    }
+
+    /// Recognizes numbers in text when the target type is not known in advance.
+    ///
+    /// See [`DeserializerOptions::infer_numbers`].
+    pub fn infer_numbers(mut self, infer: bool) -> Self {
+        self.options.infer_numbers = infer;
+        self
+    }
+
+    /// Presents all attributes with an `@` prefix.
+    ///
+    /// See [`DeserializerOptions::prefix_attributes`].
+    pub fn prefix_attributes(mut self, prefix: bool) -> Self {
+        self.options.prefix_attributes = prefix;
+        self
+    }
+
+    /// Applies per-element whitespace handling to the text before deserializing.
+    ///
+    /// See [`DeserializerOptions::whitespace_policy`].
+    pub fn whitespace_policy(mut self, policy: WhitespacePolicy) -> Self {
+        self.options.whitespace_policy = Some(policy);
+        self
+    }
+
+    /// Creates a deserializer for the given fragment.
+    pub fn build(
+        self,
+        fragment: SgmlFragment<'_>,
+    ) -> Result<SgmlDeserializer<'_>, DeserializationError> {
+        SgmlDeserializer::from_fragment_with_options(fragment, self.options)
+    }
+
+    /// Deserializes an instance of type `T` from the given fragment.
+    pub fn deserialize<'de, T>(self, fragment: SgmlFragment<'de>) -> Result<T, DeserializationError>
+    where
+        T: de::Deserialize<'de>,
+    {
+        from_fragment_with_options(fragment, self.options)
+    }
+
+    /// Returns the [`DeserializerOptions`] that were built using other methods.
+    pub fn into_options(self) -> DeserializerOptions {
+        self.options
+    }
 }
 
 /// A deserializer for SGML content.
@@ -81,10 +467,19 @@ pub struct SgmlDeserializer<'de> {
     stack: Vec<Cow<'de, str>>,
     map_key: Option<Rc<str>>,
     accumulated_text: Option<Cow<'de, str>>,
+    /// Whether `accumulated_text` is a run of text in mixed content
+    text_node: bool,
+    /// Markup declarations and processing instructions not yet captured by any struct
+    set_aside: Vec<SgmlEvent<'de>>,
+    options: DeserializerOptions,
 }
 
 /// The error type for deserialization problems.
+///
+/// New kinds of errors may be added in future versions,
+/// so matches on this enum should include a wildcard arm.
 #[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum DeserializationError {
     #[error("unexpected end of content")]
     UnexpectedEof,
@@ -94,6 +489,9 @@ pub enum DeserializationError {
     ExpectedStartTag,
     #[error("mismatched close tag: expected </{expected}>, found </{found}>")]
     MismatchedCloseTag { expected: String, found: String },
+    /// An element not matching any field was found in strict mode.
+    #[error("unknown element: <{0}>")]
+    UnknownElement(String),
     /// An event which is not supported by deserialization was found.
     #[error("deserialization of '{0}' is not supported")]
     Unsupported(SgmlEvent<'static>),
@@ -111,20 +509,133 @@ pub enum DeserializationError {
 
     #[error("{0}")]
     Message(String),
+
+    /// An error within a specific element or attribute.
+    ///
+    /// The path lists element names from the root, separated by `/`,
+    /// with the index of items in sequences in brackets
+    /// (as in `BANKTRANLIST/STMTTRN[412]/TRNAMT`), and attribute names prefixed with `@`.
+    ///
+    /// The path stops where serde buffers content before deserializing it, as it does
+    /// for flattened fields and internally tagged or untagged enums: an error in the
+    /// field of a flattened struct is reported at the element containing it.
+    #[error("at {path}: {source}")]
+    WithPath {
+        path: String,
+        source: Box<DeserializationError>,
+    },
+}
+
+impl DeserializationError {
+    /// Returns the path to the element or attribute where the error occurred, if known.
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            DeserializationError::WithPath { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+
+    /// Returns the error without its path information.
+    pub fn inner(&self) -> &DeserializationError {
+        match self {
+            DeserializationError::WithPath { source, .. } => source,
+            err => err,
+        }
+    }
+
+    /// Prepends an element or attribute name to the path.
+    fn at(self, name: &str) -> Self {
+        self.prepend_path(name, |path| {
+            if path.starts_with('[') {
+                format!("{}{}", name, path)
+            } else {
+                format!("{}/{}", name, path)
+            }
+        })
+    }
+
+    /// Prepends the index of an item in a sequence to the path.
+    fn at_index(self, index: usize) -> Self {
+        let segment = format!("[{}]", index);
+        self.prepend_path(&segment, |path| {
+            if path.starts_with('[') {
+                format!("{}{}", segment, path)
+            } else {
+                format!("{}/{}", segment, path)
+            }
+        })
+    }
+
+    fn prepend_path(self, segment: &str, join: impl FnOnce(&str) -> String) -> Self {
+        match self {
+            DeserializationError::WithPath { path, source } => DeserializationError::WithPath {
+                path: join(&path),
+                source,
+            },
+            err => DeserializationError::WithPath {
+                path: segment.to_owned(),
+                source: Box::new(err),
+            },
+        }
+    }
 }
 
 impl<'de> SgmlDeserializer<'de> {
     pub fn from_fragment(fragment: SgmlFragment<'de>) -> Result<Self, DeserializationError> {
+        Self::from_fragment_with_options(fragment, DeserializerOptions::default())
+    }
+
+    pub fn from_fragment_with_options(
+        fragment: SgmlFragment<'de>,
+        options: DeserializerOptions,
+    ) -> Result<Self, DeserializationError> {
+        let fragment = match &options.whitespace_policy {
+            Some(policy) => policy.apply(fragment),
+            None => fragment,
+        };
         let mut reader = SgmlDeserializer {
             events: fragment.into_vec().into_iter(),
             stack: Vec::new(),
             map_key: None,
             accumulated_text: None,
+            text_node: false,
+            set_aside: Vec::new(),
+            options,
         };
         reader.normalize_at_cursor()?;
         Ok(reader)
     }
 
+    /// Checks that all markup declarations and processing instructions seen
+    /// were either skipped or captured.
+    ///
+    /// This is called by [`from_fragment`]; when using the deserializer directly,
+    /// call it after deserializing the value.
+    pub fn end(&mut self) -> Result<(), DeserializationError> {
+        match self.set_aside.drain(..).next() {
+            Some(event) => Err(DeserializationError::Unsupported(event.into_owned())),
+            None => Ok(()),
+        }
+    }
+
+    /// Takes the events of the given kind set aside since `start`, as text.
+    fn capture(&mut self, capture: Capture, start: usize) -> Vec<String> {
+        let start = start.min(self.set_aside.len());
+        let (captured, kept) = self
+            .set_aside
+            .split_off(start)
+            .into_iter()
+            .partition(|event| capture.matches(event));
+        self.set_aside.extend::<Vec<_>>(kept);
+        captured
+            .into_iter()
+            .map(|event: SgmlEvent| match event {
+                SgmlEvent::ProcessingInstruction(pi) => pi.into_owned(),
+                event => event.to_string(),
+            })
+            .collect()
+    }
+
     fn advance(&mut self) -> Result<SgmlEvent<'de>, DeserializationError> {
         if let Some(next) = self.events.next() {
             self.normalize_at_cursor()?;
@@ -138,7 +649,7 @@ impl<'de> SgmlDeserializer<'de> {
         let current = self
             .events
             .as_slice()
-            .get(0)
+            .first()
             .ok_or(DeserializationError::UnexpectedEof)?;
         trace!("peeked: {:?}", current);
         Ok(current)
@@ -164,12 +675,12 @@ impl<'de> SgmlDeserializer<'de> {
             .skip(1)
             .find_map(|event| match event {
                 SgmlEvent::OpenStartTag { .. } => Some(true),
-                SgmlEvent::EndTag { .. } => Some(false),
+                SgmlEvent::EndTag { .. } | SgmlEvent::XmlCloseEmptyElement => Some(false),
                 SgmlEvent::Attribute { .. } => {
                     contains_attributes = true;
                     None
                 }
-                SgmlEvent::Character(text) if !text.is_empty() => {
+                SgmlEvent::Character(text) if !self.trim(text).is_empty() => {
                     contains_text = true;
                     None
                 }
@@ -186,17 +697,61 @@ impl<'de> SgmlDeserializer<'de> {
         Ok(content)
     }
 
-    /// Rejects unsupported events (like empty start tags), ignores markup declarations and processing instructions,
-    /// and ensures any `Data` is expanded
+    /// Rejects unsupported events (like empty start tags), skips comments,
+    /// and skips markup declarations, processing instructions and marked sections
+    /// if so configured.
+    ///
+    /// Markup declarations and processing instructions that are not skipped are set aside,
+    /// to be either captured by a struct or rejected by [`end`](SgmlDeserializer::end).
     fn normalize_at_cursor(&mut self) -> Result<(), DeserializationError> {
-        let event = match self.events.as_mut_slice().get_mut(0) {
-            Some(event) => event,
-            None => return Ok(()),
+        let event = loop {
+            match self.events.as_slice().first() {
+                // Comments never carry data, much like whitespace between elements
+                Some(SgmlEvent::Comment(_)) => {
+                    self.events.next();
+                }
+                Some(SgmlEvent::MarkupDeclaration { .. })
+                    if self.options.skip_markup_declarations =>
+                {
+                    self.events.next();
+                }
+                Some(SgmlEvent::ProcessingInstruction(_))
+                    if self.options.skip_processing_instructions =>
+                {
+                    self.events.next();
+                }
+                Some(SgmlEvent::ParsedMarkedSection {
+                    content: MarkedSectionContent::Events(_),
+                    ..
+                }) if self.options.skip_marked_sections => {
+                    if let Some(SgmlEvent::ParsedMarkedSection {
+                        content: MarkedSectionContent::Events(content),
+                        ..
+                    }) = self.events.next()
+                    {
+                        let rest = mem::replace(&mut self.events, Vec::new().into_iter());
+                        self.events = content
+                            .into_iter()
+                            .chain(rest)
+                            .collect::<Vec<_>>()
+                            .into_iter();
+                    }
+                }
+                Some(SgmlEvent::MarkedSection { .. } | SgmlEvent::ParsedMarkedSection { .. })
+                    if self.options.skip_marked_sections =>
+                {
+                    self.events.next();
+                }
+                Some(SgmlEvent::MarkupDeclaration { .. } | SgmlEvent::ProcessingInstruction(_)) => {
+                    let event = self.events.next().unwrap();
+                    self.set_aside.push(event);
+                }
+                Some(event) => break event,
+                None => return Ok(()),
+            }
         };
         match event {
-            SgmlEvent::MarkupDeclaration { .. }
-            | SgmlEvent::ProcessingInstruction(_)
-            | SgmlEvent::MarkedSection { .. } => {}
+            SgmlEvent::MarkedSection { .. } | SgmlEvent::ParsedMarkedSection { .. } => {}
             SgmlEvent::OpenStartTag { name } | SgmlEvent::EndTag { name } if name.is_empty() => {}
             _ => return Ok(()),
         }
@@ -226,6 +781,13 @@ impl<'de> SgmlDeserializer<'de> {
 
     /// Consumes all events until the current top of the stack is popped.
     fn pop_elt(&mut self) -> Result<(), DeserializationError> {
+        self.skip_to_end_of_elt()?;
+        self.normalize_within_element()
+    }
+
+    /// Consumes all events until the current top of the stack is popped,
+    /// without normalizing the cursor afterwards.
+    fn skip_to_end_of_elt(&mut self) -> Result<(), DeserializationError> {
         let stack_size = self.stack.len();
         trace!(
             "popping({}): {:?}",
@@ -245,7 +807,7 @@ impl<'de> SgmlDeserializer<'de> {
                 SgmlEvent::EndTag { name } => {
                     self.check_stack_size(stack_size);
                     let expected = self.stack.pop().unwrap();
-                    if name != expected {
+                    if !self.names_match(&name, &expected) {
                         return Err(DeserializationError::MismatchedCloseTag {
                             expected: expected.to_string(),
                             found: name.to_string(),
@@ -256,13 +818,23 @@ impl<'de> SgmlDeserializer<'de> {
                 }
                 SgmlEvent::OpenStartTag { name } => {
                     self.stack.push(name);
-                    self.pop_elt()?;
+                    self.skip_to_end_of_elt()?;
                 }
                 _ => {}
             };
         }
     }
 
+    /// Normalizes the cursor, unless the root element was closed,
+    /// in which case any remaining events are ignored.
+    fn normalize_within_element(&mut self) -> Result<(), DeserializationError> {
+        if self.stack.is_empty() {
+            Ok(())
+        } else {
+            self.normalize_at_cursor()
+        }
+    }
+
     /// Skips attributes and CloseStartTag, going to the main content.
     ///
     /// Should only be used immediately after `push_elt`.
@@ -273,15 +845,35 @@ impl<'de> SgmlDeserializer<'de> {
         Ok(())
     }
 
+    /// Deserializes text whose type is not known, recognizing numbers if enabled.
+    fn deserialize_inferred<V>(&mut self, visitor: V) -> Result<V::Value, DeserializationError>
+    where
+        V: de::Visitor<'de>,
+    {
+        let text = self.consume_text()?;
+        if self.options.infer_numbers {
+            if let Some(value) = infer_number(&text) {
+                trace!("inferred number {:?} from {:?}", value, text);
+                return match value {
+                    Number::Unsigned(n) => visitor.visit_u64(n),
+                    Number::Signed(n) => visitor.visit_i64(n),
+                    Number::Float(n) => visitor.visit_f64(n),
+                };
+            }
+        }
+        match text {
+            Cow::Borrowed(s) => visitor.visit_borrowed_str(s),
+            Cow::Owned(s) => visitor.visit_string(s),
+        }
+    }
+
     /// Consumes an element and returns all its text.
     ///
     /// Includes text from child elements as well.
-    fn consume_text<'r, V: de::Visitor<'r>>(
-        &mut self,
-    ) -> Result<Cow<'de, str>, DeserializationError> {
+    fn consume_text(&mut self) -> Result<Cow<'de, str>, DeserializationError> {
         if let Some(accumulated_text) = self.accumulated_text.take() {
             debug!("consume_text accumulated");
-            return Ok(accumulated_text);
+            return Ok(self.trim_cow(accumulated_text));
         }
 
         debug!("consume_text");
@@ -302,7 +894,7 @@ impl<'de> SgmlDeserializer<'de> {
                 SgmlEvent::OpenStartTag { .. } => {
                     self.push_elt()?;
                 }
-                SgmlEvent::EndTag { .. } => {
+                SgmlEvent::EndTag { .. } | SgmlEvent::XmlCloseEmptyElement => {
                     self.pop_elt()?;
                     if self.stack.len() == starting_stack_size {
                         break;
@@ -319,20 +911,67 @@ impl<'de> SgmlDeserializer<'de> {
         }
 
         debug!("consumed text content: {:?}", text.as_str());
-        Ok(text.into_cow())
+        Ok(self.trim_cow(text.into_cow()))
+    }
+
+    /// Trims the text if so configured.
+    fn trim<'t>(&self, text: &'t str) -> &'t str {
+        if self.options.trim_text {
+            text.trim_matches(crate::text::is_sgml_whitespace)
+        } else {
+            text
+        }
+    }
+
+    fn trim_cow(&self, text: Cow<'de, str>) -> Cow<'de, str> {
+        match text {
+            Cow::Borrowed(text) => Cow::Borrowed(self.trim(text)),
+            Cow::Owned(text) if self.trim(&text).len() != text.len() => {
+                Cow::Owned(self.trim(&text).to_owned())
+            }
+            text => text,
+        }
+    }
+
+    /// Checks whether the next value is empty, for [`DeserializerOptions::empty_as_none`].
+    fn peek_is_empty(&self) -> Result<bool, DeserializationError> {
+        if let Some(text) = &self.accumulated_text {
+            return Ok(self.trim(text).is_empty());
+        }
+        match self.peek()? {
+            SgmlEvent::Attribute { value, .. } => {
+                Ok(value.as_deref().map_or(false, |value| value.is_empty()))
+            }
+            SgmlEvent::OpenStartTag { .. } => {
+                let content = self.peek_content_type()?;
+                Ok(!content.contains_attributes
+                    && !content.contains_child_elements
+                    && !content.contains_text)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Compares two element names, taking case sensitivity options into account.
+    fn names_match(&self, a: &str, b: &str) -> bool {
+        if self.options.case_insensitive_keys {
+            a.eq_ignore_ascii_case(b)
+        } else {
+            a == b
+        }
     }
 
     fn do_map<'r, V>(
         &'r mut self,
         visitor: V,
-        emit_value: bool,
+        fields: &'static [&'static str],
     ) -> Result<V::Value, DeserializationError>
     where
         V: de::Visitor<'de>,
     {
         self.push_elt()?;
         let stack_size = self.stack.len();
-        let value = visitor.visit_map(MapAccess::new(self, emit_value))?;
+        let value = visitor.visit_map(MapAccess::new(self, fields))?;
         self.check_stack_size(stack_size);
         self.pop_elt()?;
 
@@ -364,13 +1003,13 @@ macro_rules! forward_parse {
             V: de::Visitor<'de>,
         {
             trace!(stringify!($deserialize));
-            let value = self.consume_text::<V>()?.parse()?;
+            let value = self.consume_text()?.parse()?;
             visitor.$visit(value)
         }
     };
 }
 
-impl<'de, 'r> Deserializer<'de> for &'r mut SgmlDeserializer<'de> {
+impl<'de> Deserializer<'de> for &mut SgmlDeserializer<'de> {
     type Error = DeserializationError;
 
     forward_parse!(deserialize_i8 => visit_i8);
@@ -399,10 +1038,12 @@ impl<'de, 'r> Deserializer<'de> for &'r mut SgmlDeserializer<'de> {
             }
         }
 
-        let str = self.consume_text::<V>()?;
-        if str == "1" || str.eq_ignore_ascii_case("true") {
+        let str = self.consume_text()?;
+        let matches =
+            |values: &[String]| values.iter().any(|value| str.eq_ignore_ascii_case(value));
+        if matches(&self.options.true_values) {
             visitor.visit_bool(true)
-        } else if str == "0" || str.eq_ignore_ascii_case("false") {
+        } else if matches(&self.options.false_values) {
             visitor.visit_bool(false)
         } else {
             Err(de::Error::invalid_value(
@@ -417,7 +1058,7 @@ impl<'de, 'r> Deserializer<'de> for &'r mut SgmlDeserializer<'de> {
         V: de::Visitor<'de>,
     {
         trace!("deserialize_str");
-        match self.consume_text::<V>()? {
+        match self.consume_text()? {
             Cow::Borrowed(s) => visitor.visit_borrowed_str(s),
             Cow::Owned(s) => visitor.visit_string(s),
         }
@@ -467,6 +1108,11 @@ impl<'de, 'r> Deserializer<'de> for &'r mut SgmlDeserializer<'de> {
     where
         V: de::Visitor<'de>,
     {
+        if self.options.empty_as_none && self.peek_is_empty()? {
+            trace!("deserialize_option -> none (empty)");
+            self.deserialize_unit(de::IgnoredAny)?;
+            return visitor.visit_none();
+        }
         visitor.visit_some(self)
     }
 
@@ -525,7 +1171,7 @@ impl<'de, 'r> Deserializer<'de> for &'r mut SgmlDeserializer<'de> {
         trace!("deserialize_seq (tag: {:?})", self.map_key);
         let stack_size = self.stack.len();
 
-        let tag_name = self.map_key.take().map(Into::into);
+        let tag_name = self.map_key.take();
         let value = visitor.visit_seq(SeqAccess::new(self, tag_name))?;
 
         self.check_stack_size(stack_size);
@@ -559,7 +1205,7 @@ impl<'de, 'r> Deserializer<'de> for &'r mut SgmlDeserializer<'de> {
         V: de::Visitor<'de>,
     {
         trace!("deserialize_map");
-        self.do_map(visitor, false)
+        self.do_map(visitor, &[])
     }
 
     fn deserialize_struct<V>(
@@ -572,7 +1218,7 @@ impl<'de, 'r> Deserializer<'de> for &'r mut SgmlDeserializer<'de> {
         V: de::Visitor<'de>,
     {
         trace!("deserialize_struct({}) -> map", name);
-        self.do_map(visitor, fields.contains(&"$value"))
+        self.do_map(visitor, fields)
     }
 
     fn deserialize_enum<V>(
@@ -586,6 +1232,11 @@ impl<'de, 'r> Deserializer<'de> for &'r mut SgmlDeserializer<'de> {
     {
         trace!("deserialize_enum({})", name);
 
+        if mem::take(&mut self.text_node) {
+            trace!("enum from text node");
+            return visitor.visit_enum(EnumAccess::new(self, VariantSource::TextNode));
+        }
+
         let stack_size = self.stack.len();
 
         // If true, we have a <map-key>(enum-value)</map-key> case;
@@ -596,34 +1247,33 @@ impl<'de, 'r> Deserializer<'de> for &'r mut SgmlDeserializer<'de> {
             .and_then(|map_key| {
                 self.expect_start_tag()
                     .ok()
-                    .map(|start_tag| start_tag == map_key)
+                    .map(|start_tag| self.names_match(start_tag, map_key))
             })
             .unwrap_or(false);
 
-        // If true, (enum-value) is <variant (fields)>(fields)</variant>;
-        // if false, (enum-value) is just a string
-        let use_tag_name_for_variant = if enum_within_element {
+        // (enum-value) is either <variant (fields)>(fields)</variant>, or just a string
+        let variant_source = if enum_within_element {
             if self.peek_content_type()?.contains_child_elements {
                 trace!("enum within element; using content elt");
                 // <key><variant (fields)>(fields)</variant></key>
                 // Advance cursor to `<variant`
                 self.push_elt()?;
                 self.advance_to_content()?;
-                true
+                VariantSource::TagName
             } else {
                 trace!("enum within element; using text content");
                 // <key>variant</key>
                 // Keep cursor on `<key`
-                false
+                VariantSource::TextContent
             }
         } else {
             // No surrounding element, so it must be <variant (fields)>(fields)</variant>
             // Keep cursor on `<variant`
-            true
+            VariantSource::TagName
         };
 
-        let value = visitor.visit_enum(EnumAccess::new(self, use_tag_name_for_variant))?;
-        if enum_within_element && use_tag_name_for_variant {
+        let value = visitor.visit_enum(EnumAccess::new(self, variant_source))?;
+        if enum_within_element && variant_source == VariantSource::TagName {
             self.pop_elt()?;
         }
 
@@ -638,20 +1288,22 @@ impl<'de, 'r> Deserializer<'de> for &'r mut SgmlDeserializer<'de> {
         trace!("deserialize_any");
 
         if self.accumulated_text.is_some() {
-            return self.deserialize_str(visitor);
+            return self.deserialize_inferred(visitor);
         }
         match self.peek()? {
             SgmlEvent::OpenStartTag { .. } => {
                 let content = self.peek_content_type()?;
-                if content.contains_child_elements || content.contains_attributes {
-                    self.do_map(visitor, !content.contains_child_elements)
+                if content.contains_child_elements {
+                    self.do_map(visitor, &[])
+                } else if content.contains_attributes {
+                    self.do_map(visitor, &[VALUE_KEY])
                 } else if content.contains_text {
-                    self.deserialize_str(visitor)
+                    self.deserialize_inferred(visitor)
                 } else {
                     self.deserialize_unit(visitor)
                 }
             }
-            SgmlEvent::Attribute { .. } => self.deserialize_str(visitor),
+            SgmlEvent::Attribute { .. } => self.deserialize_inferred(visitor),
             _ => Err(DeserializationError::ExpectedStartTag),
         }
     }
@@ -675,15 +1327,29 @@ struct MapAccess<'de, 'r> {
     de: &'r mut SgmlDeserializer<'de>,
     stack_size: usize,
     map_key: Option<Rc<str>>,
+    fields: &'static [&'static str],
     content_strategy: ContentStrategy,
     text_content: Option<CowBuffer<'de>>,
     next_entry_is_dollarvalue: bool,
+    /// Key under which the text content is presented: `$value`, or `$text` if requested
+    text_key: &'static str,
+    /// Special fields for set aside events, to be emitted at the end of the element
+    pending_captures: std::vec::IntoIter<Capture>,
+    next_entry_capture: Option<Capture>,
+    /// Position of the first set aside event within this element
+    set_aside_start: usize,
 }
 
 impl<'de, 'r> MapAccess<'de, 'r> {
-    fn new(de: &'r mut SgmlDeserializer<'de>, emit_value: bool) -> Self {
+    fn new(de: &'r mut SgmlDeserializer<'de>, fields: &'static [&'static str]) -> Self {
         let stack_size = de.stack.len();
-        let content_strategy = if emit_value {
+        // The root element also captures everything before it, like the DOCTYPE
+        let set_aside_start = if stack_size <= 1 {
+            0
+        } else {
+            de.set_aside.len()
+        };
+        let content_strategy = if fields.contains(&VALUE_KEY) {
             if de
                 .peek_content_type()
                 .map(|content| content.contains_child_elements)
@@ -700,9 +1366,52 @@ impl<'de, 'r> MapAccess<'de, 'r> {
             de,
             stack_size,
             map_key: None,
+            fields,
             content_strategy,
-            text_content: (content_strategy == ContentStrategy::TextOnly).then(CowBuffer::new),
+            text_content: (content_strategy == ContentStrategy::TextOnly
+                || fields.contains(&TEXT_KEY))
+            .then(CowBuffer::new),
             next_entry_is_dollarvalue: false,
+            text_key: if fields.contains(&TEXT_KEY) {
+                TEXT_KEY
+            } else {
+                VALUE_KEY
+            },
+            pending_captures: Capture::ALL
+                .iter()
+                .copied()
+                .filter(|capture| fields.contains(&capture.key()))
+                .collect::<Vec<_>>()
+                .into_iter(),
+            next_entry_capture: None,
+            set_aside_start,
+        }
+    }
+}
+
+/// Special fields for capturing set aside events.
+#[derive(Clone, Copy, Debug)]
+enum Capture {
+    /// Markup declarations, in the `$declarations` field
+    Declarations,
+    /// Processing instructions, in the `$pi` field
+    ProcessingInstructions,
+}
+
+impl Capture {
+    const ALL: [Capture; 2] = [Capture::Declarations, Capture::ProcessingInstructions];
+
+    fn key(self) -> &'static str {
+        match self {
+            Capture::Declarations => "$declarations",
+            Capture::ProcessingInstructions => "$pi",
+        }
+    }
+
+    fn matches(self, event: &SgmlEvent) -> bool {
+        match self {
+            Capture::Declarations => matches!(event, SgmlEvent::MarkupDeclaration { .. }),
+            Capture::ProcessingInstructions => matches!(event, SgmlEvent::ProcessingInstruction(_)),
         }
     }
 }
@@ -727,22 +1436,30 @@ impl<'de, 'r> de::MapAccess<'de> for MapAccess<'de, 'r> {
         trace!("next_key_seed");
         self.de.check_stack_size(self.stack_size);
 
+        let trim_text = self.de.options.trim_text;
         loop {
             break match self.de.peek_mut()? {
                 SgmlEvent::EndTag { .. } | SgmlEvent::XmlCloseEmptyElement => {
                     if self.text_content.is_some() {
                         self.next_entry_is_dollarvalue = true;
-                        debug!("next key: $value");
-                        self.map_key = Some("$value".into());
-                        seed.deserialize("$value".into_deserializer()).map(Some)
+                        debug!("next key: {}", self.text_key);
+                        self.map_key = Some(self.text_key.into());
+                        seed.deserialize(self.text_key.into_deserializer())
+                            .map(Some)
+                    } else if let Some(capture) = self.pending_captures.next() {
+                        debug!("next key: {}", capture.key());
+                        self.next_entry_capture = Some(capture);
+                        seed.deserialize(capture.key().into_deserializer())
+                            .map(Some)
                     } else {
                         Ok(None)
                     }
                 }
                 SgmlEvent::Attribute { name, .. } => {
                     debug!("next key: {} (from attribute)", name);
-                    seed.deserialize(name.as_ref().into_deserializer())
-                        .map(Some)
+                    let name = name.clone();
+                    let key = resolve_attribute_key(&self.de.options, self.fields, &name);
+                    seed.deserialize(key.into_deserializer()).map(Some)
                 }
                 SgmlEvent::CloseStartTag => {
                     self.de.advance()?;
@@ -751,16 +1468,30 @@ impl<'de, 'r> de::MapAccess<'de> for MapAccess<'de, 'r> {
                 SgmlEvent::OpenStartTag { name } => match self.content_strategy {
                     ContentStrategy::ElementsAreMapEntries => {
                         debug!("next key: {} (from tag name)", name);
-                        self.map_key = Some(name.clone().into_owned().into());
-                        seed.deserialize(name.as_ref().into_deserializer())
-                            .map(Some)
+                        let name = name.clone();
+                        let key = resolve_key(&self.de.options, self.fields, &name);
+                        if self.de.options.strict
+                            && !self.fields.is_empty()
+                            && !self.fields.contains(&key)
+                        {
+                            return Err(DeserializationError::UnknownElement(name.into_owned()));
+                        }
+                        self.map_key = Some(name.as_ref().into());
+                        seed.deserialize(key.into_deserializer()).map(Some)
                     }
                     ContentStrategy::ElementsAreDollarValue => {
                         debug!("next key: $value (for element {:?})", name);
-                        seed.deserialize("$value".into_deserializer()).map(Some)
+                        seed.deserialize(VALUE_KEY.into_deserializer()).map(Some)
                     }
                     ContentStrategy::TextOnly => unreachable!(),
                 },
+                SgmlEvent::Character(text)
+                    if self.content_strategy == ContentStrategy::ElementsAreDollarValue
+                        && !(text.is_empty() || trim_text && crate::text::is_blank(text)) =>
+                {
+                    debug!("next key: $value (for mixed content)");
+                    seed.deserialize(VALUE_KEY.into_deserializer()).map(Some)
+                }
                 SgmlEvent::Character(text) => {
                     let text = mem::take(text);
                     self.de.advance()?;
@@ -771,7 +1502,9 @@ impl<'de, 'r> de::MapAccess<'de> for MapAccess<'de, 'r> {
                 }
                 SgmlEvent::ProcessingInstruction(_)
                 | SgmlEvent::MarkupDeclaration { .. }
-                | SgmlEvent::MarkedSection { .. } => unreachable!(),
+                | SgmlEvent::MarkedSection { .. }
+                | SgmlEvent::ParsedMarkedSection { .. }
+                | SgmlEvent::Comment(_) => unreachable!(),
             };
         }
     }
@@ -783,16 +1516,28 @@ impl<'de, 'r> de::MapAccess<'de> for MapAccess<'de, 'r> {
         trace!("next_value_seed (key={:?})", self.map_key);
         self.de.check_stack_size(self.stack_size);
 
-        if self.next_entry_is_dollarvalue {
+        if let Some(capture) = self.next_entry_capture.take() {
+            let captured = self.de.capture(capture, self.set_aside_start);
+            seed.deserialize(de::value::SeqDeserializer::new(captured.into_iter()))
+                .map_err(|err: DeserializationError| err.at(capture.key()))
+        } else if self.next_entry_is_dollarvalue {
             self.de.accumulated_text = Some(self.text_content.take().unwrap().into_cow());
             let value = seed.deserialize(&mut *self.de)?;
             self.de.accumulated_text = None;
             Ok(value)
-        } else if let Ok(SgmlEvent::Attribute { .. }) = self.de.peek() {
-            seed.deserialize(&mut *self.de)
+        } else if let Ok(SgmlEvent::Attribute { name, .. }) = self.de.peek() {
+            let name = format!("{}{}", ATTRIBUTE_PREFIX, name);
+            seed.deserialize(&mut *self.de).map_err(|err| err.at(&name))
         } else {
-            self.de.map_key = self.map_key.take();
-            let value = seed.deserialize(&mut *self.de)?;
+            let key = self.map_key.take();
+            self.de.map_key = key.clone();
+            let value = seed.deserialize(&mut *self.de).map_err(|err| match &key {
+                Some(key) => err.at(key),
+                None if self.content_strategy == ContentStrategy::ElementsAreDollarValue => {
+                    err.at(VALUE_KEY)
+                }
+                None => err,
+            })?;
             self.de.map_key = None;
             Ok(value)
         }
@@ -803,6 +1548,7 @@ struct SeqAccess<'de, 'r> {
     de: &'r mut SgmlDeserializer<'de>,
     stack_size: usize,
     tag_name: Option<Rc<str>>,
+    index: usize,
 }
 
 impl<'de, 'r> SeqAccess<'de, 'r> {
@@ -812,6 +1558,7 @@ impl<'de, 'r> SeqAccess<'de, 'r> {
             de,
             stack_size,
             tag_name,
+            index: 0,
         }
     }
 }
@@ -828,17 +1575,41 @@ impl<'de, 'r> de::SeqAccess<'de> for SeqAccess<'de, 'r> {
         loop {
             match self.de.peek()? {
                 SgmlEvent::OpenStartTag { name } => match &self.tag_name {
-                    Some(expected_tag) if name.as_ref() != expected_tag.as_ref() => {
+                    Some(expected_tag) if !self.de.names_match(name, expected_tag) => {
                         return Ok(None)
                     }
                     _ => {
                         if self.de.map_key != self.tag_name {
                             self.de.map_key = self.tag_name.clone();
                         }
-                        return Ok(Some(seed.deserialize(&mut *self.de)?));
+                        let index = self.index;
+                        self.index += 1;
+                        return seed
+                            .deserialize(&mut *self.de)
+                            .map(Some)
+                            .map_err(|err| err.at_index(index));
                     }
                 },
                 SgmlEvent::Character(text) if text.is_empty() => self.de.advance()?,
+                // Mixed content, when the sequence is not limited to a single element name
+                SgmlEvent::Character(_) if self.tag_name.is_none() => {
+                    let text = match self.de.advance()? {
+                        SgmlEvent::Character(text) => text,
+                        _ => unreachable!(),
+                    };
+                    if self.de.trim(&text).is_empty() {
+                        continue;
+                    }
+                    debug!("text node: {:?}", text);
+                    let index = self.index;
+                    self.index += 1;
+                    self.de.accumulated_text = Some(text);
+                    self.de.text_node = true;
+                    let value = seed.deserialize(&mut *self.de);
+                    self.de.accumulated_text = None;
+                    self.de.text_node = false;
+                    return value.map(Some).map_err(|err| err.at_index(index));
+                }
                 _ => return Ok(None),
             };
         }
@@ -847,15 +1618,23 @@ impl<'de, 'r> de::SeqAccess<'de> for SeqAccess<'de, 'r> {
 
 struct EnumAccess<'de, 'r> {
     de: &'r mut SgmlDeserializer<'de>,
-    use_tag_name_for_variant: bool,
+    variant_source: VariantSource,
+}
+
+/// Where the name of an enum variant comes from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum VariantSource {
+    /// `<variant>(fields)</variant>`
+    TagName,
+    /// `<key>variant</key>`
+    TextContent,
+    /// A run of text in mixed content, for the `$text` variant
+    TextNode,
 }
 
 impl<'de, 'r> EnumAccess<'de, 'r> {
-    fn new(de: &'r mut SgmlDeserializer<'de>, use_tag_name_for_variant: bool) -> Self {
-        Self {
-            de,
-            use_tag_name_for_variant,
-        }
+    fn new(de: &'r mut SgmlDeserializer<'de>, variant_source: VariantSource) -> Self {
+        Self { de, variant_source }
     }
 }
 
@@ -868,13 +1647,20 @@ impl<'de, 'r> de::EnumAccess<'de> for EnumAccess<'de, 'r> {
         V: de::DeserializeSeed<'de>,
     {
         trace!("variant_seed");
-        let name = if self.use_tag_name_for_variant {
-            debug!("using tag name for enum variant");
-            let name = self.de.expect_start_tag()?.as_ref();
-            seed.deserialize(name.into_deserializer())
-        } else {
-            debug!("using text content for enum variant");
-            seed.deserialize(&mut *self.de)
+        let name = match self.variant_source {
+            VariantSource::TagName => {
+                debug!("using tag name for enum variant");
+                let name = self.de.expect_start_tag()?.as_ref();
+                seed.deserialize(name.into_deserializer())
+            }
+            VariantSource::TextContent => {
+                debug!("using text content for enum variant");
+                seed.deserialize(&mut *self.de)
+            }
+            VariantSource::TextNode => {
+                debug!("using $text for enum variant");
+                seed.deserialize(TEXT_KEY.into_deserializer())
+            }
         }?;
         Ok((name, self))
     }
@@ -885,9 +1671,13 @@ impl<'de, 'r> de::VariantAccess<'de> for EnumAccess<'de, 'r> {
 
     fn unit_variant(self) -> Result<(), Self::Error> {
         trace!("unit_variant");
-        if self.use_tag_name_for_variant {
-            self.de.push_elt()?;
-            self.de.pop_elt()?;
+        match self.variant_source {
+            VariantSource::TagName => {
+                self.de.push_elt()?;
+                self.de.pop_elt()?;
+            }
+            VariantSource::TextContent => {}
+            VariantSource::TextNode => self.de.accumulated_text = None,
         }
         Ok(())
     }
@@ -899,8 +1689,8 @@ impl<'de, 'r> de::VariantAccess<'de> for EnumAccess<'de, 'r> {
         trace!("newtype_variant");
         // If we just used the current tag name as a variant, we need to push the tag into the stack,
         // or else we might get stuck in a loop
-        let push =
-            self.use_tag_name_for_variant && self.de.peek_content_type()?.contains_child_elements;
+        let push = self.variant_source == VariantSource::TagName
+            && self.de.peek_content_type()?.contains_child_elements;
         if push {
             self.de.push_elt()?;
             self.de.advance_to_content()?;
@@ -917,7 +1707,12 @@ impl<'de, 'r> de::VariantAccess<'de> for EnumAccess<'de, 'r> {
         V: de::Visitor<'de>,
     {
         trace!("tuple_variant({} items)", len);
-        if self.use_tag_name_for_variant {
+        if self.variant_source == VariantSource::TextNode {
+            return Err(de::Error::custom(
+                "$text can only be a newtype or unit variant",
+            ));
+        }
+        if self.variant_source == VariantSource::TagName {
             self.de.map_key = Some(self.de.expect_start_tag()?.clone().into_owned().into());
         }
         self.de.deserialize_seq(visitor)
@@ -932,7 +1727,91 @@ impl<'de, 'r> de::VariantAccess<'de> for EnumAccess<'de, 'r> {
         V: de::Visitor<'de>,
     {
         trace!("struct_variant");
-        self.de.do_map(visitor, fields.contains(&"$value"))
+        if self.variant_source == VariantSource::TextNode {
+            return Err(de::Error::custom(
+                "$text can only be a newtype or unit variant",
+            ));
+        }
+        self.de.do_map(visitor, fields)
+    }
+}
+
+/// Returns the name of the struct field matching the given element or attribute name.
+///
+/// Unless case-insensitive keys are enabled, this is always the name itself.
+fn resolve_key<'n>(
+    options: &DeserializerOptions,
+    fields: &'static [&'static str],
+    name: &'n str,
+) -> &'n str {
+    if !options.case_insensitive_keys {
+        return name;
+    }
+    fields
+        .iter()
+        .find(|field| field.eq_ignore_ascii_case(name))
+        .copied()
+        .unwrap_or(name)
+}
+
+/// Returns the key for the given attribute name.
+///
+/// Attributes are presented with an `@` prefix when the struct has a matching field
+/// (like `@ID`), or when requested for all attributes; otherwise, the same as elements.
+fn resolve_attribute_key<'n>(
+    options: &DeserializerOptions,
+    fields: &'static [&'static str],
+    name: &'n str,
+) -> Cow<'n, str> {
+    if !options.prefix_attributes
+        && !fields
+            .iter()
+            .any(|field| field.starts_with(ATTRIBUTE_PREFIX))
+    {
+        return Cow::Borrowed(resolve_key(options, fields, name));
+    }
+    let prefixed = format!("{}{}", ATTRIBUTE_PREFIX, name);
+    let key = resolve_key(options, fields, &prefixed);
+    if options.prefix_attributes || fields.contains(&key) {
+        Cow::Owned(key.to_owned())
+    } else {
+        Cow::Borrowed(resolve_key(options, fields, name))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Number {
+    Unsigned(u64),
+    Signed(i64),
+    Float(f64),
+}
+
+/// Recognizes text that is a number written in its shortest form.
+///
+/// Anything that would not be written back identically (like `012` or `1.50`)
+/// is not considered a number, so it can still be deserialized as a string.
+fn infer_number(text: &str) -> Option<Number> {
+    let number = if let Ok(n) = text.parse() {
+        Number::Unsigned(n)
+    } else if let Ok(n) = text.parse() {
+        Number::Signed(n)
+    } else if text.contains('.') {
+        match text.parse::<f64>() {
+            Ok(n) if n.is_finite() => Number::Float(n),
+            _ => return None,
+        }
+    } else {
+        return None;
+    };
+    let canonical = match number {
+        Number::Unsigned(n) => n.to_string(),
+        Number::Signed(n) => n.to_string(),
+        Number::Float(n) => n.to_string(),
+    };
+    if canonical == text {
+        Some(number)
+    } else {
+        None
     }
 }
 
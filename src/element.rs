@@ -0,0 +1,416 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::ops::Range;
+
+use crate::{SgmlEvent, SgmlFragment};
+
+/// The error type when navigating the elements of an unbalanced fragment.
+///
+/// This is returned by [`SgmlFragment::elements`] and [`SgmlFragment::root`].
+#[derive(Clone, Debug, Eq, PartialEq, thiserror::Error)]
+pub enum ElementError {
+    #[error("unpaired end tag at event {index}: </{name}>")]
+    UnpairedEndTag { name: String, index: usize },
+    #[error("end tag </{found}> at event {index} does not match start tag <{expected}>")]
+    MismatchedEndTag {
+        expected: String,
+        found: String,
+        index: usize,
+    },
+    #[error("element <{name}> starting at event {index} is not closed")]
+    UnclosedElement { name: String, index: usize },
+    #[error("start tag <{name}> at event {index} is not terminated")]
+    UnterminatedStartTag { name: String, index: usize },
+    #[error("fragment has no root element")]
+    NoRootElement,
+    #[error("fragment has more than one root element")]
+    MultipleRootElements,
+}
+
+/// A handle to an element in a [`SgmlFragment`].
+///
+/// Handles are created by [`SgmlFragment::elements`] and [`SgmlFragment::root`],
+/// and know the position of every part of their element in the list of events.
+///
+/// # Example
+///
+/// ```rust
+/// # fn main() -> sgmlish::Result<()> {
+/// let fragment = sgmlish::parse(r#"<UL CLASS="menu"><LI>One</LI><LI>Two</LI></UL>"#)?;
+///
+/// let root = fragment.root()?;
+/// assert_eq!(root.name(), "UL");
+/// assert_eq!(root.attr("CLASS"), Some("menu"));
+/// assert_eq!(
+///     root.children().map(|li| li.text()).collect::<Vec<_>>(),
+///     vec!["One", "Two"]
+/// );
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ElementRef<'f, 'a> {
+    events: &'f [SgmlEvent<'a>],
+    start: usize,
+    attributes: Range<usize>,
+    content: Range<usize>,
+    end: usize,
+    depth: usize,
+}
+
+/// An iterator over elements, in the order their start tags appear.
+///
+/// Created by [`SgmlFragment::elements`], [`ElementRef::children`] and
+/// [`ElementRef::descendants`].
+#[derive(Clone, Debug)]
+pub struct Elements<'f, 'a> {
+    inner: std::vec::IntoIter<ElementRef<'f, 'a>>,
+}
+
+impl<'a> SgmlFragment<'a> {
+    /// Returns an iterator over all elements in the fragment, in the order their start tags appear.
+    ///
+    /// Fails if the fragment has unbalanced tags; if end tags may have been omitted,
+    /// consider applying [`normalize_end_tags`](crate::transforms::normalize_end_tags) first.
+    ///
+    /// Tag names are compared in a case-sensitive manner.
+    pub fn elements(&self) -> Result<Elements<'_, 'a>, ElementError> {
+        let elements = scan(self.as_slice(), 0..self.len(), 0)?;
+        Ok(Elements {
+            inner: elements.into_iter(),
+        })
+    }
+
+    /// Returns the only top-level element in the fragment.
+    ///
+    /// Fails if the fragment has unbalanced tags, or if there is not exactly one top-level element.
+    pub fn root(&self) -> Result<ElementRef<'_, 'a>, ElementError> {
+        let mut top_level = self.elements()?.filter(|element| element.depth == 0);
+        match (top_level.next(), top_level.next()) {
+            (Some(root), None) => Ok(root),
+            (None, _) => Err(ElementError::NoRootElement),
+            (Some(_), Some(_)) => Err(ElementError::MultipleRootElements),
+        }
+    }
+}
+
+impl<'f, 'a> ElementRef<'f, 'a> {
+    /// Returns the name of the element.
+    pub fn name(&self) -> &'f str {
+        match &self.events[self.start] {
+            SgmlEvent::OpenStartTag { name } => name,
+            _ => unreachable!("elements always begin with a start tag"),
+        }
+    }
+
+    /// Returns the index of the [`OpenStartTag`](SgmlEvent::OpenStartTag) event.
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// Returns the range of [`Attribute`](SgmlEvent::Attribute) events in the start tag.
+    pub fn attributes_range(&self) -> Range<usize> {
+        self.attributes.clone()
+    }
+
+    /// Returns the range of events between the start and end tags.
+    ///
+    /// For elements closed with [`XmlCloseEmptyElement`](SgmlEvent::XmlCloseEmptyElement),
+    /// this is an empty range.
+    pub fn content_range(&self) -> Range<usize> {
+        self.content.clone()
+    }
+
+    /// Returns the index of the last event in the element, which is either an
+    /// [`EndTag`](SgmlEvent::EndTag) or an [`XmlCloseEmptyElement`](SgmlEvent::XmlCloseEmptyElement).
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    /// Returns the range of all events in the element, from start to end tags.
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end + 1
+    }
+
+    /// Returns the value of the attribute with the given name.
+    ///
+    /// Attributes without a value (e.g. `<INPUT CHECKED>`) have an empty value.
+    pub fn attr(&self, name: &str) -> Option<&'f str> {
+        self.events[self.attributes.clone()]
+            .iter()
+            .find_map(|event| match event {
+                SgmlEvent::Attribute {
+                    name: attr_name,
+                    value,
+                } if attr_name == name => Some(value.as_deref().unwrap_or_default()),
+                _ => None,
+            })
+    }
+
+    /// Returns the text in the element, including text in nested elements.
+    pub fn text(&self) -> Cow<'f, str> {
+        let mut texts = self.events[self.content.clone()]
+            .iter()
+            .filter_map(|event| match event {
+                SgmlEvent::Character(text) => Some(&**text),
+                _ => None,
+            });
+        let first = match texts.next() {
+            Some(first) => first,
+            None => return Cow::Borrowed(""),
+        };
+        match texts.next() {
+            None => Cow::Borrowed(first),
+            Some(second) => {
+                let mut text = String::from(first);
+                text.push_str(second);
+                texts.for_each(|t| text.push_str(t));
+                Cow::Owned(text)
+            }
+        }
+    }
+
+    /// Returns an iterator over elements directly inside this element.
+    pub fn children(&self) -> Elements<'f, 'a> {
+        let depth = self.depth + 1;
+        let mut elements = self.scan_content();
+        elements.retain(|element| element.depth == depth);
+        Elements {
+            inner: elements.into_iter(),
+        }
+    }
+
+    /// Returns an iterator over all elements inside this element, at any depth.
+    pub fn descendants(&self) -> Elements<'f, 'a> {
+        Elements {
+            inner: self.scan_content().into_iter(),
+        }
+    }
+
+    fn scan_content(&self) -> Vec<ElementRef<'f, 'a>> {
+        scan(self.events, self.content.clone(), self.depth + 1)
+            .expect("content was validated when the element was found")
+    }
+}
+
+impl fmt::Debug for ElementRef<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ElementRef")
+            .field("name", &self.name())
+            .field("start", &self.start)
+            .field("attributes", &self.attributes)
+            .field("content", &self.content)
+            .field("end", &self.end)
+            .finish()
+    }
+}
+
+impl<'f, 'a> Iterator for Elements<'f, 'a> {
+    type Item = ElementRef<'f, 'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for Elements<'_, '_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back()
+    }
+}
+
+impl ExactSizeIterator for Elements<'_, '_> {}
+
+impl std::iter::FusedIterator for Elements<'_, '_> {}
+
+/// Finds all elements in the given range of events, checking that tags are balanced.
+fn scan<'f, 'a>(
+    events: &'f [SgmlEvent<'a>],
+    range: Range<usize>,
+    depth: usize,
+) -> Result<Vec<ElementRef<'f, 'a>>, ElementError> {
+    let mut elements: Vec<ElementRef> = vec![];
+    // Indices into `elements` of those still open
+    let mut stack = vec![];
+
+    let mut i = range.start;
+    while i < range.end {
+        match &events[i] {
+            SgmlEvent::OpenStartTag { name } => {
+                let attributes_end = (i + 1..range.end)
+                    .find(|&j| !matches!(events[j], SgmlEvent::Attribute { .. }))
+                    .unwrap_or(range.end);
+                let element_depth = depth + stack.len();
+                let content = match events.get(attributes_end) {
+                    _ if attributes_end == range.end => None,
+                    Some(SgmlEvent::CloseStartTag) => {
+                        stack.push(elements.len());
+                        Some(attributes_end + 1..attributes_end + 1)
+                    }
+                    Some(SgmlEvent::XmlCloseEmptyElement) => Some(attributes_end..attributes_end),
+                    _ => None,
+                };
+                let content = content.ok_or_else(|| ElementError::UnterminatedStartTag {
+                    name: name.to_string(),
+                    index: i,
+                })?;
+                elements.push(ElementRef {
+                    events,
+                    start: i,
+                    attributes: i + 1..attributes_end,
+                    content,
+                    end: attributes_end,
+                    depth: element_depth,
+                });
+                i = attributes_end;
+            }
+            SgmlEvent::EndTag { name } => {
+                let open = stack.pop().ok_or_else(|| ElementError::UnpairedEndTag {
+                    name: name.to_string(),
+                    index: i,
+                })?;
+                let element = &mut elements[open];
+                if element.name() != name {
+                    return Err(ElementError::MismatchedEndTag {
+                        expected: element.name().to_owned(),
+                        found: name.to_string(),
+                        index: i,
+                    });
+                }
+                element.content.end = i;
+                element.end = i;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    if let Some(&open) = stack.last() {
+        let element = &elements[open];
+        return Err(ElementError::UnclosedElement {
+            name: element.name().to_owned(),
+            index: element.start,
+        });
+    }
+
+    Ok(elements)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_elements_ranges() {
+        let fragment = crate::parse(r#"<A HREF="/" TARGET=_blank>x<BR/></A>"#).unwrap();
+        let elements = fragment.elements().unwrap().collect::<Vec<_>>();
+        assert_eq!(elements.len(), 2);
+
+        let a = &elements[0];
+        assert_eq!(a.name(), "A");
+        assert_eq!(a.start(), 0);
+        assert_eq!(a.attributes_range(), 1..3);
+        assert_eq!(a.content_range(), 4..7);
+        assert_eq!(a.end(), 7);
+        assert_eq!(a.range(), 0..8);
+
+        let br = &elements[1];
+        assert_eq!(br.name(), "BR");
+        assert_eq!(br.attributes_range(), 6..6);
+        assert_eq!(br.content_range(), 6..6);
+        assert_eq!(br.end(), 6);
+        assert_eq!(br.text(), "");
+    }
+
+    #[test]
+    fn test_attr() {
+        let fragment = crate::parse("<INPUT TYPE=checkbox CHECKED/>").unwrap();
+        let input = fragment.root().unwrap();
+        assert_eq!(input.attr("TYPE"), Some("checkbox"));
+        assert_eq!(input.attr("CHECKED"), Some(""));
+        assert_eq!(input.attr("type"), None);
+        assert_eq!(input.attr("VALUE"), None);
+    }
+
+    #[test]
+    fn test_text() {
+        let fragment = crate::parse("<P>Hello, <B>world</B>!</P>").unwrap();
+        let p = fragment.root().unwrap();
+        assert_eq!(p.text(), "Hello,world!");
+        assert!(matches!(
+            p.children().next().unwrap().text(),
+            Cow::Borrowed("world")
+        ));
+    }
+
+    fn names<'f>(elements: Elements<'f, '_>) -> Vec<&'f str> {
+        elements.map(|element| element.name()).collect()
+    }
+
+    #[test]
+    fn test_children_and_descendants() {
+        let fragment = crate::parse("<DL><DT>a<DD><I>b</I></DD></DT><DT>c</DT><HR/></DL>").unwrap();
+        let root = fragment.root().unwrap();
+        assert_eq!(names(root.children()), vec!["DT", "DT", "HR"]);
+        assert_eq!(names(root.descendants()), vec!["DT", "DD", "I", "DT", "HR"]);
+        let dt = root.children().next().unwrap();
+        assert_eq!(names(dt.children()), vec!["DD"]);
+        assert_eq!(names(dt.descendants()), vec!["DD", "I"]);
+    }
+
+    #[test]
+    fn test_root_errors() {
+        let fragment = crate::parse("text").unwrap();
+        assert_eq!(fragment.root().unwrap_err(), ElementError::NoRootElement);
+
+        let fragment = crate::parse("<A></A><B></B>").unwrap();
+        assert_eq!(
+            fragment.root().unwrap_err(),
+            ElementError::MultipleRootElements
+        );
+    }
+
+    #[test]
+    fn test_unbalanced() {
+        let fragment = crate::parse("<A><B>x</A>").unwrap();
+        assert_eq!(
+            fragment.elements().unwrap_err(),
+            ElementError::MismatchedEndTag {
+                expected: "B".into(),
+                found: "A".into(),
+                index: 5,
+            }
+        );
+
+        let fragment = crate::parse("<A><B>x</B>").unwrap();
+        assert_eq!(
+            fragment.elements().unwrap_err(),
+            ElementError::UnclosedElement {
+                name: "A".into(),
+                index: 0,
+            }
+        );
+
+        let fragment = crate::parse("x</A>").unwrap();
+        assert_eq!(
+            fragment.elements().unwrap_err(),
+            ElementError::UnpairedEndTag {
+                name: "A".into(),
+                index: 1,
+            }
+        );
+
+        let fragment = SgmlFragment::from(vec![SgmlEvent::OpenStartTag { name: "A".into() }]);
+        assert_eq!(
+            fragment.elements().unwrap_err(),
+            ElementError::UnterminatedStartTag {
+                name: "A".into(),
+                index: 0,
+            }
+        );
+    }
+}
@@ -7,7 +7,7 @@ use std::ops::Range;
 use nom::branch::alt;
 use nom::bytes::complete::{tag, take_while1};
 use nom::character::complete::digit1;
-use nom::combinator::{consumed, map, opt, recognize};
+use nom::combinator::{consumed, map, map_opt, opt, recognize};
 use nom::sequence::{preceded, terminated};
 use nom::IResult;
 
@@ -39,15 +39,18 @@ pub struct EntityError {
 /// let expanded = expand_characters("&#60;hello&#44; world&#33;&#62;");
 /// assert_eq!(expanded, Ok("<hello, world!>".into()));
 /// ```
-pub fn expand_characters(text: &str) -> Result<Cow<str>> {
+pub fn expand_characters(text: &str) -> Result<Cow<'_, str>> {
     expand_entities(text, |_| None::<&str>)
 }
 
 /// Expands entity references (`&foo;`) in the text using the given closure as lookup.
 ///
-/// Character references (`&#123;`) are also expanded, without going through the closure.
-/// Function names (`&#SPACE;`) as well as invalid character references
-/// (codes that go beyond Unicode, e.g. `&#1234567;`) are also passed to the closure.
+/// Character references (`&#123;`) are also expanded, without going through the closure,
+/// and so are references to the function characters of the reference concrete syntax
+/// (`&#RE;`, `&#RS;`, `&#SPACE;`, `&#TAB;`; see [`function_character`]).
+/// Other function names (`&#EXAMPLE;`) as well as invalid character references
+/// (codes that go beyond Unicode, e.g. `&#1234567;`) are passed to the closure,
+/// including the leading `#`.
 ///
 /// If the closure returns `None`, the entity is considered invalid,
 /// and the expansion fails.
@@ -63,7 +66,7 @@ pub fn expand_characters(text: &str) -> Result<Cow<str>> {
 /// let expanded = expand_entities("caf&eacute; &#9749;", |entity| entities.get(entity));
 /// assert_eq!(expanded, Ok("café ☕".into()));
 /// ```
-pub fn expand_entities<F, T>(text: &str, f: F) -> Result<Cow<str>>
+pub fn expand_entities<F, T>(text: &str, f: F) -> Result<Cow<'_, str>>
 where
     F: FnMut(&str) -> Option<T>,
     T: AsRef<str>,
@@ -89,7 +92,7 @@ where
 /// let expanded = expand_parameter_entities(" %HTML.Reserved; ", |entity| entities.get(entity));
 /// assert_eq!(expanded, Ok(" IGNORE ".into()));
 /// ```
-pub fn expand_parameter_entities<F, T>(text: &str, f: F) -> Result<Cow<str>>
+pub fn expand_parameter_entities<F, T>(text: &str, f: F) -> Result<Cow<'_, str>>
 where
     F: FnMut(&str) -> Option<T>,
     T: AsRef<str>,
@@ -97,6 +100,34 @@ where
     expand_entities_with(text, "%", entity_ref, f)
 }
 
+/// Returns the character for a function name of the reference concrete syntax,
+/// as used in character references like `&#RE;`.
+///
+/// The recognized names are, ignoring case:
+///
+/// * `RE` (record end): U+000D CARRIAGE RETURN
+/// * `RS` (record start): U+000A LINE FEED
+/// * `SPACE`: U+0020 SPACE
+/// * `TAB`: U+0009 CHARACTER TABULATION
+///
+/// # Example
+///
+/// ```rust
+/// # use sgmlish::entities::{expand_characters, function_character};
+/// assert_eq!(function_character("SPACE"), Some(' '));
+/// assert_eq!(function_character("MSOCHAR"), None);
+///
+/// assert_eq!(expand_characters("a&#TAB;b&#RE;&#RS;"), Ok("a\tb\r\n".into()));
+/// ```
+pub fn function_character(name: &str) -> Option<char> {
+    const FUNCTIONS: [(&str, char); 4] =
+        [("RE", '\r'), ("RS", '\n'), ("SPACE", ' '), ("TAB", '\t')];
+    FUNCTIONS
+        .iter()
+        .find(|(function, _)| function.eq_ignore_ascii_case(name))
+        .map(|&(_, c)| c)
+}
+
 fn expand_entities_with<'a, M, F, T>(
     text: &'a str,
     prefix: &str,
@@ -148,11 +179,11 @@ where
     Ok(out.into())
 }
 
-fn entity_or_char_ref(input: &str) -> IResult<&str, EntityRef> {
-    alt((char_ref, entity_ref))(input)
+fn entity_or_char_ref(input: &str) -> IResult<&str, EntityRef<'_>> {
+    alt((char_ref, function_ref, entity_ref))(input)
 }
 
-fn char_ref(input: &str) -> IResult<&str, EntityRef> {
+fn char_ref(input: &str) -> IResult<&str, EntityRef<'_>> {
     map(
         consumed(preceded(
             tag("#"),
@@ -175,7 +206,14 @@ fn char_ref(input: &str) -> IResult<&str, EntityRef> {
     )(input)
 }
 
-fn entity_ref(input: &str) -> IResult<&str, EntityRef> {
+fn function_ref(input: &str) -> IResult<&str, EntityRef<'_>> {
+    map(
+        preceded(tag("#"), map_opt(name, function_character)),
+        EntityRef::Char,
+    )(input)
+}
+
+fn entity_ref(input: &str) -> IResult<&str, EntityRef<'_>> {
     map(recognize(preceded(opt(tag("#")), name)), EntityRef::Entity)(input)
 }
 
@@ -283,6 +321,24 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_expand_function_characters() {
+        let result = expand_entities("a&#SPACE;b&#tab;c&#RE&#RS;&#RSX;", |key| {
+            assert_eq!(key, "#RSX");
+            Some("!")
+        });
+        assert_eq!(result, Ok("a b\tc\r\n!".into()));
+    }
+
+    #[test]
+    fn test_expand_parameter_entities_ignores_function_characters() {
+        let result = expand_parameter_entities("foo %#RE;", |name| {
+            assert_eq!(name, "#RE");
+            Some("x")
+        });
+        assert_eq!(result, Ok("foo x".into()));
+    }
+
     #[test]
     fn test_expand_entities_invalid_function() {
         let mut called = false;
@@ -21,9 +21,18 @@ pub enum Error {
     /// An error occurred when normalizing end tags.
     #[error(transparent)]
     NormalizationError(#[from] crate::transforms::NormalizationError),
+    /// An error occurred when navigating the elements of a fragment.
+    #[error(transparent)]
+    ElementError(#[from] crate::ElementError),
+    /// An error occurred when applying a [`Transform`](crate::transforms::Transform).
+    #[error(transparent)]
+    TransformError(#[from] crate::transforms::TransformError),
     /// An error occurred when decoding an entity reference.
     #[error(transparent)]
     EntityError(#[from] crate::entities::EntityError),
+    /// An error occurred when writing SGML data.
+    #[error(transparent)]
+    WriterError(#[from] crate::writer::WriterError),
     /// An error ocurred when processing a marked section.
     #[error("invalid marked section keyword: {0}")]
     InvalidMarkedSectionKeyword(String),
@@ -0,0 +1,651 @@
+//! Pretty-printing of [`SgmlFragment`]s.
+//!
+//! The [`Display`](std::fmt::Display) implementation for [`SgmlFragment`] writes
+//! events back to back, which is faithful but hard to read. The [`Formatter`]
+//! places each element on its own line when it's safe to do so:
+//!
+//! * Elements with *element-only content* (that is, only child elements and
+//!   whitespace) have each child on its own line, indented.
+//! * Elements with any text content (*mixed content*) are written inline,
+//!   exactly as found, so as not to change their content.
+//!
+//! Whitespace-only text spanning multiple lines is considered indentation,
+//! and is discarded from element-only content.
+//!
+//! # Example
+//!
+//! ```rust
+//! # fn main() -> sgmlish::Result<()> {
+//! let sgml = sgmlish::parse("<LIST><ITEM><B>Hello</B>, world</ITEM><ITEM>!</ITEM></LIST>")?;
+//! assert_eq!(
+//!     sgmlish::fmt::format(&sgml),
+//!     "<LIST>\n  <ITEM><B>Hello</B>, world</ITEM>\n  <ITEM>!</ITEM>\n</LIST>",
+//! );
+//! # Ok(())
+//! # }
+//! ```
+
+use std::borrow::Cow;
+use std::fmt::{self, Write};
+use std::ops::Range;
+
+use crate::parser::NameNormalization;
+use crate::{text, SgmlEvent, SgmlFragment};
+
+/// Formats the given fragment using a [`Formatter`] with default settings.
+pub fn format(fragment: &SgmlFragment) -> String {
+    Formatter::new().format(fragment)
+}
+
+/// A pretty-printer for SGML fragments.
+///
+/// The formatter works best with *tag-valid* fragments, where every start tag
+/// has a matching end tag (see [`normalize_end_tags`]). Start tags without a
+/// matching end tag are treated as empty elements, keeping whatever follows
+/// them as a sibling. Tag names are matched without regard to case.
+///
+/// Comments containing `--`, which would end the comment early, or ending with `-`
+/// are written with a space between the dashes.
+///
+/// [`normalize_end_tags`]: crate::transforms::normalize_end_tags
+#[derive(Debug, Default)]
+pub struct Formatter {
+    config: FormatterConfig,
+}
+
+/// The configuration for a [`Formatter`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FormatterConfig {
+    /// The string used for each level of indentation. Defaults to two spaces.
+    pub indent: Cow<'static, str>,
+    /// Defines when attributes should be placed on separate lines.
+    pub attribute_wrapping: AttributeWrapping,
+    /// Defines how tag and attribute names should be written.
+    pub name_normalization: NameNormalization,
+    /// When `true`, end tags for elements with no child elements are omitted,
+    /// as long as they are in element-only content, in the style of [OFX] 1.x.
+    ///
+    /// Such output can be restored with [`normalize_end_tags`].
+    ///
+    /// [OFX]: https://en.wikipedia.org/wiki/Open_Financial_Exchange
+    /// [`normalize_end_tags`]: crate::transforms::normalize_end_tags
+    pub omit_end_tags: bool,
+}
+
+impl Default for FormatterConfig {
+    /// Creates a new, default `FormatterConfig`. See [`Formatter::new`] for the default settings.
+    fn default() -> Self {
+        FormatterConfig {
+            indent: "  ".into(),
+            attribute_wrapping: Default::default(),
+            name_normalization: Default::default(),
+            omit_end_tags: false,
+        }
+    }
+}
+
+/// When attributes should be placed on separate lines.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AttributeWrapping {
+    /// Always keep attributes on the same line as the tag name.
+    Never,
+    /// Place each attribute on its own line.
+    Always,
+    /// Place each attribute on its own line when the start tag, including
+    /// indentation, would be wider than the given number of characters.
+    MaxWidth(usize),
+}
+
+impl Default for AttributeWrapping {
+    fn default() -> Self {
+        AttributeWrapping::Never
+    }
+}
+
+impl Formatter {
+    /// Creates a new formatter with default settings.
+    ///
+    /// The default settings are:
+    ///
+    /// * Indentation of two spaces
+    /// * Attributes are kept on the same line as the tag name
+    /// * Tag and attribute names are kept in original casing
+    /// * All end tags present in the fragment are written
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Creates a new formatter builder.
+    pub fn builder() -> FormatterBuilder {
+        FormatterBuilder::new()
+    }
+
+    /// Formats the given fragment into a string.
+    pub fn format(&self, fragment: &SgmlFragment) -> String {
+        let mut out = String::new();
+        self.format_to(fragment, &mut out).unwrap();
+        out
+    }
+
+    /// Writes the formatted fragment to the given output.
+    pub fn format_to<W: Write>(&self, fragment: &SgmlFragment, out: W) -> fmt::Result {
+        let events = fragment.as_slice();
+        let mut writer = FormatWriter {
+            config: &self.config,
+            events,
+            out,
+            at_line_start: true,
+        };
+        if writer.is_element_only(0..events.len()) {
+            writer.write_block(0..events.len(), 0)
+        } else {
+            writer.write_inline(0..events.len())
+        }
+    }
+}
+
+/// A fluent interface for configuring formatters.
+#[derive(Debug, Default)]
+pub struct FormatterBuilder {
+    config: FormatterConfig,
+}
+
+impl FormatterBuilder {
+    /// Creates a new builder.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Defines the string used for each level of indentation.
+    pub fn indent(mut self, indent: impl Into<Cow<'static, str>>) -> Self {
+        self.config.indent = indent.into();
+        self
+    }
+
+    /// Defines when attributes should be placed on separate lines.
+    pub fn attribute_wrapping(mut self, attribute_wrapping: AttributeWrapping) -> Self {
+        self.config.attribute_wrapping = attribute_wrapping;
+        self
+    }
+
+    /// Defines how tag and attribute names should be written.
+    pub fn name_normalization(mut self, name_normalization: NameNormalization) -> Self {
+        self.config.name_normalization = name_normalization;
+        self
+    }
+
+    /// Writes all tag and attribute names in lowercase.
+    pub fn lowercase_names(self) -> Self {
+        self.name_normalization(NameNormalization::ToLowercase)
+    }
+
+    /// Writes all tag and attribute names in uppercase.
+    pub fn uppercase_names(self) -> Self {
+        self.name_normalization(NameNormalization::ToUppercase)
+    }
+
+    /// Defines whether end tags of elements without child elements should be omitted.
+    ///
+    /// See [`FormatterConfig::omit_end_tags`].
+    pub fn omit_end_tags(mut self, omit_end_tags: bool) -> Self {
+        self.config.omit_end_tags = omit_end_tags;
+        self
+    }
+
+    /// Builds a new formatter from the given configuration.
+    pub fn build(self) -> Formatter {
+        Formatter {
+            config: self.config,
+        }
+    }
+
+    /// Formats the given fragment with the built formatter.
+    pub fn format(self, fragment: &SgmlFragment) -> String {
+        self.build().format(fragment)
+    }
+
+    /// Returns a [`FormatterConfig`] with the configuration that was built using other methods.
+    pub fn into_config(self) -> FormatterConfig {
+        self.config
+    }
+}
+
+/// A unit of content: either a whole element or a single event.
+enum Item {
+    Element {
+        /// From `OpenStartTag` to `CloseStartTag` or `XmlCloseEmptyElement`, inclusive.
+        start_tag: Range<usize>,
+        /// The position of the matching `EndTag`, if any.
+        end_tag: Option<usize>,
+    },
+    Event(usize),
+}
+
+struct FormatWriter<'f, 'e, 'a, W> {
+    config: &'f FormatterConfig,
+    events: &'e [SgmlEvent<'a>],
+    out: W,
+    at_line_start: bool,
+}
+
+impl<W: Write> FormatWriter<'_, '_, '_, W> {
+    /// Writes items from a range of element-only content, one per line.
+    fn write_block(&mut self, range: Range<usize>, level: usize) -> fmt::Result {
+        let events = self.events;
+        for item in self.items(range) {
+            match item {
+                Item::Event(i) => match &events[i] {
+                    SgmlEvent::Character(s) if text::is_blank(s) => {}
+                    event => {
+                        self.start_line(level)?;
+                        self.write_event(event)?;
+                    }
+                },
+                Item::Element { start_tag, end_tag } => {
+                    self.start_line(level)?;
+                    self.write_start_tag(start_tag.clone(), level)?;
+                    let end_tag = match end_tag {
+                        Some(end_tag) => end_tag,
+                        None => continue,
+                    };
+                    let content = start_tag.end..end_tag;
+                    let has_children = events[content.clone()]
+                        .iter()
+                        .any(|event| matches!(event, SgmlEvent::OpenStartTag { .. }));
+                    if has_children && self.is_element_only(content.clone()) {
+                        self.write_block(content, level + 1)?;
+                        self.start_line(level)?;
+                    } else {
+                        self.write_inline(content)?;
+                        if !has_children && self.config.omit_end_tags {
+                            continue;
+                        }
+                    }
+                    self.write_event(&events[end_tag])?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes a range of events exactly as found.
+    fn write_inline(&mut self, range: Range<usize>) -> fmt::Result {
+        let events = self.events;
+        for event in &events[range] {
+            if let SgmlEvent::Attribute { .. } = event {
+                self.out.write_char(' ')?;
+            }
+            self.write_event(event)?;
+        }
+        Ok(())
+    }
+
+    fn write_start_tag(&mut self, range: Range<usize>, level: usize) -> fmt::Result {
+        let events = &self.events[range];
+        let has_attributes = events
+            .iter()
+            .any(|event| matches!(event, SgmlEvent::Attribute { .. }));
+        let wrap = has_attributes
+            && match self.config.attribute_wrapping {
+                AttributeWrapping::Never => false,
+                AttributeWrapping::Always => true,
+                AttributeWrapping::MaxWidth(max_width) => {
+                    let mut line = self.config.indent.repeat(level);
+                    events.iter().try_for_each(|event| {
+                        if let SgmlEvent::Attribute { .. } = event {
+                            line.push(' ');
+                        }
+                        self.write_event_to(&mut line, event)
+                    })?;
+                    line.chars().count() > max_width
+                }
+            };
+
+        for event in events {
+            if let SgmlEvent::Attribute { .. } = event {
+                if wrap {
+                    self.start_line(level + 1)?;
+                } else {
+                    self.out.write_char(' ')?;
+                }
+            }
+            self.write_event(event)?;
+        }
+        Ok(())
+    }
+
+    fn write_event(&mut self, event: &SgmlEvent) -> fmt::Result {
+        self.at_line_start = false;
+        let config = self.config;
+        write_event(config, &mut self.out, event)
+    }
+
+    fn write_event_to(&self, out: &mut String, event: &SgmlEvent) -> fmt::Result {
+        write_event(self.config, out, event)
+    }
+
+    fn start_line(&mut self, level: usize) -> fmt::Result {
+        if !self.at_line_start {
+            self.out.write_char('\n')?;
+        }
+        for _ in 0..level {
+            self.out.write_str(&self.config.indent)?;
+        }
+        self.at_line_start = true;
+        Ok(())
+    }
+
+    /// Splits a range of events into items, pairing start and end tags.
+    fn items(&self, range: Range<usize>) -> Vec<Item> {
+        let mut items = vec![];
+        let mut i = range.start;
+        while i < range.end {
+            let item = match &self.events[i] {
+                SgmlEvent::OpenStartTag { name } => {
+                    let close = (i..range.end).find(|&j| {
+                        matches!(
+                            self.events[j],
+                            SgmlEvent::CloseStartTag | SgmlEvent::XmlCloseEmptyElement
+                        )
+                    });
+                    match close {
+                        Some(close) => Item::Element {
+                            start_tag: i..close + 1,
+                            end_tag: match self.events[close] {
+                                SgmlEvent::CloseStartTag => {
+                                    self.find_end_tag(name, close + 1..range.end)
+                                }
+                                _ => None,
+                            },
+                        },
+                        // Unterminated start tag: keep the rest as found
+                        None => Item::Element {
+                            start_tag: i..range.end,
+                            end_tag: None,
+                        },
+                    }
+                }
+                _ => Item::Event(i),
+            };
+            i = match item {
+                Item::Element {
+                    end_tag: Some(end), ..
+                } => end + 1,
+                Item::Element { ref start_tag, .. } => start_tag.end,
+                Item::Event(i) => i + 1,
+            };
+            items.push(item);
+        }
+        items
+    }
+
+    /// Finds the end tag matching an element whose content starts at `range.start`.
+    fn find_end_tag(&self, name: &str, range: Range<usize>) -> Option<usize> {
+        let mut depth = 0usize;
+        for i in range {
+            match &self.events[i] {
+                SgmlEvent::OpenStartTag { name: other } if other.eq_ignore_ascii_case(name) => {
+                    depth += 1
+                }
+                SgmlEvent::EndTag { name: other }
+                    if other.eq_ignore_ascii_case(name) || other.is_empty() =>
+                {
+                    if depth == 0 {
+                        return Some(i);
+                    }
+                    depth -= 1;
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
+    /// Checks whether a range contains only elements, markup and whitespace spanning lines.
+    ///
+    /// Whitespace within a single line (like the space in `<B>a</B> <I>b</I>`)
+    /// is considered significant.
+    fn is_element_only(&self, range: Range<usize>) -> bool {
+        self.items(range).into_iter().all(|item| match item {
+            Item::Event(i) => match &self.events[i] {
+                SgmlEvent::Character(s) => {
+                    s.is_empty() || (text::is_blank(s) && s.contains(&['\r', '\n'][..]))
+                }
+                _ => true,
+            },
+            Item::Element { .. } => true,
+        })
+    }
+}
+
+fn write_event<W: Write>(config: &FormatterConfig, mut out: W, event: &SgmlEvent) -> fmt::Result {
+    match event {
+        SgmlEvent::OpenStartTag { name } => write!(out, "<{}", normalize_name(config, name)),
+        SgmlEvent::EndTag { name } => write!(out, "</{}>", normalize_name(config, name)),
+        SgmlEvent::Attribute { name, value } => write!(
+            out,
+            "{}",
+            SgmlEvent::Attribute {
+                name: normalize_name(config, name),
+                value: value.as_deref().map(Cow::from),
+            }
+        ),
+        SgmlEvent::Comment(text) => write!(out, "<!--{}-->", escape_comment(text)),
+        event => write!(out, "{}", event),
+    }
+}
+
+/// Separates consecutive dashes in comment text, so it cannot end the comment early.
+fn escape_comment(text: &str) -> Cow<'_, str> {
+    if !text.contains("--") && !text.ends_with('-') {
+        return text.into();
+    }
+    let mut escaped = String::with_capacity(text.len() + 2);
+    for c in text.chars() {
+        if c == '-' && escaped.ends_with('-') {
+            escaped.push(' ');
+        }
+        escaped.push(c);
+    }
+    if escaped.ends_with('-') {
+        escaped.push(' ');
+    }
+    escaped.into()
+}
+
+fn normalize_name<'n>(config: &FormatterConfig, name: &'n str) -> Cow<'n, str> {
+    config.name_normalization.normalize(name.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transforms::normalize_end_tags;
+
+    const OFX: &str = r##"
+        <OFX><SIGNONMSGSRSV1><SONRS><STATUS><CODE>0<SEVERITY>INFO</STATUS>
+        <DTSERVER>20210101</SONRS></SIGNONMSGSRSV1></OFX>
+    "##;
+
+    #[test]
+    fn test_format_element_only() {
+        let fragment = normalize_end_tags(crate::parse(OFX).unwrap()).unwrap();
+        assert_eq!(
+            format(&fragment),
+            concat!(
+                "<OFX>\n",
+                "  <SIGNONMSGSRSV1>\n",
+                "    <SONRS>\n",
+                "      <STATUS>\n",
+                "        <CODE>0</CODE>\n",
+                "        <SEVERITY>INFO</SEVERITY>\n",
+                "      </STATUS>\n",
+                "      <DTSERVER>20210101</DTSERVER>\n",
+                "    </SONRS>\n",
+                "  </SIGNONMSGSRSV1>\n",
+                "</OFX>",
+            )
+        );
+    }
+
+    #[test]
+    fn test_format_omit_end_tags() {
+        let fragment = normalize_end_tags(crate::parse(OFX).unwrap()).unwrap();
+        let formatted = Formatter::builder()
+            .indent("\t")
+            .omit_end_tags(true)
+            .format(&fragment);
+        assert_eq!(
+            formatted,
+            concat!(
+                "<OFX>\n",
+                "\t<SIGNONMSGSRSV1>\n",
+                "\t\t<SONRS>\n",
+                "\t\t\t<STATUS>\n",
+                "\t\t\t\t<CODE>0\n",
+                "\t\t\t\t<SEVERITY>INFO\n",
+                "\t\t\t</STATUS>\n",
+                "\t\t\t<DTSERVER>20210101\n",
+                "\t\t</SONRS>\n",
+                "\t</SIGNONMSGSRSV1>\n",
+                "</OFX>",
+            )
+        );
+        assert_eq!(
+            normalize_end_tags(crate::parse(&formatted).unwrap()).unwrap(),
+            fragment
+        );
+    }
+
+    #[test]
+    fn test_format_mixed_content_unchanged() {
+        let parser = crate::Parser::builder().trim_whitespace(false).build();
+        let input = "<DOC>\n<P>Hello, <B>bold</B> <I>world</I>!</P>\n<P><B>x</B> </P></DOC>";
+        let fragment = parser.parse(input).unwrap();
+        assert_eq!(
+            format(&fragment),
+            concat!(
+                "<DOC>\n",
+                "  <P>Hello, <B>bold</B> <I>world</I>!</P>\n",
+                "  <P><B>x</B> </P>\n",
+                "</DOC>",
+            )
+        );
+    }
+
+    #[test]
+    fn test_format_top_level_mixed_content_unchanged() {
+        let parser = crate::Parser::builder().trim_whitespace(false).build();
+        let input = "Hello,\n  <B>bold</B> <I>world</I>!\n";
+        let fragment = parser.parse(input).unwrap();
+        assert_eq!(format(&fragment), input);
+    }
+
+    #[test]
+    fn test_format_unterminated_start_tag() {
+        let fragment = SgmlFragment::from(vec![
+            SgmlEvent::OpenStartTag { name: "A".into() },
+            SgmlEvent::CloseStartTag,
+            SgmlEvent::OpenStartTag { name: "B".into() },
+        ]);
+        assert_eq!(format(&fragment), "<A>\n<B");
+
+        let fragment = SgmlFragment::from(vec![
+            SgmlEvent::OpenStartTag { name: "A".into() },
+            SgmlEvent::Attribute {
+                name: "X".into(),
+                value: Some("1".into()),
+            },
+        ]);
+        assert_eq!(
+            Formatter::builder()
+                .attribute_wrapping(AttributeWrapping::Always)
+                .format(&fragment),
+            "<A\n  X=\"1\""
+        );
+    }
+
+    #[test]
+    fn test_format_declarations_and_unpaired_tags() {
+        let input = "<!DOCTYPE HTML><?PI><HTML><BODY><P>text<BR><HR/></BODY></HTML>";
+        let fragment = crate::parse(input).unwrap();
+        assert_eq!(
+            format(&fragment),
+            concat!(
+                "<!DOCTYPE HTML>\n",
+                "<?PI>\n",
+                "<HTML>\n",
+                "  <BODY><P>text<BR><HR/></BODY>\n",
+                "</HTML>",
+            )
+        );
+    }
+
+    #[test]
+    fn test_format_end_tag_case() {
+        let fragment =
+            crate::parse("<LIST>\n<Item>a</ITEM>\n<item><b>b</B></Item>\n</list>").unwrap();
+        assert_eq!(
+            format(&fragment),
+            "<LIST>\n  <Item>a</ITEM>\n  <item>\n    <b>b</B>\n  </Item>\n</list>"
+        );
+    }
+
+    #[test]
+    fn test_format_comments() {
+        let fragment = SgmlFragment::from(vec![
+            SgmlEvent::Comment(" ok ".into()),
+            SgmlEvent::Comment("a--b---c".into()),
+            SgmlEvent::Comment("trailing-".into()),
+        ]);
+        assert_eq!(
+            format(&fragment),
+            "<!-- ok -->\n<!--a- -b- - -c-->\n<!--trailing- -->"
+        );
+    }
+
+    #[test]
+    fn test_format_names() {
+        let fragment = crate::parse("<Root Id=1><Item/></Root>").unwrap();
+        assert_eq!(
+            Formatter::builder().lowercase_names().format(&fragment),
+            "<root id=\"1\">\n  <item/>\n</root>"
+        );
+        assert_eq!(
+            Formatter::builder().uppercase_names().format(&fragment),
+            "<ROOT ID=\"1\">\n  <ITEM/>\n</ROOT>"
+        );
+    }
+
+    #[test]
+    fn test_format_attribute_wrapping() {
+        let fragment = crate::parse("<A><IMG SRC='a.gif' ALT='An image'><B X=1></B></A>").unwrap();
+        assert_eq!(
+            Formatter::builder()
+                .attribute_wrapping(AttributeWrapping::Always)
+                .format(&fragment),
+            concat!(
+                "<A>\n",
+                "  <IMG\n",
+                "    SRC=\"a.gif\"\n",
+                "    ALT=\"An image\">\n",
+                "  <B\n",
+                "    X=\"1\"></B>\n",
+                "</A>",
+            )
+        );
+        assert_eq!(
+            Formatter::builder()
+                .attribute_wrapping(AttributeWrapping::MaxWidth(20))
+                .format(&fragment),
+            concat!(
+                "<A>\n",
+                "  <IMG\n",
+                "    SRC=\"a.gif\"\n",
+                "    ALT=\"An image\">\n",
+                "  <B X=\"1\"></B>\n",
+                "</A>",
+            )
+        );
+    }
+}
@@ -33,12 +33,12 @@ impl<'a> SgmlFragment<'a> {
     }
 
     /// Returns an iterator over references to events.
-    pub fn iter(&self) -> std::slice::Iter<SgmlEvent<'a>> {
+    pub fn iter(&self) -> std::slice::Iter<'_, SgmlEvent<'a>> {
         self.events.iter()
     }
 
     /// Returns an iterator over mutable references to events.
-    pub fn iter_mut(&mut self) -> std::slice::IterMut<SgmlEvent<'a>> {
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, SgmlEvent<'a>> {
         self.events.iter_mut()
     }
 
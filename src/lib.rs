@@ -2,20 +2,26 @@
 //!
 //! For a quick example of deserialization, see [`from_fragment`].
 
+mod element;
 pub mod entities;
 pub mod error;
+pub mod fmt;
 mod fragment;
 pub mod marked_sections;
 pub mod parser;
 pub mod text;
 pub mod transforms;
+pub mod writer;
 
 use std::borrow::Cow;
-use std::fmt::{self, Write};
 
+use marked_sections::{MarkedSectionContent, MarkedSectionStatus};
+
+pub use element::*;
 pub use error::{Error, Result};
 pub use fragment::*;
 pub use parser::{parse, Parser, ParserConfig};
+pub use writer::SgmlWriter;
 
 #[cfg(feature = "serde")]
 pub mod de;
@@ -32,12 +38,18 @@ pub use de::from_fragment;
 ///   optionally followed by one event for each attribute (`HREF="example"`),
 ///   and finally one event for the closing of the tag (`>`).
 /// * End tags (`</A>`), however, are single-event occurrences.
-/// * Comments are *ignored*, and do not show up as events.
+/// * Comments are *ignored*, and do not show up as events,
+///   unless the parser is configured with [`keep_comments`](parser::ParserBuilder::keep_comments).
+///
+/// New kinds of events may be added in future versions,
+/// so matches on this enum should include a wildcard arm.
 #[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
 pub enum SgmlEvent<'a> {
     /// A markup declaration, like `<!SGML ...>` or `<!DOCTYPE ...>`.
     ///
-    /// Markup declarations that are purely comments are ignored.
+    /// Comment declarations (`<!-- ... -->`) are not represented by this event;
+    /// see [`Comment`](SgmlEvent::Comment).
     MarkupDeclaration {
         keyword: Cow<'a, str>,
         body: Cow<'a, str>,
@@ -45,10 +57,24 @@ pub enum SgmlEvent<'a> {
     /// A processing instruction, e.g. `<?EXAMPLE>`
     ProcessingInstruction(Cow<'a, str>),
     /// A marked section, like `<![IGNORE[...]]>`.
+    ///
+    /// The section is kept exactly as found, including any nested marked sections.
+    /// To keep its parsed content instead, see [`ParsedMarkedSection`](SgmlEvent::ParsedMarkedSection).
     MarkedSection {
         status_keywords: Cow<'a, str>,
         section: Cow<'a, str>,
     },
+    /// A marked section, with its status resolved and, for `INCLUDE` sections,
+    /// its content already parsed, including any nested marked sections.
+    ///
+    /// Only produced with [`MarkedSectionHandling::KeepParsed`](parser::MarkedSectionHandling::KeepParsed).
+    ParsedMarkedSection {
+        /// The status resulting from the keywords.
+        status: MarkedSectionStatus,
+        /// The keywords as found in the input, with any parameter entity references unexpanded.
+        status_keywords: Cow<'a, str>,
+        content: MarkedSectionContent<'a>,
+    },
     /// The beginning of a start-element tag, e.g. `<EXAMPLE`.
     ///
     /// Empty start-elements (`<>`) are also represented by this event,
@@ -70,6 +96,14 @@ pub enum SgmlEvent<'a> {
     EndTag { name: Cow<'a, str> },
     /// Any string of characters that is not part of a tag.
     Character(Cow<'a, str>),
+    /// The text of a comment, without its delimiters, e.g. ` example ` in `<!-- example -->`.
+    ///
+    /// A comment declaration with several comments (`<!-- a -- -- b -->`)
+    /// produces one event for each comment. Comments within other markup declarations
+    /// are produced right after the declaration they were found in.
+    ///
+    /// Only produced when enabled with [`keep_comments`](parser::ParserBuilder::keep_comments).
+    Comment(Cow<'a, str>),
 }
 
 impl<'a> SgmlEvent<'a> {
@@ -87,6 +121,15 @@ impl<'a> SgmlEvent<'a> {
                 status_keywords: make_owned(status_keywords),
                 section: make_owned(section),
             },
+            SgmlEvent::ParsedMarkedSection {
+                status,
+                status_keywords,
+                content,
+            } => SgmlEvent::ParsedMarkedSection {
+                status,
+                status_keywords: make_owned(status_keywords),
+                content: content.into_owned(),
+            },
             SgmlEvent::OpenStartTag { name } => SgmlEvent::OpenStartTag {
                 name: make_owned(name),
             },
@@ -100,6 +143,7 @@ impl<'a> SgmlEvent<'a> {
                 name: make_owned(name),
             },
             SgmlEvent::Character(text) => SgmlEvent::Character(make_owned(text)),
+            SgmlEvent::Comment(text) => SgmlEvent::Comment(make_owned(text)),
         }
     }
 }
@@ -111,8 +155,8 @@ fn make_owned<T: ?Sized + ToOwned>(cow: Cow<T>) -> Cow<'static, T> {
     }
 }
 
-impl fmt::Display for SgmlEvent<'_> {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+impl std::fmt::Display for SgmlEvent<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             SgmlEvent::MarkupDeclaration { keyword, body } => {
                 write!(f, "<!{}", keyword)?;
@@ -128,32 +172,22 @@ impl fmt::Display for SgmlEvent<'_> {
             } => {
                 write!(f, "<![{}[{}]]>", status_keywords, section)
             }
+            SgmlEvent::ParsedMarkedSection {
+                status_keywords,
+                content,
+                ..
+            } => write!(f, "<![{}[{}]]>", status_keywords, content),
             SgmlEvent::OpenStartTag { name } => write!(f, "<{}", name),
             SgmlEvent::Attribute { name, value: None } => f.write_str(name),
             SgmlEvent::Attribute {
                 name,
                 value: Some(value),
-            } => {
-                f.write_str(name)?;
-                let escape_ampersand = value.contains('&');
-                if !escape_ampersand && !value.contains('"') {
-                    write!(f, "=\"{}\"", value)
-                } else if !escape_ampersand && !value.contains('\'') {
-                    write!(f, "='{}'", value)
-                } else {
-                    f.write_str("=\"")?;
-                    value.chars().try_for_each(|c| match c {
-                        '"' => f.write_str("&#34;"),
-                        '&' => f.write_str("&#38;"),
-                        c => f.write_char(c),
-                    })?;
-                    f.write_str("\"")
-                }
-            }
+            } => write!(f, "{}={}", name, text::escape_attribute_value(value)),
             SgmlEvent::CloseStartTag => f.write_str(">"),
             SgmlEvent::XmlCloseEmptyElement => f.write_str("/>"),
             SgmlEvent::EndTag { name } => write!(f, "</{}>", name),
-            SgmlEvent::Character(value) => fmt::Display::fmt(&text::escape(value), f),
+            SgmlEvent::Character(value) => std::fmt::Display::fmt(&text::escape(value), f),
+            SgmlEvent::Comment(text) => write!(f, "<!--{}-->", text),
         }
     }
 }
@@ -217,6 +251,7 @@ mod tests {
         assert_eq!(format!("{}", EndTag { name: "".into() }), "</>");
 
         assert_eq!(format!("{}", Character("hello".into())), "hello");
+        assert_eq!(format!("{}", Comment(" hello ".into())), "<!-- hello -->");
     }
 
     #[test]
@@ -1,7 +1,16 @@
 //! Items related to parsing marked sections.
 
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::ops::Range;
+use std::rc::Rc;
 use std::str::FromStr;
 
+use crate::parser::{MarkedSectionHandling, ParserBuilder};
+use crate::{SgmlEvent, SgmlFragment};
+
 const KEYWORDS: &[(&str, MarkedSectionStatus)] = &[
     ("CDATA", MarkedSectionStatus::CData),
     ("RCDATA", MarkedSectionStatus::RcData),
@@ -10,6 +19,41 @@ const KEYWORDS: &[(&str, MarkedSectionStatus)] = &[
     ("TEMP", MarkedSectionStatus::Include),
 ];
 
+/// The content of a [`ParsedMarkedSection`](SgmlEvent::ParsedMarkedSection).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum MarkedSectionContent<'a> {
+    /// The parsed content of an `INCLUDE` section.
+    Events(Vec<SgmlEvent<'a>>),
+    /// The unparsed content of an `IGNORE`, `CDATA` or `RCDATA` section.
+    Text(Cow<'a, str>),
+}
+
+impl<'a> MarkedSectionContent<'a> {
+    /// Detaches the content from the source string, taking ownership of all substrings.
+    pub fn into_owned(self) -> MarkedSectionContent<'static> {
+        match self {
+            MarkedSectionContent::Events(events) => MarkedSectionContent::Events(
+                events.into_iter().map(SgmlEvent::into_owned).collect(),
+            ),
+            MarkedSectionContent::Text(text) => {
+                MarkedSectionContent::Text(Cow::Owned(text.into_owned()))
+            }
+        }
+    }
+}
+
+impl fmt::Display for MarkedSectionContent<'_> {
+    /// Writes the content as it would appear inside the marked section.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MarkedSectionContent::Events(events) => {
+                events.iter().try_for_each(|event| write!(f, "{}", event))
+            }
+            MarkedSectionContent::Text(text) => f.write_str(text),
+        }
+    }
+}
+
 /// The different levels a marked section may have, depending on its keywords.
 #[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
 pub enum MarkedSectionStatus {
@@ -56,6 +100,212 @@ impl FromStr for MarkedSectionStatus {
 #[non_exhaustive]
 pub struct ParseMarkedSectionStatusError;
 
+/// A set of conditions for building one output from a document with conditional text.
+///
+/// Conditional text is written as marked sections whose status keywords are
+/// parameter entity references, like `<![ %audience.admin; [ ... ]]>`.
+/// A profile resolves those references into `INCLUDE` or `IGNORE`:
+///
+/// * For a [condition](Profile::condition) like `audience=admin`, the entity
+///   `audience.admin` resolves to `INCLUDE`, while any other value of the same
+///   condition, like `audience.user`, resolves to `IGNORE`;
+/// * For a [flag](Profile::flag) like `draft`, the entity `draft` resolves to
+///   `INCLUDE` when enabled, or `IGNORE` when disabled.
+///
+/// Any other parameter entity is considered undefined: it resolves to `IGNORE`,
+/// and is listed in [`ProfileReport::undefined_entities`].
+///
+/// # Example
+///
+/// ```rust
+/// # use sgmlish::marked_sections::Profile;
+/// # fn main() -> sgmlish::Result<()> {
+/// let input = r##"
+///     <MANUAL>
+///         <![ %audience.admin; [ <P>Restart the server. ]]>
+///         <![ %audience.user; [ <P>Ask your administrator. ]]>
+///         <![ %platform.linux; [ <P>Run <CODE>systemctl restart</CODE>. ]]>
+///         <![ %beta; [ <P>Not yet available. ]]>
+///     </MANUAL>
+/// "##;
+/// let profile = Profile::new()
+///     .condition("audience", "admin")
+///     .condition("platform", "linux")
+///     .report_dropped_sections(true);
+/// let profiled = profile.parse(sgmlish::Parser::builder(), input)?;
+///
+/// assert_eq!(
+///     profiled.fragment.to_string(),
+///     "<MANUAL><P>Restart the server.<P>Run<CODE>systemctl restart</CODE>.</MANUAL>",
+/// );
+/// assert_eq!(profiled.report.undefined_entities, vec!["beta"]);
+/// let dropped = &profiled.report.dropped_sections;
+/// assert_eq!(dropped.len(), 2);
+/// assert_eq!(dropped[0].status_keywords, "%audience.user;");
+/// assert_eq!(dropped[1].status_keywords, "%beta;");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Profile {
+    conditions: HashMap<String, HashSet<String>>,
+    flags: HashMap<String, bool>,
+    report_dropped_sections: bool,
+}
+
+impl Profile {
+    /// Creates an empty profile, where all conditional sections are undefined.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds a value to the named condition.
+    ///
+    /// A condition may have multiple values, in which case sections for
+    /// any of them are included.
+    pub fn condition(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.conditions
+            .entry(name.into())
+            .or_default()
+            .insert(value.into());
+        self
+    }
+
+    /// Defines a flag, to include or ignore sections marked with its name.
+    pub fn flag(mut self, name: impl Into<String>, enabled: bool) -> Self {
+        self.flags.insert(name.into(), enabled);
+        self
+    }
+
+    /// Changes whether the report should list the sections that were dropped.
+    /// Defaults to `false`.
+    pub fn report_dropped_sections(mut self, report: bool) -> Self {
+        self.report_dropped_sections = report;
+        self
+    }
+
+    /// Returns the status a parameter entity resolves to under this profile,
+    /// or `None` if it is undefined.
+    pub fn status_for(&self, entity: &str) -> Option<MarkedSectionStatus> {
+        let included = match self.flags.get(entity) {
+            Some(&enabled) => enabled,
+            None => {
+                let (name, value) = entity.split_once('.')?;
+                self.conditions.get(name)?.contains(value)
+            }
+        };
+        Some(if included {
+            MarkedSectionStatus::Include
+        } else {
+            MarkedSectionStatus::Ignore
+        })
+    }
+
+    /// Parses the input with the given parser configuration, applying this profile.
+    ///
+    /// The builder's marked section handling and parameter entity resolution
+    /// are replaced; all other settings are kept. Sections are resolved by the parser
+    /// as with [`MarkedSectionHandling::ExpandAll`], so any
+    /// [pipeline](ParserBuilder::pipeline) only sees the profiled content.
+    pub fn parse<'a>(&self, builder: ParserBuilder, input: &'a str) -> crate::Result<Profiled<'a>> {
+        let undefined = Rc::new(RefCell::new(BTreeSet::new()));
+        let profile = self.clone();
+        let undefined_entities = undefined.clone();
+        let mut builder = builder
+            .marked_section_handling(MarkedSectionHandling::ExpandAll)
+            .expand_parameter_entities(move |entity| {
+                let status = profile.status_for(entity).unwrap_or_else(|| {
+                    undefined_entities.borrow_mut().insert(entity.to_owned());
+                    MarkedSectionStatus::Ignore
+                });
+                Some(match status {
+                    MarkedSectionStatus::Ignore => "IGNORE",
+                    _ => "INCLUDE",
+                })
+            });
+
+        // Sections are recorded by their position, as the callback cannot borrow the input
+        let dropped = Rc::new(RefCell::new(BTreeMap::new()));
+        if self.report_dropped_sections {
+            let dropped = dropped.clone();
+            let start = input.as_ptr() as usize;
+            builder = builder.on_ignored_section(move |status_keywords, section| {
+                let range = |s: &str| {
+                    let offset = s.as_ptr() as usize - start;
+                    offset..offset + s.len()
+                };
+                dropped.borrow_mut().insert(
+                    range(section).start,
+                    (range(status_keywords), range(section)),
+                );
+            });
+        }
+        let fragment = builder.parse(input)?;
+
+        let dropped_sections = dropped
+            .borrow()
+            .values()
+            .map(|(status_keywords, section)| {
+                DroppedSection::new(input, status_keywords.clone(), section.clone())
+            })
+            .collect();
+        let undefined_entities = undefined.borrow().iter().cloned().collect();
+        Ok(Profiled {
+            fragment,
+            report: ProfileReport {
+                undefined_entities,
+                dropped_sections,
+            },
+        })
+    }
+}
+
+/// The result of parsing with a [`Profile`].
+#[derive(Clone, Debug)]
+pub struct Profiled<'a> {
+    /// The parsed content, without any of the sections that were dropped.
+    pub fragment: SgmlFragment<'a>,
+    pub report: ProfileReport<'a>,
+}
+
+/// Details on how a [`Profile`] was applied.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ProfileReport<'a> {
+    /// Parameter entities referenced by marked sections, but not defined in the profile,
+    /// in alphabetical order.
+    ///
+    /// Entities referenced only from within dropped sections are not listed,
+    /// as those sections are skipped without being interpreted.
+    pub undefined_entities: Vec<String>,
+    /// The sections that were dropped, in document order.
+    ///
+    /// Only filled when enabled with [`Profile::report_dropped_sections`].
+    pub dropped_sections: Vec<DroppedSection<'a>>,
+}
+
+/// A marked section dropped by a [`Profile`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DroppedSection<'a> {
+    /// The status keywords, as found in the input, e.g. `%audience.user;`.
+    pub status_keywords: &'a str,
+    /// The content of the section.
+    pub section: &'a str,
+    /// The byte offset of the marked section declaration in the input.
+    pub offset: usize,
+}
+
+impl<'a> DroppedSection<'a> {
+    fn new(input: &'a str, status_keywords: Range<usize>, section: Range<usize>) -> Self {
+        // Status keywords cannot contain `<` or `[`, so the last `<![` is where it starts
+        let offset = input[..status_keywords.start].rfind("<![").unwrap_or(0);
+        DroppedSection {
+            status_keywords: input[status_keywords].trim_matches(crate::text::is_sgml_whitespace),
+            section: &input[section],
+            offset,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -114,4 +364,84 @@ mod tests {
             Err("unknown")
         );
     }
+
+    #[test]
+    fn test_profile_status_for() {
+        let profile = Profile::new()
+            .condition("audience", "admin")
+            .condition("audience", "support")
+            .condition("version", "2.1")
+            .flag("draft", true)
+            .flag("beta", false);
+        assert_eq!(
+            profile.status_for("audience.admin"),
+            Some(MarkedSectionStatus::Include)
+        );
+        assert_eq!(
+            profile.status_for("audience.support"),
+            Some(MarkedSectionStatus::Include)
+        );
+        assert_eq!(
+            profile.status_for("audience.user"),
+            Some(MarkedSectionStatus::Ignore)
+        );
+        assert_eq!(
+            profile.status_for("version.2.1"),
+            Some(MarkedSectionStatus::Include)
+        );
+        assert_eq!(
+            profile.status_for("draft"),
+            Some(MarkedSectionStatus::Include)
+        );
+        assert_eq!(
+            profile.status_for("beta"),
+            Some(MarkedSectionStatus::Ignore)
+        );
+        assert_eq!(profile.status_for("audience"), None);
+        assert_eq!(profile.status_for("platform.linux"), None);
+    }
+
+    #[test]
+    fn test_profile_parse() {
+        let input = r##"<DOC>
+            <![ %draft; [ a <![%audience.user;[ b ]]> <![ %os.linux; [ c ]]> ]]>
+            <![ %audience.admin; [ <![ CDATA [ <d> ]]> <![ RCDATA [ &#101; ]]> ]]>
+            <![ %beta; [ <![ %unseen; [ f ]]> ]]>
+        </DOC>"##;
+        let profile = Profile::new()
+            .condition("audience", "admin")
+            .flag("draft", true)
+            .flag("beta", false);
+
+        let profiled = profile.parse(crate::Parser::builder(), input).unwrap();
+        assert_eq!(profiled.fragment.to_string(), "<DOC>a&#60;d&#62;e</DOC>");
+        assert_eq!(profiled.report.undefined_entities, vec!["os.linux"]);
+        assert_eq!(profiled.report.dropped_sections, vec![]);
+
+        let profiled = profile
+            .report_dropped_sections(true)
+            .parse(crate::Parser::builder(), input)
+            .unwrap();
+        let dropped = profiled.report.dropped_sections;
+        assert_eq!(dropped.len(), 3);
+        assert_eq!(dropped[0].status_keywords, "%audience.user;");
+        assert_eq!(dropped[0].section, " b ");
+        assert_eq!(&input[dropped[0].offset..][..4], "<![%");
+        assert_eq!(dropped[1].status_keywords, "%os.linux;");
+        assert_eq!(dropped[1].section, " c ");
+        assert_eq!(dropped[2].status_keywords, "%beta;");
+        assert_eq!(dropped[2].section, " <![ %unseen; [ f ]]> ");
+    }
+
+    #[test]
+    fn test_profile_parse_pipeline() {
+        use crate::transforms::{NormalizeEndTags, Pipeline};
+
+        let builder = crate::Parser::builder().pipeline(Pipeline::new().then(NormalizeEndTags));
+        let profiled = Profile::new()
+            .flag("x", true)
+            .parse(builder, "<A><![%x;[<B>b]]><![%y;[<C>c]]></A>")
+            .unwrap();
+        assert_eq!(profiled.fragment.to_string(), "<A><B>b</B></A>");
+    }
 }
@@ -0,0 +1,270 @@
+use std::borrow::Cow;
+use std::iter::FusedIterator;
+
+use nom::error::context;
+use nom::Finish;
+
+use super::events;
+use super::raw::MarkedSectionEndHandling;
+use super::{util, ContextualizedError, Parser};
+use crate::transforms::FragmentTransform;
+use crate::{text, SgmlEvent, SgmlFragment};
+
+/// An iterator over the documents in a single input.
+///
+/// Created by [`Parser::parse_documents`].
+#[derive(Debug)]
+pub struct Documents<'p, 'a> {
+    parser: &'p Parser,
+    input: &'a str,
+    rest: &'a str,
+}
+
+/// Where the parser is in the current document.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum State {
+    /// Only declarations, processing instructions and comments seen so far.
+    Prolog,
+    /// Inside the root element.
+    Root,
+    /// The root element was closed; only processing instructions and
+    /// comments may follow.
+    Epilogue,
+}
+
+impl<'p, 'a> Documents<'p, 'a> {
+    pub(super) fn new(parser: &'p Parser, input: &'a str) -> Self {
+        Documents {
+            parser,
+            input,
+            rest: input,
+        }
+    }
+
+    fn parse_document(&mut self) -> crate::Result<SgmlFragment<'a>> {
+        let config = &self.parser.config;
+        let mut events = Vec::new();
+        let mut state = State::Prolog;
+        let mut root = None;
+        // How many elements named like the root are open
+        let mut depth = 0;
+
+        while !self.rest.is_empty() {
+            let (rest, item) = context("document content", |input| {
+                events::content_item::<ContextualizedError<_>>(
+                    input,
+                    config,
+                    MarkedSectionEndHandling::TreatAsText,
+                )
+            })(self.rest)
+            .finish()
+            .map_err(|err| crate::Error::ParseError(err.describe(&self.input)))?;
+            let item = item.collect::<Vec<_>>();
+
+            match item.first() {
+                Some(SgmlEvent::MarkupDeclaration { keyword, .. })
+                    if state != State::Prolog && starts_prolog(keyword) =>
+                {
+                    break;
+                }
+                Some(SgmlEvent::OpenStartTag { name }) => {
+                    let is_empty = matches!(item.last(), Some(SgmlEvent::XmlCloseEmptyElement));
+                    match state {
+                        State::Prolog if is_empty => state = State::Epilogue,
+                        State::Prolog => {
+                            root = Some(name.clone());
+                            state = State::Root;
+                            depth = 1;
+                        }
+                        State::Root if !is_empty && is_root(&root, name) => depth += 1,
+                        State::Root => {}
+                        State::Epilogue => break,
+                    }
+                }
+                Some(SgmlEvent::EndTag { name })
+                    if state == State::Root && is_root(&root, name) =>
+                {
+                    depth -= 1;
+                    if depth == 0 {
+                        state = State::Epilogue;
+                    }
+                }
+                Some(SgmlEvent::Character(text)) if state != State::Root => {
+                    if !text::is_blank(text) {
+                        let position = self.rest.trim_start_matches(text::is_sgml_whitespace);
+                        return Err(crate::Error::ParseError(
+                            ContextualizedError {
+                                input: position,
+                                char: None,
+                                error: Some(crate::Error::ParseError(
+                                    "text outside of the root element".to_owned(),
+                                )),
+                                context: vec![],
+                            }
+                            .describe(&self.input),
+                        ));
+                    }
+                    self.rest = rest;
+                    continue;
+                }
+                Some(SgmlEvent::ProcessingInstruction(_)) | Some(SgmlEvent::Comment(_)) => {}
+                Some(_) if state == State::Epilogue => break,
+                _ => {}
+            }
+
+            events.extend(item);
+            self.rest = rest;
+        }
+
+        config
+            .pipeline
+            .transform(self.parser.finish_events(events.into_iter()))
+    }
+}
+
+impl<'p, 'a> Iterator for Documents<'p, 'a> {
+    type Item = crate::Result<SgmlFragment<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (rest, _) = util::spaces::<nom::error::Error<_>>(self.rest).ok()?;
+        self.rest = rest;
+        if self.rest.is_empty() {
+            return None;
+        }
+
+        let result = self.parse_document();
+        if result.is_err() {
+            self.rest = "";
+        }
+        Some(result)
+    }
+}
+
+impl FusedIterator for Documents<'_, '_> {}
+
+/// Returns whether the given name is the root element name.
+///
+/// Names are compared without regard to case, as SGML names usually are.
+fn is_root(root: &Option<Cow<str>>, name: &str) -> bool {
+    root.as_ref()
+        .map_or(false, |root| root.eq_ignore_ascii_case(name))
+}
+
+/// Returns whether a declaration with the given keyword can only appear in a prolog,
+/// and thus marks the start of a new document.
+fn starts_prolog(keyword: &str) -> bool {
+    keyword.eq_ignore_ascii_case("DOCTYPE") || keyword.eq_ignore_ascii_case("SGML")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::WhitespaceHandling;
+
+    fn documents(parser: &Parser, input: &str) -> Vec<String> {
+        parser
+            .parse_documents(input)
+            .map(|doc| doc.unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_root_boundaries() {
+        let parser = Parser::new();
+        assert_eq!(
+            documents(&parser, "<A>1</A>\n<?pi>\n<B>2</B>  <A>3</A>"),
+            vec!["<A>1</A><?pi>", "<B>2</B>", "<A>3</A>"]
+        );
+    }
+
+    #[test]
+    fn test_root_name_case() {
+        let parser = Parser::new();
+        assert_eq!(
+            documents(&parser, "<DOC><doc>1</DOC>2</doc><DOC>3</DOC>"),
+            vec!["<DOC><doc>1</DOC>2</doc>", "<DOC>3</DOC>"]
+        );
+    }
+
+    #[test]
+    fn test_empty_root() {
+        let parser = Parser::new();
+        assert_eq!(
+            documents(&parser, "<doc/><?pi>\n<doc><doc/>1</doc><doc/>"),
+            vec!["<doc/><?pi>", "<doc><doc/>1</doc>", "<doc/>"]
+        );
+    }
+
+    #[test]
+    fn test_prolog_boundaries() {
+        let parser = Parser::new();
+        assert_eq!(
+            documents(
+                &parser,
+                "<!DOCTYPE a><A>1<!USEMAP m>2<!DOCTYPE b><B>3</B><!sgml x>"
+            ),
+            vec![
+                "<!DOCTYPE a><A>1<!USEMAP m>2",
+                "<!DOCTYPE b><B>3</B>",
+                "<!sgml x>"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_nested_elements_named_like_root() {
+        let parser = Parser::new();
+        assert_eq!(
+            documents(&parser, "<DIV><DIV>1</DIV>2</DIV><DIV>3</DIV>"),
+            vec!["<DIV><DIV>1</DIV>2</DIV>", "<DIV>3</DIV>"]
+        );
+    }
+
+    #[test]
+    fn test_text_outside_documents() {
+        let parser = Parser::new();
+        let mut docs = parser.parse_documents("<A>1</A>\n<A>2</A>\n  HEADER:3\n<A>3</A>");
+        assert!(docs.next().unwrap().is_ok());
+        match docs.next().unwrap() {
+            Err(crate::Error::ParseError(message)) => assert!(
+                message.starts_with("parse error at line 3: text outside of the root element"),
+                "unexpected error: {}",
+                message
+            ),
+            result => panic!("expected a parse error, got {:?}", result),
+        }
+        assert!(docs.next().is_none());
+
+        let mut docs = parser.parse_documents("HEADER\n<A>1</A>");
+        assert!(docs.next().unwrap().is_err());
+        let mut docs = parser.parse_documents("<A>1</A>trailer");
+        assert!(docs.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_record_boundaries() {
+        let parser = Parser::builder()
+            .whitespace_handling(WhitespaceHandling::RecordBoundaries)
+            .build();
+        assert_eq!(
+            documents(&parser, "<A>\n1\n</A>\n<A>\n2\n</A>\n"),
+            vec!["<A>1</A>", "<A>2</A>"]
+        );
+    }
+
+    #[test]
+    fn test_empty_input() {
+        let parser = Parser::new();
+        assert!(documents(&parser, "").is_empty());
+        assert!(documents(&parser, " \n ").is_empty());
+    }
+
+    #[test]
+    fn test_error_stops_iteration() {
+        let parser = Parser::new();
+        let mut docs = parser.parse_documents("<A>1</A><B>&x;</B><C>3</C>");
+        assert!(docs.next().unwrap().is_ok());
+        assert!(docs.next().unwrap().is_err());
+        assert!(docs.next().is_none());
+    }
+}
@@ -194,7 +194,7 @@ mod tests {
     fn test_locate() {
         let input = "hello\nworld\n";
         assert_eq!(
-            LocatedLine::locate(input, &input[..]),
+            LocatedLine::locate(input, input),
             LocatedLine {
                 line: "hello",
                 line_number: 1,
@@ -7,15 +7,15 @@ use std::{fmt, mem};
 use nom::branch::alt;
 use nom::combinator::{all_consuming, cut, map, recognize, value};
 use nom::error::{context, ContextError, ErrorKind, FromExternalError, ParseError};
-use nom::multi::{many0, many0_count, many1};
+use nom::multi::{many0, many1};
 use nom::sequence::{terminated, tuple};
 use nom::IResult;
 
-use crate::marked_sections::MarkedSectionStatus;
+use crate::marked_sections::{MarkedSectionContent, MarkedSectionStatus};
 use crate::{Error, SgmlEvent};
 
-use super::raw::{self, comment_declaration, MarkedSectionEndHandling};
-use super::util::{comments_and_spaces, strip_comments_and_spaces_after, strip_spaces_after};
+use super::raw::{self, MarkedSectionEndHandling};
+use super::util::{spaces, strip_spaces_after};
 use super::{MarkedSectionHandling, ParserConfig};
 
 pub fn document_entity<'a, E>(
@@ -27,15 +27,16 @@ where
 {
     all_consuming(map(
         tuple((
-            comments_and_spaces,
+            spaces,
             |input| prolog(input, config),
             context(
                 "document content",
                 cut(|input| content(input, config, MarkedSectionEndHandling::TreatAsText)),
             ),
-            many0(strip_comments_and_spaces_after(|input| {
-                processing_instruction(input, config)
-            })),
+            many0(strip_spaces_after(alt((
+                |input| processing_instruction(input, config),
+                |input| comment_declaration(input, config),
+            )))),
         )),
         |(_, declarations, content, epilogue)| {
             declarations
@@ -46,6 +47,21 @@ where
     ))(input)
 }
 
+/// Matches any sequence of content, such as multiple top-level elements
+/// interleaved with text, or no content at all.
+pub fn fragment<'a, E>(
+    input: &'a str,
+    config: &ParserConfig,
+) -> IResult<&'a str, impl Iterator<Item = SgmlEvent<'a>>, E>
+where
+    E: ParseError<&'a str> + ContextError<&'a str> + FromExternalError<&'a str, Error>,
+{
+    all_consuming(map(
+        many0(|input| content_item(input, config, MarkedSectionEndHandling::TreatAsText)),
+        |items| items.into_iter().flatten(),
+    ))(input)
+}
+
 pub fn prolog<'a, E>(
     input: &'a str,
     config: &ParserConfig,
@@ -56,10 +72,11 @@ where
     context(
         "prolog",
         map(
-            many0(strip_comments_and_spaces_after(alt((
+            many0(strip_spaces_after(alt((
                 |input| markup_declaration(input, config),
                 |input| marked_section_declaration(input, config),
                 |input| processing_instruction(input, config),
+                |input| comment_declaration(input, config),
             )))),
             |events| events.into_iter().flatten().collect(),
         ),
@@ -74,15 +91,69 @@ where
     E: ParseError<&'a str> + ContextError<&'a str>,
 {
     map(raw::markup_declaration, |(keyword, body)| {
-        EventIter::cond(!config.ignore_markup_declarations, || {
-            SgmlEvent::MarkupDeclaration {
+        if config.ignore_markup_declarations {
+            return EventIter::empty();
+        }
+        let parts = match raw::markup_declaration_body::<()>(body) {
+            Ok(("", parts)) if config.keep_comments => parts,
+            _ => {
+                return EventIter::once(SgmlEvent::MarkupDeclaration {
+                    keyword: keyword.into(),
+                    body: body.into(),
+                })
+            }
+        };
+        if !parts
+            .iter()
+            .any(|part| matches!(part, raw::DeclarationPart::Comment(_)))
+        {
+            return EventIter::once(SgmlEvent::MarkupDeclaration {
                 keyword: keyword.into(),
                 body: body.into(),
+            });
+        }
+
+        // Comments are split out of the declaration, and placed right after it
+        let mut text = Vec::with_capacity(parts.len());
+        let mut comments = Vec::new();
+        for part in parts {
+            match part {
+                raw::DeclarationPart::Comment(comment) => {
+                    comments.push(SgmlEvent::Comment(comment.into()))
+                }
+                raw::DeclarationPart::Text(part) => text.push(part),
             }
+        }
+        std::iter::once(SgmlEvent::MarkupDeclaration {
+            keyword: keyword.into(),
+            body: text.join(" ").into(),
         })
+        .chain(comments)
+        .collect()
     })(input)
 }
 
+/// Matches a comment declaration (`<!-- example -->`), producing
+/// [`Comment`](SgmlEvent::Comment) events if [`ParserConfig::keep_comments`] is enabled.
+pub fn comment_declaration<'a, E>(
+    input: &'a str,
+    config: &ParserConfig,
+) -> IResult<&'a str, EventIter<'a>, E>
+where
+    E: ParseError<&'a str> + ContextError<&'a str>,
+{
+    if config.keep_comments {
+        map(raw::comment_declaration_comments, |comments| {
+            comments
+                .into_iter()
+                .map(|comment| SgmlEvent::Comment(comment.into()))
+                .collect()
+        })(input)
+    } else {
+        map(raw::comment_declaration, |_| EventIter::empty())(input)
+    }
+}
+
 /// Matches an entire marked section declaration and
 /// produces events according to [`ParserConfig::marked_section_handling`].
 pub fn marked_section_declaration<'a, E>(
@@ -116,10 +187,58 @@ where
             }
         };
 
-        marked_section_body(rest, status_keywords, status, config)
+        if config.marked_section_handling == MarkedSectionHandling::KeepParsed {
+            return parsed_marked_section_body(rest, raw_status_keywords, status, config);
+        }
+        let (after, events) = marked_section_body(rest, status_keywords, status, config)?;
+        if let (MarkedSectionStatus::Ignore, MarkedSectionHandling::ExpandAll, Some(f)) = (
+            status,
+            config.marked_section_handling,
+            &config.ignored_section_fn,
+        ) {
+            let section_len = rest.len() - after.len() - "]]>".len();
+            f(raw_status_keywords, &rest[..section_len]);
+        }
+        Ok((after, events))
     })(input)
 }
 
+/// Matches the marked section + end inside a marked section declaration and
+/// produces a [`ParsedMarkedSection`](SgmlEvent::ParsedMarkedSection) event,
+/// for [`MarkedSectionHandling::KeepParsed`].
+pub fn parsed_marked_section_body<'a, E>(
+    input: &'a str,
+    raw_status_keywords: &'a str,
+    status: MarkedSectionStatus,
+    config: &ParserConfig,
+) -> IResult<&'a str, EventIter<'a>, E>
+where
+    E: ParseError<&'a str> + ContextError<&'a str> + FromExternalError<&'a str, Error>,
+{
+    let (rest, content) = match status {
+        MarkedSectionStatus::Include => map(
+            |input| marked_section_content(input, config),
+            |events| MarkedSectionContent::Events(events.collect()),
+        )(input),
+        MarkedSectionStatus::Ignore => map(raw::marked_section_body_ignore, |text| {
+            MarkedSectionContent::Text(text.into())
+        })(input),
+        MarkedSectionStatus::CData | MarkedSectionStatus::RcData => {
+            map(raw::marked_section_body_character_data, |text| {
+                MarkedSectionContent::Text(text.into())
+            })(input)
+        }
+    }?;
+    Ok((
+        rest,
+        EventIter::once(SgmlEvent::ParsedMarkedSection {
+            status,
+            status_keywords: raw_status_keywords.into(),
+            content,
+        }),
+    ))
+}
+
 /// Matches the marked section + end inside a marked section declaration and
 /// produces events according to [`ParserConfig::marked_section_handling`].
 pub fn marked_section_body<'a, E>(
@@ -140,9 +259,9 @@ where
                 MarkedSectionStatus::CData => raw::marked_section_body_character_data(input),
                 MarkedSectionStatus::RcData => raw::marked_section_body_character_data(input),
                 MarkedSectionStatus::Include => terminated(
-                    recognize(|input| {
-                        content(input, config, MarkedSectionEndHandling::StopParsing)
-                    }),
+                    recognize(many0(|input| {
+                        content_item(input, config, MarkedSectionEndHandling::StopParsing)
+                    })),
                     raw::marked_section_end,
                 )(input),
             }?;
@@ -170,17 +289,32 @@ where
                     )),
                 ))
             }
-            MarkedSectionStatus::Include => terminated(
-                map(
-                    |input| content(input, config, MarkedSectionEndHandling::StopParsing),
-                    EventIter::from_iter,
-                ),
-                raw::marked_section_body_character_data,
+            MarkedSectionStatus::Include => map(
+                |input| marked_section_content(input, config),
+                EventIter::from_iter,
             )(input),
         },
     }
 }
 
+/// Matches the content of an `INCLUDE` marked section, which may be empty,
+/// and the marked section end.
+fn marked_section_content<'a, E>(
+    input: &'a str,
+    config: &ParserConfig,
+) -> IResult<&'a str, impl Iterator<Item = SgmlEvent<'a>>, E>
+where
+    E: ParseError<&'a str> + ContextError<&'a str> + FromExternalError<&'a str, Error>,
+{
+    terminated(
+        map(
+            many0(|input| content_item(input, config, MarkedSectionEndHandling::StopParsing)),
+            |items| items.into_iter().flatten(),
+        ),
+        raw::marked_section_end,
+    )(input)
+}
+
 pub fn processing_instruction<'a, E>(
     input: &'a str,
     config: &ParserConfig,
@@ -204,16 +338,13 @@ pub fn content<'a, E>(
 where
     E: ParseError<&'a str> + ContextError<&'a str> + FromExternalError<&'a str, Error>,
 {
-    map(
-        many1(terminated(
-            |input| content_item(input, config, mse),
-            many0_count(comment_declaration),
-        )),
-        |items| items.into_iter().flatten(),
-    )(input)
+    map(many1(|input| content_item(input, config, mse)), |items| {
+        items.into_iter().flatten()
+    })(input)
 }
 
-/// Matches a single unit of content --- a tag, text data, processing instruction, or section declaration.
+/// Matches a single unit of content --- a tag, text data, processing instruction,
+/// marked section declaration, comment declaration, or other markup declaration.
 pub fn content_item<'a, E>(
     input: &'a str,
     config: &ParserConfig,
@@ -228,6 +359,8 @@ where
         map(|input| end_tag(input, config), EventIter::once),
         |input| processing_instruction(input, config),
         |input| marked_section_declaration(input, config),
+        |input| comment_declaration(input, config),
+        |input| markup_declaration(input, config),
         // When all else fails, sinalize we expected at least opening a tag
         |input| Err(nom::Err::Error(E::from_char(input, '<'))),
     ))(input)
@@ -713,6 +846,110 @@ mod tests {
         assert_eq!(events.next(), None);
     }
 
+    #[test]
+    fn test_comment_declaration() {
+        let input = r##"<!-- one -- -- two --> "##;
+
+        let (rest, mut events) = comment_declaration::<E>(input, &Default::default()).unwrap();
+        assert_eq!(rest, " ");
+        assert_eq!(events.next(), None);
+
+        let config = Parser::builder().keep_comments(true).into_config();
+        let (rest, events) = comment_declaration::<E>(input, &config).unwrap();
+        assert_eq!(rest, " ");
+        assert_eq!(
+            events.collect::<Vec<_>>(),
+            vec![
+                SgmlEvent::Comment(" one ".into()),
+                SgmlEvent::Comment(" two ".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_document_entity_keep_comments() {
+        const SAMPLE: &str = r#"
+            <!-- License: public domain -->
+            <!DOCTYPE EXAMPLE [ <!ENTITY x "y" -- inline comment --> ]>
+            <!ENTITY z -- first -- "-- not a comment --"
+                -- second -->
+            <!---- -- Revision 1 ---- Revision 2 -->
+            <EXAMPLE>text<!-- inside -->more</EXAMPLE>
+            <!-- end -->
+        "#;
+
+        let config = Parser::builder().keep_comments(true).into_config();
+        let (rest, events) = document_entity::<E>(SAMPLE, &config).unwrap();
+        assert!(rest.is_empty(), "rest: {:?}", rest);
+        assert_eq!(
+            events.collect::<Vec<_>>(),
+            vec![
+                SgmlEvent::Comment(" License: public domain ".into()),
+                SgmlEvent::MarkupDeclaration {
+                    keyword: "DOCTYPE".into(),
+                    body: r#"EXAMPLE [ <!ENTITY x "y" -- inline comment --> ]"#.into(),
+                },
+                SgmlEvent::MarkupDeclaration {
+                    keyword: "ENTITY".into(),
+                    body: r#"z "-- not a comment --""#.into(),
+                },
+                SgmlEvent::Comment(" first ".into()),
+                SgmlEvent::Comment(" second ".into()),
+                SgmlEvent::Comment("".into()),
+                SgmlEvent::Comment(" Revision 1 ".into()),
+                SgmlEvent::Comment(" Revision 2 ".into()),
+                SgmlEvent::OpenStartTag {
+                    name: "EXAMPLE".into()
+                },
+                SgmlEvent::CloseStartTag,
+                SgmlEvent::Character("text".into()),
+                SgmlEvent::Comment(" inside ".into()),
+                SgmlEvent::Character("more".into()),
+                SgmlEvent::EndTag {
+                    name: "EXAMPLE".into()
+                },
+                SgmlEvent::Comment(" end ".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_content_markup_declarations() {
+        let input = r#"<P>one<!USEMAP map1><!ENTITY e "x">two</P>"#;
+        let (rest, events) = content::<E>(
+            input,
+            &Default::default(),
+            MarkedSectionEndHandling::TreatAsText,
+        )
+        .unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(
+            events.collect::<Vec<_>>(),
+            vec![
+                SgmlEvent::OpenStartTag { name: "P".into() },
+                SgmlEvent::CloseStartTag,
+                SgmlEvent::Character("one".into()),
+                SgmlEvent::MarkupDeclaration {
+                    keyword: "USEMAP".into(),
+                    body: "map1".into(),
+                },
+                SgmlEvent::MarkupDeclaration {
+                    keyword: "ENTITY".into(),
+                    body: r#"e "x""#.into(),
+                },
+                SgmlEvent::Character("two".into()),
+                SgmlEvent::EndTag { name: "P".into() },
+            ]
+        );
+
+        let config = Parser::builder()
+            .ignore_markup_declarations(true)
+            .into_config();
+        let (_, events) =
+            content::<E>(input, &config, MarkedSectionEndHandling::TreatAsText).unwrap();
+        assert_eq!(events.count(), 5);
+    }
+
     #[test]
     fn test_start_tag() {
         let config = Default::default();
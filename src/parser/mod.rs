@@ -1,16 +1,21 @@
 //! Access to configuration and inner workings of the parser.
 
 use std::borrow::Cow;
+use std::collections::HashMap;
 use std::fmt;
 
 use crate::marked_sections::MarkedSectionStatus;
-use crate::{entities, text, SgmlFragment};
+use crate::transforms::{FragmentTransform, Pipeline};
+use crate::{entities, text, SgmlEvent, SgmlFragment};
 
+mod documents;
 mod error;
 pub mod events;
 pub mod raw;
+mod records;
 pub mod util;
 
+pub use documents::Documents;
 pub use error::*;
 
 /// Parses the given string using a [`Parser`] with default settings,
@@ -20,7 +25,7 @@ pub use error::*;
 /// to deserialize into a specific type.
 ///
 /// [`from_fragment`]: crate::from_fragment
-pub fn parse(input: &str) -> crate::Result<SgmlFragment> {
+pub fn parse(input: &str) -> crate::Result<SgmlFragment<'_>> {
     Parser::new().parse(input)
 }
 
@@ -42,7 +47,7 @@ impl Parser {
     ///
     /// The default settings are:
     ///
-    /// * Whitespace is automatically trimmed
+    /// * Whitespace surrounding text is automatically trimmed
     /// * Tag and attribute names are kept in original casing
     /// * Only `CDATA` and `RCDATA` marked sections are allowed;
     ///   `IGNORE` and `INCLUDE` blocks, for instance, are rejected,
@@ -59,13 +64,20 @@ impl Parser {
         ParserBuilder::new()
     }
 
+    /// Returns the configuration used by this parser.
+    pub fn config(&self) -> &ParserConfig {
+        &self.config
+    }
+
     /// Parses the given input.
     ///
     /// Parse errors are flattened into a descriptive string.
     /// To capture the full error, use [`parse_with_detailed_errors`](Parser::parse_with_detailed_errors).
     pub fn parse<'a>(&self, input: &'a str) -> crate::Result<SgmlFragment<'a>> {
-        self.parse_with_detailed_errors::<ContextualizedError<_>>(input)
-            .map_err(|err| crate::Error::ParseError(err.describe(&input)))
+        let fragment = self
+            .parse_with_detailed_errors::<ContextualizedError<_>>(input)
+            .map_err(|err| crate::Error::ParseError(err.describe(&input)))?;
+        self.config.pipeline.transform(fragment)
     }
 
     /// Parses the given input, using a different error handler for parser errors,
@@ -73,6 +85,8 @@ impl Parser {
     ///
     /// Different [`nom`] error handlers may be used to adjust between speed and
     /// level of detail in error messages.
+    ///
+    /// The [pipeline](ParserBuilder::pipeline), if any, is not applied.
     pub fn parse_with_detailed_errors<'a, E>(&self, input: &'a str) -> Result<SgmlFragment<'a>, E>
     where
         E: nom::error::ParseError<&'a str>
@@ -83,33 +97,101 @@ impl Parser {
         let (rest, events) = events::document_entity::<E>(input, &self.config).finish()?;
         debug_assert!(rest.is_empty(), "document_entity should be all_consuming");
 
-        let events = events.collect::<Vec<_>>();
+        Ok(self.finish_events(events))
+    }
+
+    /// Parses the given input as a fragment of content, rather than a full document.
+    ///
+    /// Unlike [`parse`](Parser::parse), the input may contain any number of
+    /// top-level elements and text, including none at all.
+    pub fn parse_fragment<'a>(&self, input: &'a str) -> crate::Result<SgmlFragment<'a>> {
+        use nom::Finish;
+        let (rest, events) = events::fragment::<ContextualizedError<_>>(input, &self.config)
+            .finish()
+            .map_err(|err| crate::Error::ParseError(err.describe(&input)))?;
+        debug_assert!(rest.is_empty(), "fragment should be all_consuming");
+
+        self.config.pipeline.transform(self.finish_events(events))
+    }
+
+    /// Parses an input containing multiple documents one after another,
+    /// such as a stream of concatenated responses.
+    ///
+    /// A new document starts at each `DOCTYPE` or `SGML` declaration after the
+    /// first start tag, or at any markup other than processing instructions
+    /// and comments following the close of the root element.
+    /// Processing instructions and comments between documents belong to the
+    /// preceding document; text outside of the root element, other than
+    /// whitespace, is reported as an error.
+    ///
+    /// Documents are parsed lazily, as the iterator advances, and the
+    /// [pipeline](ParserBuilder::pipeline) is applied to each of them;
+    /// iteration stops after the first error.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # fn main() -> sgmlish::Result<()> {
+    /// let parser = sgmlish::Parser::new();
+    /// let docs = parser
+    ///     .parse_documents("<OFX>first</OFX>\n<OFX>second</OFX>")
+    ///     .collect::<sgmlish::Result<Vec<_>>>()?;
+    /// assert_eq!(docs.len(), 2);
+    /// assert_eq!(docs[1].to_string(), "<OFX>second</OFX>");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn parse_documents<'p, 'a>(&'p self, input: &'a str) -> Documents<'p, 'a> {
+        Documents::new(self, input)
+    }
 
-        Ok(SgmlFragment::from(events))
+    fn finish_events<'a>(&self, events: impl Iterator<Item = SgmlEvent<'a>>) -> SgmlFragment<'a> {
+        let mut events = events.collect::<Vec<_>>();
+        if self.config.whitespace_handling == WhitespaceHandling::RecordBoundaries {
+            records::suppress_record_ends(&mut events);
+        }
+        SgmlFragment::from(events)
     }
 }
 
 /// The configuration for a [`Parser`].
 pub struct ParserConfig {
-    /// When `true`, leading and trailing whitespace from
-    /// [`Character`](crate::SgmlEvent::Character) events will be trimmed.
-    /// Defaults to `true`.
-    pub trim_whitespace: bool,
+    /// Defines how whitespace in [`Character`](crate::SgmlEvent::Character) events
+    /// should be handled. Defaults to [`WhitespaceHandling::Trim`].
+    pub whitespace_handling: WhitespaceHandling,
     /// Defines how tag and attribute names should be handled.
     pub name_normalization: NameNormalization,
     pub marked_section_handling: MarkedSectionHandling,
     pub ignore_markup_declarations: bool,
     pub ignore_processing_instructions: bool,
+    /// When `true`, comments are kept as [`Comment`](crate::SgmlEvent::Comment) events.
+    /// Defaults to `false`.
+    pub keep_comments: bool,
     entity_fn: Option<EntityFn>,
     parameter_entity_fn: Option<EntityFn>,
+    function_characters: HashMap<String, char>,
+    pipeline: Pipeline,
+    ignored_section_fn: Option<IgnoredSectionFn>,
 }
 
 type EntityFn = Box<dyn Fn(&str) -> Option<Cow<'static, str>>>;
+/// Receives the raw status keywords and the content of each `IGNORE` section
+/// dropped with [`MarkedSectionHandling::ExpandAll`], both as slices of the input.
+type IgnoredSectionFn = Box<dyn Fn(&str, &str)>;
 
 impl ParserConfig {
+    /// Returns whether whitespace surrounding text is trimmed.
+    ///
+    /// This replaces the former `trim_whitespace` field, which was superseded by
+    /// [`whitespace_handling`](ParserConfig::whitespace_handling).
+    #[deprecated(since = "0.3.0", note = "use the `whitespace_handling` field instead")]
+    pub fn trim_whitespace(&self) -> bool {
+        self.whitespace_handling == WhitespaceHandling::Trim
+    }
+
     /// Trims the given text according to the configured rules.
     pub fn trim<'a>(&self, text: &'a str) -> &'a str {
-        if self.trim_whitespace {
+        if self.whitespace_handling == WhitespaceHandling::Trim {
             text.trim_matches(text::is_sgml_whitespace)
         } else {
             text
@@ -121,7 +203,16 @@ impl ParserConfig {
     where
         E: nom::error::ContextError<&'a str> + nom::error::FromExternalError<&'a str, crate::Error>,
     {
-        let f = self.entity_fn.as_deref().unwrap_or(&|_| None);
+        let f = |entity: &str| {
+            let function = entity
+                .strip_prefix('#')
+                .and_then(|name| self.function_characters.get(&name.to_uppercase()));
+            match (function, &self.entity_fn) {
+                (Some(c), _) => Some(c.to_string().into()),
+                (None, Some(entity_fn)) => entity_fn(entity),
+                (None, None) => None,
+            }
+        };
         entities::expand_entities(rcdata, f).map_err(|err| into_nom_failure(rcdata, err))
     }
 
@@ -138,6 +229,35 @@ impl ParserConfig {
     }
 }
 
+/// How whitespace surrounding text should be handled.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WhitespaceHandling {
+    /// Trim all leading and trailing whitespace from text,
+    /// dropping text that is only whitespace.
+    Trim,
+    /// Keep all whitespace as-is.
+    Keep,
+    /// Apply the record boundary rules from ISO 8879 (§7.6.1), treating
+    /// each line break as a record end (RE).
+    ///
+    /// All other whitespace is kept, but a line break is dropped when it is:
+    ///
+    /// * the first in an element, right after the start tag;
+    /// * the last in an element, right before the end tag;
+    /// * ending a line that only contained markup, like a processing instruction
+    ///   or a comment.
+    ///
+    /// This matches what a conforming SGML parser passes on to the application
+    /// in mixed content, and is best suited for literal text, like `<PRE>` elements.
+    RecordBoundaries,
+}
+
+impl Default for WhitespaceHandling {
+    fn default() -> Self {
+        WhitespaceHandling::Trim
+    }
+}
+
 /// How tag and attribute names should be handled.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum NameNormalization {
@@ -172,14 +292,21 @@ impl NameNormalization {
 /// How marked sections (`<![CDATA[example]]>`) should be handled.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum MarkedSectionHandling {
-    /// Keep all marked sections as raw [`MarkedSection`](crate::SgmlEvent::MarkedSection)
-    /// events in the stream.
+    /// Keep all marked sections in the stream, as raw
+    /// [`MarkedSection`](crate::SgmlEvent::MarkedSection) events.
+    ///
+    /// Nested sections are kept as part of the outermost section's content.
     KeepUnmodified,
     /// Expand `CDATA` and `RCDATA` sections into [`Character`][crate::SgmlEvent::Character]
     /// events, treat anything else as a parsing error.
     AcceptOnlyCharacterData,
     /// Expand also `INCLUDE` and `IGNORE` sections.
     ExpandAll,
+    /// Keep all marked sections as [`ParsedMarkedSection`](crate::SgmlEvent::ParsedMarkedSection)
+    /// events, with their resolved status, and the parsed content of `INCLUDE` sections.
+    ///
+    /// Nested sections are kept as a tree, with each section in its parent's content.
+    KeepParsed,
 }
 
 impl Default for MarkedSectionHandling {
@@ -231,13 +358,17 @@ impl Default for ParserConfig {
     /// Creates a new, default `ParserConfig`. See [`Parser::new`] for the default settings.
     fn default() -> Self {
         ParserConfig {
-            trim_whitespace: true,
+            whitespace_handling: Default::default(),
             name_normalization: Default::default(),
             marked_section_handling: Default::default(),
             ignore_markup_declarations: false,
             ignore_processing_instructions: false,
+            keep_comments: false,
             entity_fn: None,
             parameter_entity_fn: None,
+            function_characters: HashMap::new(),
+            pipeline: Pipeline::new(),
+            ignored_section_fn: None,
         }
     }
 }
@@ -245,10 +376,12 @@ impl Default for ParserConfig {
 impl fmt::Debug for ParserConfig {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("ParserConfig")
-            .field("trim_whitespace", &self.trim_whitespace)
+            .field("whitespace_handling", &self.whitespace_handling)
             .field("process_marked_sections", &self.marked_section_handling)
             .field("expand_entity", &omit(&self.entity_fn))
             .field("expand_parameter_entity", &omit(&self.parameter_entity_fn))
+            .field("function_characters", &self.function_characters)
+            .field("pipeline", &self.pipeline)
             .finish()
     }
 }
@@ -267,8 +400,36 @@ impl ParserBuilder {
     }
 
     /// Defines whether whitespace surrounding text should be trimmed.
-    pub fn trim_whitespace(mut self, trim_whitespace: bool) -> Self {
-        self.config.trim_whitespace = trim_whitespace;
+    ///
+    /// This is a shorthand for [`whitespace_handling`](ParserBuilder::whitespace_handling)
+    /// with either [`Trim`](WhitespaceHandling::Trim) or [`Keep`](WhitespaceHandling::Keep).
+    pub fn trim_whitespace(self, trim_whitespace: bool) -> Self {
+        self.whitespace_handling(if trim_whitespace {
+            WhitespaceHandling::Trim
+        } else {
+            WhitespaceHandling::Keep
+        })
+    }
+
+    /// Changes how whitespace surrounding text should be handled.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use sgmlish::parser::WhitespaceHandling;
+    /// # fn main() -> sgmlish::Result<()> {
+    /// let sgml = sgmlish::Parser::builder()
+    ///     .whitespace_handling(WhitespaceHandling::RecordBoundaries)
+    ///     .parse("<PRE>\n  indented\n    more\n</PRE>")?;
+    /// assert_eq!(
+    ///     sgml.as_slice()[2],
+    ///     sgmlish::SgmlEvent::Character("  indented\n    more".into()),
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn whitespace_handling(mut self, whitespace_handling: WhitespaceHandling) -> Self {
+        self.config.whitespace_handling = whitespace_handling;
         self
     }
 
@@ -338,6 +499,34 @@ impl ParserBuilder {
         self
     }
 
+    /// Defines a function character, to be accepted in character references (`&#NAME;`).
+    ///
+    /// The function characters of the reference concrete syntax (`RE`, `RS`, `SPACE`
+    /// and `TAB`) are always recognized; use this for any others declared in the
+    /// SGML declaration. Names are matched ignoring case.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # fn main() -> sgmlish::Result<()> {
+    /// let sgml = sgmlish::Parser::builder()
+    ///     .function_character("ZWSP", '\u{200b}')
+    ///     .parse("<P TITLE='a&#zwsp;b'>c&#ZWSP;d&#SPACE;e</P>")?;
+    /// assert_eq!(sgml.as_slice()[1], sgmlish::SgmlEvent::Attribute {
+    ///     name: "TITLE".into(),
+    ///     value: Some("a\u{200b}b".into()),
+    /// });
+    /// assert_eq!(sgml.as_slice()[3], sgmlish::SgmlEvent::Character("c\u{200b}d e".into()));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn function_character(mut self, name: &str, c: char) -> Self {
+        self.config
+            .function_characters
+            .insert(name.to_uppercase(), c);
+        self
+    }
+
     /// Changes how marked sections should be handled.
     pub fn marked_section_handling(mut self, mode: MarkedSectionHandling) -> Self {
         self.config.marked_section_handling = mode;
@@ -366,6 +555,43 @@ impl ParserBuilder {
         self
     }
 
+    /// Changes whether comments (`<!-- example -->`) should be kept in the event stream
+    /// as [`Comment`](crate::SgmlEvent::Comment) events, or ignored.
+    ///
+    /// Comments that are part of other markup declarations (`<!ENTITY x -- example -- "v">`)
+    /// are split out of the declaration body, and placed right after the declaration,
+    /// so the above becomes `<!ENTITY x "v">` followed by a `Comment(" example ")`.
+    /// Comments inside a declaration subset (`<!DOCTYPE x [ ... ]>`) are kept in the body.
+    /// When comments are not kept, declaration bodies are left unchanged.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use sgmlish::SgmlEvent;
+    /// # fn main() -> sgmlish::Result<()> {
+    /// let sgml = sgmlish::Parser::builder()
+    ///     .keep_comments(true)
+    ///     .parse("<!-- Copyright -- -- Revision 2 --><A>text<!---->")?;
+    /// assert_eq!(sgml.as_slice()[0], SgmlEvent::Comment(" Copyright ".into()));
+    /// assert_eq!(sgml.as_slice()[1], SgmlEvent::Comment(" Revision 2 ".into()));
+    /// assert_eq!(sgml.as_slice()[5], SgmlEvent::Comment("".into()));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn keep_comments(mut self, keep: bool) -> Self {
+        self.config.keep_comments = keep;
+        self
+    }
+
+    /// Sets a function to be called for each `IGNORE` section dropped by the parser.
+    pub(crate) fn on_ignored_section<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&str, &str) + 'static,
+    {
+        self.config.ignored_section_fn = Some(Box::new(f));
+        self
+    }
+
     /// Builds a new parser from the given configuration.
     pub fn build(self) -> Parser {
         Parser {
@@ -373,11 +599,32 @@ impl ParserBuilder {
         }
     }
 
+    /// Defines a pipeline of transforms to be applied after parsing,
+    /// such as inserting implied end tags, so that the parsed data is ready
+    /// for deserialization.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use sgmlish::transforms::{NormalizeEndTags, Pipeline};
+    /// # fn main() -> sgmlish::Result<()> {
+    /// let sgml = sgmlish::Parser::builder()
+    ///     .pipeline(Pipeline::new().then(NormalizeEndTags))
+    ///     .parse("<STMTTRN><TRNTYPE>DEBIT</STMTTRN>")?;
+    /// assert_eq!(sgml.to_string(), "<STMTTRN><TRNTYPE>DEBIT</TRNTYPE></STMTTRN>");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn pipeline(mut self, pipeline: Pipeline) -> Self {
+        self.config.pipeline = pipeline;
+        self
+    }
+
     /// Parses the given input with the built parser.
     ///
     /// To reuse the same parser for multiple inputs, use [`build()`](ParserBuilder::build)
     /// then [`Parser::parse()`].
-    pub fn parse(self, input: &str) -> crate::Result<SgmlFragment> {
+    pub fn parse(self, input: &str) -> crate::Result<SgmlFragment<'_>> {
         self.build().parse(input)
     }
 
@@ -413,6 +660,27 @@ mod tests {
 
         let config = Parser::builder().trim_whitespace(false).into_config();
         assert_eq!(config.trim(" hello "), " hello ");
+        #[allow(deprecated)]
+        {
+            assert!(!config.trim_whitespace());
+        }
+
+        let config = Parser::builder()
+            .whitespace_handling(WhitespaceHandling::RecordBoundaries)
+            .into_config();
+        assert_eq!(config.trim(" hello "), " hello ");
+    }
+
+    #[test]
+    fn test_parse_fragment() {
+        let parser = Parser::new();
+        let fragment = parser
+            .parse_fragment("text <A>1</A> <B>2</B> more")
+            .unwrap();
+        assert_eq!(fragment.to_string(), "text<A>1</A><B>2</B>more");
+        assert_eq!(parser.parse_fragment("").unwrap().len(), 0);
+        assert_eq!(parser.parse_fragment("  \n").unwrap().len(), 0);
+        assert!(parser.parse_fragment("<A>&x;</A>").is_err());
     }
 
     #[test]
@@ -424,6 +692,24 @@ mod tests {
         };
     }
 
+    #[test]
+    fn test_config_parse_rcdata_function_characters() {
+        let config = ParserBuilder::new()
+            .function_character("nul", '\0')
+            .expand_entities(|entity| match entity {
+                "#NUL" => unreachable!(),
+                "#CUSTOM" => Some("!"),
+                _ => None,
+            })
+            .into_config();
+        let result = config.parse_rcdata::<nom::error::Error<_>>("a&#NUL;&#Nul;&#CUSTOM;&#TAB;");
+        assert_eq!(result, Ok("a\0\0!\t".into()));
+        match config.parse_rcdata::<nom::error::Error<_>>("&#OTHER;") {
+            Err(nom::Err::Error(err)) => assert_eq!(err.input, "&#OTHER;"),
+            err => panic!("expected nom::Err::Error, got: {:?}", err),
+        };
+    }
+
     #[test]
     fn test_name_normalization_unchanged() {
         assert!(matches!(
@@ -7,7 +7,7 @@ use nom::bytes::complete::{is_not, tag, take_till, take_while};
 use nom::character::complete::{char, none_of, one_of, satisfy};
 use nom::combinator::{cut, map, not, opt, peek, recognize, verify};
 use nom::error::{context, ContextError, ErrorKind, ParseError};
-use nom::multi::many0_count;
+use nom::multi::{many0, many0_count};
 use nom::sequence::{delimited, pair, preceded, terminated, tuple};
 use nom::IResult;
 
@@ -31,6 +31,31 @@ where
     )(input)
 }
 
+/// Matches an entire comment declaration (`<!-- a -- -- b -->`)
+/// and outputs each of its comments (`[" a ", " b "]`).
+pub fn comment_declaration_comments<'a, E>(input: &'a str) -> IResult<&'a str, Vec<&'a str>, E>
+where
+    E: ParseError<&'a str> + ContextError<&'a str>,
+{
+    context(
+        "comment declaration",
+        delimited(
+            pair(tag("<!"), peek(one_of("->"))),
+            map(
+                opt(pair(comment, many0(preceded(spaces, comment)))),
+                |comments| match comments {
+                    Some((first, mut rest)) => {
+                        rest.insert(0, first);
+                        rest
+                    }
+                    None => vec![],
+                },
+            ),
+            context(r##"comment declaration close ("-->")"##, cut(char('>'))),
+        ),
+    )(input)
+}
+
 /// Matches `-- example --` and outputs `  example  `.
 pub fn comment<'a, E>(input: &'a str) -> IResult<&'a str, &'a str, E>
 where
@@ -69,6 +94,46 @@ where
     )(input)
 }
 
+/// A piece of the body of a markup declaration, as split by [`markup_declaration_body`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeclarationPart<'a> {
+    /// A comment (`-- example --`), without its delimiters
+    Comment(&'a str),
+    /// Anything else, like a name, a literal or a declaration subset
+    Text(&'a str),
+}
+
+/// Splits the body of a markup declaration, as output by [`markup_declaration`],
+/// into its comments and other parts, discarding the spaces between them.
+///
+/// Comments inside declaration subsets (`[...]`) belong to the declarations
+/// within, so they are kept as part of the subset.
+pub fn markup_declaration_body<'a, E>(
+    input: &'a str,
+) -> IResult<&'a str, Vec<DeclarationPart<'a>>, E>
+where
+    E: ParseError<&'a str> + ContextError<&'a str>,
+{
+    terminated(
+        many0(preceded(
+            spaces,
+            alt((
+                map(comment, DeclarationPart::Comment),
+                map(
+                    recognize(alt((
+                        quoted_attribute_value,
+                        declaration_subset,
+                        terminated(tag("-"), not(tag("-"))),
+                        is_not("<>\"'[- \n\r\t"),
+                    ))),
+                    DeclarationPart::Text,
+                ),
+            )),
+        )),
+        spaces,
+    )(input)
+}
+
 fn declaration_subset<'a, E>(input: &'a str) -> IResult<&'a str, &'a str, E>
 where
     E: ParseError<&'a str> + ContextError<&'a str>,
@@ -112,7 +177,7 @@ where
 /// Matches the content for `CDATA` and `RCDATA` marked sections,
 /// immediately after [`marked_section_start_and_keywords`].
 ///
-/// These sections do nest, meaning they end on the first `]]>` found.
+/// These sections do not nest, meaning they end on the first `]]>` found.
 pub fn marked_section_body_character_data<'a, E>(input: &'a str) -> IResult<&'a str, &'a str, E>
 where
     E: ParseError<&'a str> + ContextError<&'a str>,
@@ -407,6 +472,40 @@ mod tests {
         comment_declaration::<E>("<!-- comment -- >").unwrap_err();
     }
 
+    #[test]
+    fn test_comment_declaration_comments() {
+        assert_eq!(
+            comment_declaration_comments::<E>("<!-- comment 1 -- \n -- comment 2-->x"),
+            Ok(("x", vec![" comment 1 ", " comment 2"]))
+        );
+        assert_eq!(
+            comment_declaration_comments::<E>("<!---->"),
+            Ok(("", vec![""]))
+        );
+        assert_eq!(comment_declaration_comments::<E>("<!>"), Ok(("", vec![])));
+        comment_declaration_comments::<E>("<! -- comment -->").unwrap_err();
+        comment_declaration_comments::<E>("<!-- comment -- >").unwrap_err();
+    }
+
+    #[test]
+    fn test_markup_declaration_body() {
+        use DeclarationPart::*;
+        assert_eq!(
+            markup_declaration_body::<E>(r#"x -- a -- - "--b--" [ -- c -- ] --d-- "#),
+            Ok((
+                "",
+                vec![
+                    Text("x"),
+                    Comment(" a "),
+                    Text("-"),
+                    Text(r#""--b--""#),
+                    Text("[ -- c -- ]"),
+                    Comment("d"),
+                ]
+            ))
+        );
+    }
+
     #[test]
     fn test_markup_declaration() {
         assert_eq!(
@@ -0,0 +1,244 @@
+//! Record boundary handling, as described in ISO 8879 §7.6.1.
+//!
+//! Input is not actually split into records; instead, each line break
+//! (`\r\n`, `\n` or `\r`) is taken to be a record end (RE), and record starts
+//! (RS) are implied at the beginning of each line, and are always ignored.
+
+use std::borrow::Cow;
+
+use crate::SgmlEvent;
+
+/// Removes record ends that are not considered data:
+///
+/// * The first RE in an element, if no data or subelement precedes it;
+/// * The last RE in an element, if no data or subelement follows it;
+/// * An RE ending a record that contained only markup (e.g. a processing instruction).
+///
+/// Unless comments are kept, they are not visible at this point, so a record with
+/// only a comment is recognized only by the text on either side of it being split.
+/// Line breaks produced by character references (`&#RE;`) cannot be
+/// distinguished from actual record ends, and are also subject to these rules.
+pub(crate) fn suppress_record_ends(events: &mut Vec<SgmlEvent>) {
+    for i in 0..events.len() {
+        let starts_with_break = match &events[i] {
+            SgmlEvent::Character(text) => line_break_len(text) > 0,
+            _ => continue,
+        };
+        if !starts_with_break {
+            continue;
+        }
+        let suppress = match preceding_data(events, i) {
+            // First RE in the element
+            Some(SgmlEvent::CloseStartTag) => true,
+            // A record containing only markup
+            Some(SgmlEvent::Character(text)) => trailing_line_break_len(text) > 0,
+            _ => false,
+        };
+        if suppress {
+            if let SgmlEvent::Character(text) = &mut events[i] {
+                let len = line_break_len(text);
+                remove(text, 0..len);
+            }
+        }
+    }
+
+    for i in (0..events.len()).rev() {
+        let len = match &events[i] {
+            SgmlEvent::Character(text) => trailing_line_break_len(text),
+            _ => continue,
+        };
+        if len == 0 {
+            continue;
+        }
+        // Last RE in the element
+        if let Some(SgmlEvent::EndTag { .. }) = following_data(events, i) {
+            if let SgmlEvent::Character(text) = &mut events[i] {
+                let end = text.len();
+                remove(text, end - len..end);
+            }
+        }
+    }
+
+    events.retain(|event| !matches!(event, SgmlEvent::Character(text) if text.is_empty()));
+}
+
+/// Finds the closest event before `index` that is either data or a tag.
+fn preceding_data<'e, 'a>(events: &'e [SgmlEvent<'a>], index: usize) -> Option<&'e SgmlEvent<'a>> {
+    events[..index].iter().rev().find(|event| is_data(event))
+}
+
+/// Finds the closest event after `index` that is either data or a tag.
+fn following_data<'e, 'a>(events: &'e [SgmlEvent<'a>], index: usize) -> Option<&'e SgmlEvent<'a>> {
+    events[index + 1..].iter().find(|event| is_data(event))
+}
+
+fn is_data(event: &SgmlEvent) -> bool {
+    match event {
+        SgmlEvent::MarkupDeclaration { .. }
+        | SgmlEvent::ParsedMarkedSection { .. }
+        | SgmlEvent::ProcessingInstruction(_)
+        | SgmlEvent::Comment(_) => false,
+        SgmlEvent::Character(text) => !text.is_empty(),
+        _ => true,
+    }
+}
+
+fn line_break_len(text: &str) -> usize {
+    if text.starts_with("\r\n") {
+        2
+    } else if text.starts_with('\n') || text.starts_with('\r') {
+        1
+    } else {
+        0
+    }
+}
+
+fn trailing_line_break_len(text: &str) -> usize {
+    if text.ends_with("\r\n") {
+        2
+    } else if text.ends_with('\n') || text.ends_with('\r') {
+        1
+    } else {
+        0
+    }
+}
+
+fn remove(text: &mut Cow<str>, range: std::ops::Range<usize>) {
+    match text {
+        Cow::Borrowed(s) => {
+            *s = if range.start == 0 {
+                &s[range.end..]
+            } else {
+                &s[..range.start]
+            }
+        }
+        Cow::Owned(s) => {
+            s.replace_range(range, "");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn suppress(events: Vec<SgmlEvent>) -> Vec<SgmlEvent> {
+        let mut events = events;
+        suppress_record_ends(&mut events);
+        events
+    }
+
+    fn start(name: &str) -> [SgmlEvent<'_>; 2] {
+        [
+            SgmlEvent::OpenStartTag { name: name.into() },
+            SgmlEvent::CloseStartTag,
+        ]
+    }
+
+    fn end(name: &str) -> SgmlEvent<'_> {
+        SgmlEvent::EndTag { name: name.into() }
+    }
+
+    fn text(text: &str) -> SgmlEvent<'_> {
+        SgmlEvent::Character(text.into())
+    }
+
+    #[test]
+    fn test_first_and_last_record_end() {
+        let [open, close] = start("PRE");
+        assert_eq!(
+            suppress(vec![
+                open.clone(),
+                close.clone(),
+                text("\n  one\n\n  two\n"),
+                end("PRE")
+            ]),
+            vec![open, close, text("  one\n\n  two"), end("PRE")]
+        );
+    }
+
+    #[test]
+    fn test_crlf() {
+        let [open, close] = start("PRE");
+        assert_eq!(
+            suppress(vec![
+                open.clone(),
+                close.clone(),
+                SgmlEvent::Character(String::from("\r\nline\r\n").into()),
+                end("PRE")
+            ]),
+            vec![open, close, text("line"), end("PRE")]
+        );
+    }
+
+    #[test]
+    fn test_record_ends_around_subelements_are_data() {
+        let [a_open, a_close] = start("A");
+        let [b_open, b_close] = start("B");
+        assert_eq!(
+            suppress(vec![
+                a_open.clone(),
+                a_close.clone(),
+                text("\n"),
+                b_open.clone(),
+                b_close.clone(),
+                text("x"),
+                end("B"),
+                text("\n"),
+                b_open.clone(),
+                b_close.clone(),
+                text("y"),
+                end("B"),
+                text("\n"),
+                end("A"),
+            ]),
+            vec![
+                a_open,
+                a_close,
+                b_open.clone(),
+                b_close.clone(),
+                text("x"),
+                end("B"),
+                text("\n"),
+                b_open,
+                b_close,
+                text("y"),
+                end("B"),
+                end("A"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_markup_only_records() {
+        let [open, close] = start("P");
+        let pi = SgmlEvent::ProcessingInstruction("<?pi>".into());
+        assert_eq!(
+            suppress(vec![
+                open.clone(),
+                close.clone(),
+                text("\n"),
+                pi.clone(),
+                text("\none\n"),
+                pi.clone(),
+                text("\ntwo\n"),
+                // A comment used to be here
+                text("\nthree\n"),
+                pi.clone(),
+                text("\n"),
+                end("P"),
+            ]),
+            vec![
+                open,
+                close,
+                pi.clone(),
+                text("one\n"),
+                pi.clone(),
+                text("two\n"),
+                text("three"),
+                pi,
+                end("P"),
+            ]
+        );
+    }
+}
@@ -39,14 +39,14 @@ pub fn take_until_terminated<'a, E: ParseError<&'a str> + ContextError<&'a str>>
 }
 
 /// Matches zero or more space characters.
-pub fn spaces<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&str, &str, E> {
+pub fn spaces<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, &'a str, E> {
     multispace0(input)
 }
 
 /// Matches zero or more comments and spaces.
 pub fn comments_and_spaces<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
     input: &'a str,
-) -> IResult<&str, &str, E> {
+) -> IResult<&'a str, &'a str, E> {
     recognize(many0_count(alt((raw::comment_declaration, multispace1))))(input)
 }
 
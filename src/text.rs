@@ -31,6 +31,7 @@ pub(crate) fn is_blank(s: &str) -> bool {
 /// SGML text (`<`, `>`, `&`) using character references (`&#60;`).
 ///
 /// This is not safe for attribute values!
+/// Use [`escape_attribute_value`] for those.
 ///
 /// # Examples
 ///
@@ -47,7 +48,7 @@ pub(crate) fn is_blank(s: &str) -> bool {
 /// # use sgmlish::text::escape;
 /// assert_eq!(escape("Sonic & Knuckles").to_string(), "Sonic &#38; Knuckles");
 /// ```
-pub fn escape(text: &str) -> Escape {
+pub fn escape(text: &str) -> Escape<'_> {
     Escape::new(text)
 }
 
@@ -127,6 +128,320 @@ impl fmt::Display for Escape<'_> {
     }
 }
 
+/// A limit on the characters that may be output verbatim,
+/// as dictated by the character set of the consumer.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Charset {
+    /// Only US-ASCII characters, up to U+007F.
+    Ascii,
+    /// Only ISO 8859-1 (Latin-1) characters, up to U+00FF.
+    Latin1,
+    /// Any Unicode character.
+    Unicode,
+}
+
+impl Charset {
+    /// Returns whether the character can be represented in this character set.
+    pub fn contains(self, c: char) -> bool {
+        match self {
+            Charset::Ascii => c.is_ascii(),
+            Charset::Latin1 => c <= '\u{ff}',
+            Charset::Unicode => true,
+        }
+    }
+}
+
+/// Returns an iterator that escapes text like [`escape`], and also replaces
+/// characters that are not part of the given character set with references.
+///
+/// For each character that needs to be replaced, the closure is called to find
+/// the name of an entity that represents it, producing an entity reference (`&eacute;`).
+/// If the closure returns `None`, a character reference (`&#233;`) is used instead.
+///
+/// The closure is also consulted for the markup characters `<`, `>` and `&`,
+/// so that named references like `&lt;` can be produced.
+///
+/// # Example
+///
+/// ```rust
+/// # use std::collections::HashMap;
+/// # use sgmlish::text::{encode_entities, Charset};
+/// let mut names = HashMap::new();
+/// names.insert('é', "eacute");
+///
+/// let encoded = encode_entities("café & crème brûlée", Charset::Ascii, |c| names.get(&c));
+/// assert_eq!(encoded.to_string(), "caf&eacute; &#38; cr&#232;me br&#251;l&eacute;e");
+/// ```
+pub fn encode_entities<F, T>(text: &str, charset: Charset, f: F) -> EncodeEntities<'_, F>
+where
+    F: FnMut(char) -> Option<T>,
+    T: AsRef<str>,
+{
+    EncodeEntities::new(text, charset, f)
+}
+
+/// The return type of [`encode_entities`].
+#[derive(Clone)]
+pub struct EncodeEntities<'a, F> {
+    escape_ampersand: bool,
+    charset: Charset,
+    lookup: F,
+    chars: std::str::Chars<'a>,
+    escape_buffer: String,
+    escape_position: usize,
+}
+
+impl<'a, F> EncodeEntities<'a, F> {
+    fn new(text: &'a str, charset: Charset, lookup: F) -> Self {
+        EncodeEntities {
+            escape_ampersand: true,
+            charset,
+            lookup,
+            chars: text.chars(),
+            escape_buffer: String::new(),
+            escape_position: 0,
+        }
+    }
+
+    /// Changes whether ampersands (`&`) should be escaped.
+    pub fn set_escape_ampersand(&mut self, escape_ampersand: bool) {
+        self.escape_ampersand = escape_ampersand;
+    }
+
+    fn needs_escape(&self, c: char) -> bool {
+        match c {
+            '<' | '>' => true,
+            '&' => self.escape_ampersand,
+            c => !self.charset.contains(c),
+        }
+    }
+}
+
+impl<F, T> Iterator for EncodeEntities<'_, F>
+where
+    F: FnMut(char) -> Option<T>,
+    T: AsRef<str>,
+{
+    type Item = char;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(c) = self.escape_buffer[self.escape_position..].chars().next() {
+            self.escape_position += c.len_utf8();
+            return Some(c);
+        }
+        let c = self.chars.next()?;
+        if !self.needs_escape(c) {
+            return Some(c);
+        }
+
+        self.escape_buffer.clear();
+        self.escape_position = 0;
+        match (self.lookup)(c) {
+            Some(name) => write!(self.escape_buffer, "{};", name.as_ref()),
+            None => write!(self.escape_buffer, "#{};", c as u32),
+        }
+        .unwrap();
+        Some('&')
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (min, _) = self.chars.size_hint();
+        let escape_len = self.escape_buffer[self.escape_position..].chars().count();
+        // Entity names can be arbitrarily long
+        (min + escape_len, None)
+    }
+}
+
+impl<F, T> FusedIterator for EncodeEntities<'_, F>
+where
+    F: FnMut(char) -> Option<T>,
+    T: AsRef<str>,
+{
+}
+
+impl<F, T> fmt::Display for EncodeEntities<'_, F>
+where
+    F: FnMut(char) -> Option<T> + Clone,
+    T: AsRef<str>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.clone().try_for_each(|c| f.write_char(c))
+    }
+}
+
+impl<F> fmt::Debug for EncodeEntities<'_, F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EncodeEntities")
+            .field("escape_ampersand", &self.escape_ampersand)
+            .field("charset", &self.charset)
+            .field("chars", &self.chars)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Returns a value that formats an attribute value as an attribute value literal,
+/// including the delimiting quotes.
+///
+/// By default, the value is delimited with double quotes (`"`), unless it contains
+/// double quotes but no single quotes, in which case single quotes are used.
+/// Ampersands (`&`) and any occurrences of the chosen delimiter are escaped
+/// using character references (`&#38;`, `&#34;`).
+///
+/// Each of these choices can be changed through the returned value's `set_` methods.
+///
+/// # Examples
+///
+/// ```rust
+/// # use sgmlish::text::escape_attribute_value;
+/// assert_eq!(escape_attribute_value("value").to_string(), r#""value""#);
+/// assert_eq!(escape_attribute_value(r#"say "hi""#).to_string(), r#"'say "hi"'"#);
+/// assert_eq!(escape_attribute_value("a&b").to_string(), r#""a&#38;b""#);
+/// ```
+///
+/// Using named references, and omitting quotes when the value is a name token:
+///
+/// ```rust
+/// # use sgmlish::text::escape_attribute_value;
+/// let mut escape = escape_attribute_value(r#"say "hi" & wave"#);
+/// escape.set_named_references(true);
+/// assert_eq!(escape.to_string(), r#""say &quot;hi&quot; &amp; wave""#);
+///
+/// let mut escape = escape_attribute_value("left");
+/// escape.set_unquoted_if_possible(true);
+/// assert_eq!(escape.to_string(), "left");
+/// ```
+pub fn escape_attribute_value(value: &str) -> EscapeAttributeValue<'_> {
+    EscapeAttributeValue::new(value)
+}
+
+/// A quotation mark delimiting an attribute value literal.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Quote {
+    /// Double quotation mark (`"`).
+    Double,
+    /// Single quotation mark, or apostrophe (`'`).
+    Single,
+}
+
+impl Quote {
+    /// Returns the delimiter character.
+    pub fn as_char(self) -> char {
+        match self {
+            Quote::Double => '"',
+            Quote::Single => '\'',
+        }
+    }
+
+    fn other(self) -> Quote {
+        match self {
+            Quote::Double => Quote::Single,
+            Quote::Single => Quote::Double,
+        }
+    }
+}
+
+impl Default for Quote {
+    fn default() -> Self {
+        Quote::Double
+    }
+}
+
+/// The return type of [`escape_attribute_value`].
+#[derive(Clone, Debug)]
+pub struct EscapeAttributeValue<'a> {
+    value: &'a str,
+    preferred_quote: Quote,
+    escape_ampersand: bool,
+    named_references: bool,
+    unquoted_if_possible: bool,
+}
+
+impl<'a> EscapeAttributeValue<'a> {
+    fn new(value: &'a str) -> Self {
+        EscapeAttributeValue {
+            value,
+            preferred_quote: Quote::default(),
+            escape_ampersand: true,
+            named_references: false,
+            unquoted_if_possible: false,
+        }
+    }
+
+    /// Changes which quotation mark should delimit the value.
+    ///
+    /// The other quotation mark is still used when that avoids escaping
+    /// the value altogether.
+    pub fn set_preferred_quote(&mut self, quote: Quote) {
+        self.preferred_quote = quote;
+    }
+
+    /// Changes whether ampersands (`&`) should be escaped.
+    ///
+    /// Disable this if the value already contains entity references that
+    /// should be kept as such.
+    pub fn set_escape_ampersand(&mut self, escape_ampersand: bool) {
+        self.escape_ampersand = escape_ampersand;
+    }
+
+    /// Changes whether to use named entity references (`&quot;`, `&apos;`, `&amp;`)
+    /// instead of numeric character references (`&#34;`, `&#39;`, `&#38;`).
+    ///
+    /// Named references are more readable, but must be declared by the
+    /// document type in order to be understood by SGML parsers.
+    pub fn set_named_references(&mut self, named_references: bool) {
+        self.named_references = named_references;
+    }
+
+    /// Changes whether quotes should be omitted when the value is made only of
+    /// name characters (ASCII letters, digits, `.` and `-`).
+    pub fn set_unquoted_if_possible(&mut self, unquoted_if_possible: bool) {
+        self.unquoted_if_possible = unquoted_if_possible;
+    }
+
+    fn reference(&self, c: char) -> &'static str {
+        match (c, self.named_references) {
+            ('"', false) => "&#34;",
+            ('"', true) => "&quot;",
+            ('\'', false) => "&#39;",
+            ('\'', true) => "&apos;",
+            (_, false) => "&#38;",
+            (_, true) => "&amp;",
+        }
+    }
+}
+
+impl fmt::Display for EscapeAttributeValue<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let value = self.value;
+        if self.unquoted_if_possible && !value.is_empty() && value.chars().all(is_name_char) {
+            return f.write_str(value);
+        }
+
+        let escape_ampersand = self.escape_ampersand && value.contains('&');
+        let preferred = self.preferred_quote;
+        let quote = if !escape_ampersand
+            && value.contains(preferred.as_char())
+            && !value.contains(preferred.other().as_char())
+        {
+            preferred.other().as_char()
+        } else {
+            preferred.as_char()
+        };
+
+        f.write_char(quote)?;
+        value.chars().try_for_each(|c| match c {
+            '&' if escape_ampersand => f.write_str(self.reference(c)),
+            c if c == quote => f.write_str(self.reference(c)),
+            c => f.write_char(c),
+        })?;
+        f.write_char(quote)
+    }
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '.' || c == '-'
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -180,6 +495,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::identity_op)]
     fn test_escape_iter() {
         let mut escape = escape("wo<rld");
         assert_eq!(escape.size_hint(), (2, Some(30)));
@@ -217,4 +533,117 @@ mod tests {
         assert_eq!(escape.next(), None);
         assert_eq!(escape.size_hint(), (0, Some(0)));
     }
+
+    #[test]
+    fn test_escape_attribute_value_quotes() {
+        assert_eq!(escape_attribute_value("").to_string(), "\"\"");
+        assert_eq!(escape_attribute_value("a'b").to_string(), "\"a'b\"");
+        assert_eq!(escape_attribute_value("a\"b").to_string(), "'a\"b'");
+        assert_eq!(
+            escape_attribute_value("a\"b'c").to_string(),
+            "\"a&#34;b'c\""
+        );
+
+        let mut esc = escape_attribute_value("a'b");
+        esc.set_preferred_quote(Quote::Single);
+        assert_eq!(esc.to_string(), "\"a'b\"");
+        let mut esc = escape_attribute_value("a\"b'c");
+        esc.set_preferred_quote(Quote::Single);
+        assert_eq!(esc.to_string(), "'a\"b&#39;c'");
+    }
+
+    #[test]
+    fn test_escape_attribute_value_ampersand() {
+        assert_eq!(escape_attribute_value("a&b").to_string(), "\"a&#38;b\"");
+        assert_eq!(
+            escape_attribute_value("a&b\"").to_string(),
+            "\"a&#38;b&#34;\""
+        );
+
+        let mut esc = escape_attribute_value("&lt;\"");
+        esc.set_escape_ampersand(false);
+        assert_eq!(esc.to_string(), "'&lt;\"'");
+    }
+
+    #[test]
+    fn test_escape_attribute_value_named_references() {
+        let mut esc = escape_attribute_value("a&b\"c'");
+        esc.set_named_references(true);
+        assert_eq!(esc.to_string(), "\"a&amp;b&quot;c'\"");
+        esc.set_preferred_quote(Quote::Single);
+        assert_eq!(esc.to_string(), "'a&amp;b\"c&apos;'");
+    }
+
+    #[test]
+    fn test_escape_attribute_value_unquoted() {
+        let unquoted = |value| {
+            let mut esc = escape_attribute_value(value);
+            esc.set_unquoted_if_possible(true);
+            esc.to_string()
+        };
+        assert_eq!(unquoted("foo"), "foo");
+        assert_eq!(unquoted("1.5-beta"), "1.5-beta");
+        assert_eq!(unquoted(""), "\"\"");
+        assert_eq!(unquoted("a b"), "\"a b\"");
+        assert_eq!(unquoted("a_b"), "\"a_b\"");
+        assert_eq!(unquoted("\u{e9}"), "\"\u{e9}\"");
+    }
+
+    #[test]
+    fn test_charset_contains() {
+        assert!(Charset::Ascii.contains('~'));
+        assert!(!Charset::Ascii.contains('\u{e9}'));
+        assert!(Charset::Latin1.contains('\u{e9}'));
+        assert!(!Charset::Latin1.contains('\u{20ac}'));
+        assert!(Charset::Unicode.contains('\u{1f970}'));
+    }
+
+    #[test]
+    fn test_encode_entities() {
+        let names = |c| match c {
+            '\u{e9}' => Some("eacute"),
+            '<' => Some("lt"),
+            _ => None,
+        };
+        let encode = |text, charset| encode_entities(text, charset, names).to_string();
+
+        assert_eq!(encode("hello!", Charset::Ascii), "hello!");
+        assert_eq!(
+            encode("<caf\u{e9}>", Charset::Ascii),
+            "&lt;caf&eacute;&#62;"
+        );
+        assert_eq!(
+            encode("\u{e7}a co\u{fb}te 5\u{20ac}", Charset::Ascii),
+            "&#231;a co&#251;te 5&#8364;"
+        );
+        assert_eq!(
+            encode("\u{e7}a co\u{fb}te 5\u{20ac}", Charset::Latin1),
+            "\u{e7}a co\u{fb}te 5&#8364;"
+        );
+        assert_eq!(
+            encode("\u{e9}\u{1f970}&", Charset::Unicode),
+            "\u{e9}\u{1f970}&#38;"
+        );
+    }
+
+    #[test]
+    fn test_encode_entities_disable_ampersand() {
+        let mut encode = encode_entities("&eacute; \u{e9}", Charset::Ascii, |_| None::<&str>);
+        encode.set_escape_ampersand(false);
+        assert_eq!(encode.to_string(), "&eacute; &#233;");
+    }
+
+    #[test]
+    fn test_encode_entities_iter() {
+        let mut encode = encode_entities("a\u{e9}", Charset::Ascii, |_| Some("x"));
+        assert_eq!(encode.size_hint(), (1, None));
+        assert_eq!(encode.next(), Some('a'));
+        assert_eq!(encode.next(), Some('&'));
+        assert_eq!(encode.size_hint(), (2, None));
+        assert_eq!(encode.next(), Some('x'));
+        assert_eq!(encode.next(), Some(';'));
+        assert_eq!(encode.size_hint(), (0, None));
+        assert_eq!(encode.next(), None);
+        assert_eq!(encode.next(), None);
+    }
 }
@@ -5,7 +5,11 @@
 //! [`SgmlFragment`]: crate::SgmlFragment
 
 pub use self::normalize_end_tags::*;
+pub use self::pipeline::*;
 pub use self::transform::*;
+pub use self::whitespace::*;
 
 mod normalize_end_tags;
+mod pipeline;
 mod transform;
+mod whitespace;
@@ -98,10 +98,8 @@ pub fn normalize_end_tags(mut fragment: SgmlFragment) -> Result<SgmlFragment, No
                 stack.push(name);
                 next_insertion_point = i;
             }
-            SgmlEvent::Character(text) => {
-                if next_insertion_point == i + 1 && text::is_blank(text) {
-                    next_insertion_point = i;
-                }
+            SgmlEvent::Character(text) if next_insertion_point == i + 1 && text::is_blank(text) => {
+                next_insertion_point = i;
             }
             _ => {}
         }
@@ -0,0 +1,618 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::parser::NameNormalization;
+use crate::transforms::{normalize_end_tags, WhitespacePolicy};
+use crate::{entities, SgmlEvent, SgmlFragment};
+
+/// A single pass over a fragment, as part of a [`Pipeline`].
+///
+/// Functions taking and returning a fragment, like
+/// `fn(SgmlFragment) -> sgmlish::Result<SgmlFragment>`, also implement this trait.
+pub trait FragmentTransform {
+    /// Transforms the given fragment.
+    fn transform<'a>(&self, fragment: SgmlFragment<'a>) -> crate::Result<SgmlFragment<'a>>;
+}
+
+impl<F> FragmentTransform for F
+where
+    F: for<'a> Fn(SgmlFragment<'a>) -> crate::Result<SgmlFragment<'a>>,
+{
+    fn transform<'a>(&self, fragment: SgmlFragment<'a>) -> crate::Result<SgmlFragment<'a>> {
+        self(fragment)
+    }
+}
+
+/// A sequence of transform passes, applied in order.
+///
+/// A pipeline can also be configured in a parser with
+/// [`ParserBuilder::pipeline`](crate::parser::ParserBuilder::pipeline),
+/// so that parsing produces data ready for deserialization.
+///
+/// # Example
+///
+/// ```rust
+/// # use sgmlish::parser::NameNormalization;
+/// # use sgmlish::transforms::{DropMarkupDeclarations, NormalizeEndTags, NormalizeNames, Pipeline, RenameElements};
+/// # fn main() -> sgmlish::Result<()> {
+/// let pipeline = Pipeline::new()
+///     .then(DropMarkupDeclarations)
+///     .then(NormalizeNames(NameNormalization::ToUppercase))
+///     .then(RenameElements::new().rename("AMOUNT", "TRNAMT"))
+///     .then(NormalizeEndTags);
+///
+/// let sgml = sgmlish::Parser::builder()
+///     .pipeline(pipeline)
+///     .parse("<!DOCTYPE ofx><stmttrn><trntype>DEBIT<amount>-12.34</stmttrn>")?;
+/// assert_eq!(
+///     sgml.to_string(),
+///     "<STMTTRN><TRNTYPE>DEBIT</TRNTYPE><TRNAMT>-12.34</TRNAMT></STMTTRN>",
+/// );
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Default)]
+pub struct Pipeline {
+    passes: Vec<Box<dyn FragmentTransform>>,
+}
+
+impl Pipeline {
+    /// Creates an empty pipeline.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds a pass to the end of the pipeline.
+    pub fn then(mut self, pass: impl FragmentTransform + 'static) -> Self {
+        self.passes.push(Box::new(pass));
+        self
+    }
+
+    /// Returns `true` if the pipeline has no passes.
+    pub fn is_empty(&self) -> bool {
+        self.passes.is_empty()
+    }
+
+    /// Returns the number of passes in the pipeline.
+    pub fn len(&self) -> usize {
+        self.passes.len()
+    }
+}
+
+impl FragmentTransform for Pipeline {
+    fn transform<'a>(&self, fragment: SgmlFragment<'a>) -> crate::Result<SgmlFragment<'a>> {
+        self.passes
+            .iter()
+            .try_fold(fragment, |fragment, pass| pass.transform(fragment))
+    }
+}
+
+impl fmt::Debug for Pipeline {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Pipeline")
+            .field("passes", &self.passes.len())
+            .finish()
+    }
+}
+
+/// Inserts omitted end tags; see [`normalize_end_tags`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NormalizeEndTags;
+
+impl FragmentTransform for NormalizeEndTags {
+    fn transform<'a>(&self, fragment: SgmlFragment<'a>) -> crate::Result<SgmlFragment<'a>> {
+        Ok(normalize_end_tags(fragment)?)
+    }
+}
+
+impl FragmentTransform for WhitespacePolicy {
+    fn transform<'a>(&self, fragment: SgmlFragment<'a>) -> crate::Result<SgmlFragment<'a>> {
+        Ok(self.apply(fragment))
+    }
+}
+
+/// Normalizes the case of all tag and attribute names.
+///
+/// To apply different rules to elements and attributes, use [`MapNames`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NormalizeNames(pub NameNormalization);
+
+impl FragmentTransform for NormalizeNames {
+    fn transform<'a>(&self, fragment: SgmlFragment<'a>) -> crate::Result<SgmlFragment<'a>> {
+        Ok(map_events(fragment, |event| match event {
+            SgmlEvent::OpenStartTag { name } => SgmlEvent::OpenStartTag {
+                name: self.0.normalize(name),
+            },
+            SgmlEvent::EndTag { name } => SgmlEvent::EndTag {
+                name: self.0.normalize(name),
+            },
+            SgmlEvent::Attribute { name, value } => SgmlEvent::Attribute {
+                name: self.0.normalize(name),
+                value,
+            },
+            event => event,
+        }))
+    }
+}
+
+/// Renames elements, in both start and end tags.
+///
+/// Names are compared in a case-sensitive manner.
+#[derive(Clone, Debug, Default)]
+pub struct RenameElements {
+    names: HashMap<String, String>,
+}
+
+impl RenameElements {
+    /// Creates a pass that renames no elements.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Renames elements named `from` to `to`.
+    pub fn rename(mut self, from: impl Into<String>, to: impl Into<String>) -> Self {
+        self.names.insert(from.into(), to.into());
+        self
+    }
+
+    fn rename_one<'a>(&self, name: Cow<'a, str>) -> Cow<'a, str> {
+        match self.names.get(name.as_ref()) {
+            Some(new_name) => new_name.clone().into(),
+            None => name,
+        }
+    }
+}
+
+impl FragmentTransform for RenameElements {
+    fn transform<'a>(&self, fragment: SgmlFragment<'a>) -> crate::Result<SgmlFragment<'a>> {
+        Ok(map_events(fragment, |event| match event {
+            SgmlEvent::OpenStartTag { name } => SgmlEvent::OpenStartTag {
+                name: self.rename_one(name),
+            },
+            SgmlEvent::EndTag { name } => SgmlEvent::EndTag {
+                name: self.rename_one(name),
+            },
+            event => event,
+        }))
+    }
+}
+
+/// Maps element and attribute names, with separate rules for each.
+///
+/// Rules are applied in the order they were added, each one seeing the name
+/// produced by the previous ones.
+/// Element rules apply to both start and end tags.
+/// Entity references are left unchanged.
+///
+/// # Example
+///
+/// ```rust
+/// # use sgmlish::parser::NameNormalization;
+/// # use sgmlish::transforms::{FragmentTransform, MapNames};
+/// # fn main() -> sgmlish::Result<()> {
+/// let pass = MapNames::new()
+///     .normalize_elements(NameNormalization::ToUppercase)
+///     .map_elements(|name| name.strip_prefix("INTU.").map(String::from))
+///     .rename_attribute("lang", "xml:lang");
+///
+/// let fragment = sgmlish::parse(r#"<intu.bid lang="en">3000</INTU.BID>"#)?;
+/// assert_eq!(
+///     pass.transform(fragment)?.to_string(),
+///     r#"<BID xml:lang="en">3000</BID>"#,
+/// );
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct MapNames {
+    elements: Vec<NameRule>,
+    attributes: Vec<NameRule>,
+}
+
+#[derive(Clone)]
+enum NameRule {
+    Normalize(NameNormalization),
+    Rename(HashMap<String, String>),
+    Map(NameFn),
+}
+
+type NameFn = Rc<dyn Fn(&str) -> Option<String>>;
+
+impl MapNames {
+    /// Creates a pass that keeps all names unchanged.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Changes the case of element names.
+    pub fn normalize_elements(mut self, normalization: NameNormalization) -> Self {
+        self.elements.push(NameRule::Normalize(normalization));
+        self
+    }
+
+    /// Changes the case of attribute names.
+    pub fn normalize_attributes(mut self, normalization: NameNormalization) -> Self {
+        self.attributes.push(NameRule::Normalize(normalization));
+        self
+    }
+
+    /// Renames elements named `from` to `to`.
+    ///
+    /// Names are compared in a case-sensitive manner.
+    pub fn rename_element(mut self, from: impl Into<String>, to: impl Into<String>) -> Self {
+        NameRule::push_rename(&mut self.elements, from.into(), to.into());
+        self
+    }
+
+    /// Renames attributes named `from` to `to`.
+    ///
+    /// Names are compared in a case-sensitive manner.
+    pub fn rename_attribute(mut self, from: impl Into<String>, to: impl Into<String>) -> Self {
+        NameRule::push_rename(&mut self.attributes, from.into(), to.into());
+        self
+    }
+
+    /// Maps element names with a closure.
+    ///
+    /// The closure returns the new name, or `None` to keep the name unchanged.
+    pub fn map_elements<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&str) -> Option<String> + 'static,
+    {
+        self.elements.push(NameRule::Map(Rc::new(f)));
+        self
+    }
+
+    /// Maps attribute names with a closure.
+    ///
+    /// The closure returns the new name, or `None` to keep the name unchanged.
+    pub fn map_attributes<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&str) -> Option<String> + 'static,
+    {
+        self.attributes.push(NameRule::Map(Rc::new(f)));
+        self
+    }
+}
+
+impl NameRule {
+    /// Adds a renaming, merging it with the previous rule if that is also a renaming.
+    fn push_rename(rules: &mut Vec<NameRule>, from: String, to: String) {
+        match rules.last_mut() {
+            Some(NameRule::Rename(names)) => {
+                names.insert(from, to);
+            }
+            _ => rules.push(NameRule::Rename(HashMap::from([(from, to)]))),
+        }
+    }
+
+    fn apply_all<'a>(rules: &[NameRule], name: Cow<'a, str>) -> Cow<'a, str> {
+        rules.iter().fold(name, |name, rule| match rule {
+            NameRule::Normalize(normalization) => normalization.normalize(name),
+            NameRule::Rename(names) => match names.get(name.as_ref()) {
+                Some(new_name) => new_name.clone().into(),
+                None => name,
+            },
+            NameRule::Map(f) => match f(&name) {
+                Some(new_name) => new_name.into(),
+                None => name,
+            },
+        })
+    }
+}
+
+impl FragmentTransform for MapNames {
+    fn transform<'a>(&self, fragment: SgmlFragment<'a>) -> crate::Result<SgmlFragment<'a>> {
+        Ok(map_events(fragment, |event| match event {
+            SgmlEvent::OpenStartTag { name } => SgmlEvent::OpenStartTag {
+                name: NameRule::apply_all(&self.elements, name),
+            },
+            SgmlEvent::EndTag { name } => SgmlEvent::EndTag {
+                name: NameRule::apply_all(&self.elements, name),
+            },
+            SgmlEvent::Attribute { name, value } => SgmlEvent::Attribute {
+                name: NameRule::apply_all(&self.attributes, name),
+                value,
+            },
+            event => event,
+        }))
+    }
+}
+
+impl fmt::Debug for MapNames {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MapNames")
+            .field("elements", &self.elements)
+            .field("attributes", &self.attributes)
+            .finish()
+    }
+}
+
+impl fmt::Debug for NameRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NameRule::Normalize(normalization) => {
+                f.debug_tuple("Normalize").field(normalization).finish()
+            }
+            NameRule::Rename(names) => f.debug_tuple("Rename").field(names).finish(),
+            NameRule::Map(_) => f.debug_tuple("Map").finish(),
+        }
+    }
+}
+
+/// Expands entity references in text and attribute values.
+///
+/// The parser already expands entities while parsing (see
+/// [`ParserBuilder::expand_entities`](crate::parser::ParserBuilder::expand_entities));
+/// this pass is meant for fragments whose text was kept unexpanded,
+/// such as those built programmatically.
+/// For details on the closure, see [`entities::expand_entities`].
+pub struct ExpandEntities<F> {
+    f: F,
+}
+
+impl<F, T> ExpandEntities<F>
+where
+    F: Fn(&str) -> Option<T>,
+    T: AsRef<str>,
+{
+    /// Creates a pass that resolves entities with the given closure.
+    pub fn new(f: F) -> Self {
+        ExpandEntities { f }
+    }
+
+    fn expand<'a>(&self, text: Cow<'a, str>) -> crate::Result<Cow<'a, str>> {
+        Ok(match text {
+            Cow::Borrowed(text) => entities::expand_entities(text, &self.f)?,
+            Cow::Owned(text) => entities::expand_entities(&text, &self.f)?
+                .into_owned()
+                .into(),
+        })
+    }
+}
+
+impl<F, T> FragmentTransform for ExpandEntities<F>
+where
+    F: Fn(&str) -> Option<T>,
+    T: AsRef<str>,
+{
+    fn transform<'a>(&self, fragment: SgmlFragment<'a>) -> crate::Result<SgmlFragment<'a>> {
+        let events = fragment
+            .into_iter()
+            .map(|event| {
+                Ok(match event {
+                    SgmlEvent::Character(text) => SgmlEvent::Character(self.expand(text)?),
+                    SgmlEvent::Attribute {
+                        name,
+                        value: Some(value),
+                    } => SgmlEvent::Attribute {
+                        name,
+                        value: Some(self.expand(value)?),
+                    },
+                    event => event,
+                })
+            })
+            .collect::<crate::Result<Vec<_>>>()?;
+        Ok(events.into())
+    }
+}
+
+impl<F> fmt::Debug for ExpandEntities<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ExpandEntities").finish()
+    }
+}
+
+/// Drops all markup declarations (`<!DOCTYPE ...>`).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DropMarkupDeclarations;
+
+impl FragmentTransform for DropMarkupDeclarations {
+    fn transform<'a>(&self, fragment: SgmlFragment<'a>) -> crate::Result<SgmlFragment<'a>> {
+        Ok(retain_events(fragment, |event| {
+            !matches!(event, SgmlEvent::MarkupDeclaration { .. })
+        }))
+    }
+}
+
+/// Drops all processing instructions (`<?example>`).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DropProcessingInstructions;
+
+impl FragmentTransform for DropProcessingInstructions {
+    fn transform<'a>(&self, fragment: SgmlFragment<'a>) -> crate::Result<SgmlFragment<'a>> {
+        Ok(retain_events(fragment, |event| {
+            !matches!(event, SgmlEvent::ProcessingInstruction(_))
+        }))
+    }
+}
+
+fn map_events<'a>(
+    fragment: SgmlFragment<'a>,
+    f: impl FnMut(SgmlEvent<'a>) -> SgmlEvent<'a>,
+) -> SgmlFragment<'a> {
+    fragment.into_iter().map(f).collect::<Vec<_>>().into()
+}
+
+fn retain_events<'a>(
+    fragment: SgmlFragment<'a>,
+    f: impl FnMut(&SgmlEvent<'a>) -> bool,
+) -> SgmlFragment<'a> {
+    let mut events = fragment.into_vec();
+    events.retain(f);
+    events.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transforms::WhitespaceMode;
+
+    #[test]
+    fn test_pipeline_runs_passes_in_order() {
+        let fragment = crate::parse("<a><b>x</a>").unwrap();
+
+        let pipeline = Pipeline::new()
+            .then(RenameElements::new().rename("b", "c"))
+            .then(RenameElements::new().rename("c", "d"))
+            .then(NormalizeEndTags);
+        assert_eq!(pipeline.len(), 3);
+        assert_eq!(
+            pipeline.transform(fragment.clone()).unwrap().to_string(),
+            "<a><d>x</d></a>"
+        );
+
+        assert!(Pipeline::new().is_empty());
+        assert_eq!(
+            Pipeline::new().transform(fragment.clone()).unwrap(),
+            fragment
+        );
+    }
+
+    #[test]
+    fn test_pipeline_stops_on_error() {
+        let fragment = crate::parse("<a>x</b>").unwrap();
+        fn unreachable_pass(_: SgmlFragment) -> crate::Result<SgmlFragment> {
+            unreachable!()
+        }
+        let pipeline = Pipeline::new()
+            .then(NormalizeEndTags)
+            .then(unreachable_pass);
+        assert!(matches!(
+            pipeline.transform(fragment),
+            Err(crate::Error::NormalizationError(_))
+        ));
+    }
+
+    #[test]
+    fn test_function_pass() {
+        fn reverse(fragment: SgmlFragment) -> crate::Result<SgmlFragment> {
+            let mut events = fragment.into_vec();
+            events.reverse();
+            Ok(events.into())
+        }
+        let fragment = crate::parse("<a>x</a>").unwrap();
+        let pipeline = Pipeline::new().then(reverse);
+        assert_eq!(
+            pipeline.transform(fragment).unwrap().to_string(),
+            "</a>x><a"
+        );
+    }
+
+    #[test]
+    fn test_normalize_names() {
+        let fragment = crate::parse("<a Href=x>y</A>").unwrap();
+        let result = NormalizeNames(NameNormalization::ToUppercase)
+            .transform(fragment)
+            .unwrap();
+        assert_eq!(result.to_string(), r#"<A HREF="x">y</A>"#);
+    }
+
+    #[test]
+    fn test_map_names_separately() {
+        let fragment = crate::parse("<a Href=x>y</A>").unwrap();
+        let result = MapNames::new()
+            .normalize_elements(NameNormalization::ToLowercase)
+            .transform(fragment.clone())
+            .unwrap();
+        assert_eq!(result.to_string(), r#"<a Href="x">y</a>"#);
+
+        let result = MapNames::new()
+            .normalize_attributes(NameNormalization::ToLowercase)
+            .transform(fragment)
+            .unwrap();
+        assert_eq!(result.to_string(), r#"<a href="x">y</A>"#);
+    }
+
+    #[test]
+    fn test_map_names_rules_in_order() {
+        let fragment = crate::parse("<intu.bid>1</intu.bid><intu.userid>2</intu.userid>").unwrap();
+        let result = MapNames::new()
+            .rename_element("intu.bid", "bid")
+            .normalize_elements(NameNormalization::ToUppercase)
+            .map_elements(|name| name.strip_prefix("INTU.").map(|name| format!("X-{}", name)))
+            .rename_element("BID", "BANKID")
+            .transform(fragment)
+            .unwrap();
+        assert_eq!(
+            result.to_string(),
+            "<BANKID>1</BANKID><X-USERID>2</X-USERID>"
+        );
+    }
+
+    #[test]
+    fn test_map_names_keeps_entities() {
+        let fragment = SgmlFragment::from(vec![
+            SgmlEvent::OpenStartTag { name: "p".into() },
+            SgmlEvent::Attribute {
+                name: "title".into(),
+                value: Some("&amp;".into()),
+            },
+            SgmlEvent::CloseStartTag,
+            SgmlEvent::Character("&nbsp;".into()),
+        ]);
+        let result = MapNames::new()
+            .normalize_elements(NameNormalization::ToUppercase)
+            .normalize_attributes(NameNormalization::ToUppercase)
+            .transform(fragment)
+            .unwrap();
+        assert_eq!(
+            result.into_vec(),
+            vec![
+                SgmlEvent::OpenStartTag { name: "P".into() },
+                SgmlEvent::Attribute {
+                    name: "TITLE".into(),
+                    value: Some("&amp;".into()),
+                },
+                SgmlEvent::CloseStartTag,
+                SgmlEvent::Character("&nbsp;".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_expand_entities() {
+        let fragment = SgmlFragment::from(vec![
+            SgmlEvent::OpenStartTag { name: "A".into() },
+            SgmlEvent::Attribute {
+                name: "TITLE".into(),
+                value: Some("&x;".into()),
+            },
+            SgmlEvent::CloseStartTag,
+            SgmlEvent::Character(String::from("&x;&#33;").into()),
+            SgmlEvent::EndTag { name: "A".into() },
+        ]);
+        let pass = ExpandEntities::new(|entity| (entity == "x").then(|| "y"));
+        let result = pass.transform(fragment.clone()).unwrap();
+        assert_eq!(result.to_string(), r#"<A TITLE="y">y!</A>"#);
+
+        let pass = ExpandEntities::new(|_| None::<&str>);
+        assert!(matches!(
+            pass.transform(fragment),
+            Err(crate::Error::EntityError(_))
+        ));
+    }
+
+    #[test]
+    fn test_drop_events() {
+        let fragment = crate::parse("<!DOCTYPE a><?pi><a><?pi>x</a>").unwrap();
+        let result = DropProcessingInstructions.transform(fragment).unwrap();
+        assert_eq!(result.to_string(), "<!DOCTYPE a><a>x</a>");
+        let result = DropMarkupDeclarations.transform(result).unwrap();
+        assert_eq!(result.to_string(), "<a>x</a>");
+    }
+
+    #[test]
+    fn test_whitespace_policy_pass() {
+        let fragment = crate::Parser::builder()
+            .trim_whitespace(false)
+            .parse("<a> x <b> y </b></a>")
+            .unwrap();
+        let policy =
+            WhitespacePolicy::new(WhitespaceMode::Trim).element("b", WhitespaceMode::Preserve);
+        let result = Pipeline::new().then(policy).transform(fragment).unwrap();
+        assert_eq!(result.to_string(), "<a>x<b> y </b></a>");
+    }
+}
@@ -1,10 +1,51 @@
+use std::borrow::Cow;
+use std::ops::Range;
+
 use crate::{SgmlEvent, SgmlFragment};
 
-/// A convenience helper to insert and remove events from a [`SgmlFragment`].
+/// A convenience helper to insert, remove and rearrange events in a [`SgmlFragment`].
+///
+/// Operations are recorded first, and only performed when the transform is applied;
+/// all indices refer to positions in the original list.
 #[derive(Clone, Debug, Default)]
 pub struct Transform<'a> {
     insertions: Vec<(usize, SgmlEvent<'a>)>,
     deletions: Vec<usize>,
+    replacements: Vec<(Range<usize>, Vec<SgmlEvent<'a>>)>,
+    wraps: Vec<(Range<usize>, Cow<'a, str>)>,
+    unwraps: Vec<usize>,
+    moves: Vec<(Range<usize>, usize)>,
+}
+
+/// The error type when a [`Transform`] cannot be applied.
+///
+/// This is returned by [`Transform::try_apply`].
+#[derive(Clone, Debug, Eq, PartialEq, thiserror::Error)]
+pub enum TransformError {
+    /// An operation refers to a position past the end of the fragment.
+    #[error("index {index} is out of bounds for a fragment of {len} events")]
+    OutOfBounds {
+        /// The offending position.
+        index: usize,
+        /// The number of events in the fragment.
+        len: usize,
+    },
+    /// A range ends before it starts.
+    #[error("range {0:?} ends before it starts")]
+    InvalidRange(Range<usize>),
+    /// Two operations touch overlapping events, or place events inside a range
+    /// taken by another operation.
+    #[error("conflicting operations on events {0:?} and {1:?}")]
+    Conflict(Range<usize>, Range<usize>),
+    /// A wrapped, moved or replaced range would leave start and end tags unpaired.
+    #[error("events {0:?} would leave elements unbalanced")]
+    Unbalanced(Range<usize>),
+    /// The event to unwrap is not an [`OpenStartTag`](SgmlEvent::OpenStartTag).
+    #[error("event {0} is not the start of an element")]
+    NotAnElement(usize),
+    /// No end tag was found for the element to unwrap.
+    #[error("element starting at event {0} is not closed")]
+    UnclosedElement(usize),
 }
 
 impl<'a> Transform<'a> {
@@ -15,7 +56,12 @@ impl<'a> Transform<'a> {
 
     /// Returns `true` if no operations were recorded.
     pub fn is_empty(&self) -> bool {
-        self.insertions.is_empty() && self.deletions.is_empty()
+        self.insertions.is_empty()
+            && self.deletions.is_empty()
+            && self.replacements.is_empty()
+            && self.wraps.is_empty()
+            && self.unwraps.is_empty()
+            && self.moves.is_empty()
     }
 
     /// Removes the event at the given position.
@@ -104,43 +150,405 @@ impl<'a> Transform<'a> {
         self.insertions.push((index, event));
     }
 
+    /// Replaces the event at the given position.
+    ///
+    /// The replacement must open and close as many elements as the original event.
+    pub fn replace_at(&mut self, index: usize, event: SgmlEvent<'a>) {
+        self.replace_range(index..index + 1, vec![event]);
+    }
+
+    /// Replaces the events in the given range with other events.
+    ///
+    /// The replacement must open and close as many elements as the original events.
+    /// An empty range inserts the events at that position.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use sgmlish::SgmlEvent;
+    /// # use sgmlish::transforms::Transform;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let fragment = sgmlish::parse("<A><B>old</B><C>kept</C></A>")?;
+    ///
+    /// let mut transform = Transform::new();
+    /// // Replace all of <B>old</B>
+    /// transform.replace_range(2..6, vec![SgmlEvent::Character("new".into())]);
+    /// let result = transform.try_apply(fragment)?;
+    ///
+    /// assert_eq!(result.to_string(), "<A>new<C>kept</C></A>");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn replace_range(&mut self, range: Range<usize>, events: Vec<SgmlEvent<'a>>) {
+        self.replacements.push((range, events));
+    }
+
+    /// Surrounds the events in the given range with start and end tags for a new element.
+    ///
+    /// The range must contain whole elements only.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use sgmlish::transforms::Transform;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let fragment = sgmlish::parse("<A><B>1</B><B>2</B></A>")?;
+    ///
+    /// let mut transform = Transform::new();
+    /// transform.wrap_range(2..10, "LIST");
+    /// let result = transform.try_apply(fragment)?;
+    ///
+    /// assert_eq!(result.to_string(), "<A><LIST><B>1</B><B>2</B></LIST></A>");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn wrap_range(&mut self, range: Range<usize>, name: impl Into<Cow<'a, str>>) {
+        self.wraps.push((range, name.into()));
+    }
+
+    /// Removes the start and end tags of the element starting at the given position,
+    /// keeping its content.
+    ///
+    /// The end tag is found when the transform is applied, so that the fragment
+    /// must have its end tags normalized (see [`normalize_end_tags`]).
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use sgmlish::transforms::Transform;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let fragment = sgmlish::parse("<A><FONT SIZE=2>small<B>bold</B></FONT></A>")?;
+    ///
+    /// let mut transform = Transform::new();
+    /// transform.unwrap_element(2);
+    /// let result = transform.try_apply(fragment)?;
+    ///
+    /// assert_eq!(result.to_string(), "<A>small<B>bold</B></A>");
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`normalize_end_tags`]: crate::transforms::normalize_end_tags
+    pub fn unwrap_element(&mut self, index: usize) {
+        self.unwraps.push(index);
+    }
+
+    /// Moves the events in the given range to another position.
+    ///
+    /// The range must contain whole elements only, and `to` cannot be inside it.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use sgmlish::transforms::Transform;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let fragment = sgmlish::parse("<A><B>1</B><C>2</C></A>")?;
+    ///
+    /// let mut transform = Transform::new();
+    /// // Move <C>2</C> before <B>
+    /// transform.move_range(6..10, 2);
+    /// let result = transform.try_apply(fragment)?;
+    ///
+    /// assert_eq!(result.to_string(), "<A><C>2</C><B>1</B></A>");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn move_range(&mut self, range: Range<usize>, to: usize) {
+        self.moves.push((range, to));
+    }
+
     /// Applies the recorded changes to the given fragment.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the changes cannot be applied; see [`try_apply`](Transform::try_apply).
+    /// Transforms with only insertions and removals never fail.
     pub fn apply(self, fragment: SgmlFragment<'a>) -> SgmlFragment<'a> {
+        match self.try_apply(fragment) {
+            Ok(fragment) => fragment,
+            Err(err) => panic!("could not apply transform: {}", err),
+        }
+    }
+
+    /// Applies the recorded changes to the given fragment.
+    ///
+    /// Fails if any of the recorded operations refers to an index beyond the end
+    /// of the fragment, if operations overlap in ways that cannot be reconciled
+    /// (e.g. moving a range that is partially replaced), or if the result would
+    /// have unbalanced elements.
+    pub fn try_apply(self, fragment: SgmlFragment<'a>) -> Result<SgmlFragment<'a>, TransformError> {
         if self.is_empty() {
-            return fragment;
+            return Ok(fragment);
+        }
+
+        let len = fragment.len();
+        let mut removed = vec![false; len];
+        for &index in &self.deletions {
+            if let Some(removed) = removed.get_mut(index) {
+                *removed = true;
+            }
         }
+        self.validate(fragment.as_slice())?;
+
+        let mut events = fragment
+            .into_vec()
+            .into_iter()
+            .map(Some)
+            .collect::<Vec<_>>();
 
-        let mut deletions = self.deletions;
-        deletions.sort_unstable();
-        deletions.dedup();
-        let mut deletions = deletions.into_iter().peekable();
+        // Events to be placed before each position, by kind
+        let mut wrap_ends = vec![vec![]; len + 1];
+        let mut wrap_starts = vec![vec![]; len + 1];
+        let mut placed = (0..=len).map(|_| vec![]).collect::<Vec<_>>();
+
+        for (range, name) in self.wraps {
+            wrap_ends[range.end].push((range.start, name.clone()));
+            wrap_starts[range.start].push((range.end, name));
+        }
+        for index in self.unwraps {
+            let (start_tag, end_tag) = find_element(&events, index)?;
+            for i in start_tag.chain(end_tag) {
+                removed[i] = true;
+            }
+        }
+        for (range, to) in self.moves {
+            let moved = events[range.clone()]
+                .iter_mut()
+                .map(|event| event.take().unwrap());
+            placed[to].extend(moved);
+            for removed in &mut removed[range] {
+                *removed = true;
+            }
+        }
+        for (range, replacement) in self.replacements {
+            placed[range.start].extend(replacement);
+            for removed in &mut removed[range] {
+                *removed = true;
+            }
+        }
 
         let mut insertions = self.insertions;
         insertions.sort_by_key(|(pos, _)| *pos);
         let mut insertions = insertions.into_iter().peekable();
 
-        let final_size = fragment.len().saturating_sub(deletions.len()) + insertions.len();
-        let mut result = Vec::with_capacity(final_size);
-
-        for (i, event) in fragment.into_iter().enumerate() {
+        let mut result = Vec::with_capacity(len);
+        for (i, event) in events.into_iter().chain(std::iter::once(None)).enumerate() {
+            // Close inner elements first
+            wrap_ends[i].sort_by(|(a, _), (b, _)| b.cmp(a));
+            for (_, name) in wrap_ends[i].drain(..) {
+                result.push(SgmlEvent::EndTag { name });
+            }
             while let Some((_, event_to_insert)) =
                 insertions.next_if(|(index_to_insert, _)| *index_to_insert == i)
             {
                 result.push(event_to_insert);
             }
+            result.append(&mut placed[i]);
+            // Open outer elements first
+            wrap_starts[i].sort_by(|(a, _), (b, _)| b.cmp(a));
+            for (_, name) in wrap_starts[i].drain(..) {
+                result.push(SgmlEvent::OpenStartTag { name });
+                result.push(SgmlEvent::CloseStartTag);
+            }
 
-            if deletions.next_if_eq(&i).is_none() {
-                result.push(event);
+            match event {
+                Some(event) if !removed[i] => result.push(event),
+                _ => {}
             }
         }
 
         // Insert remaining events at the end
         result.extend(insertions.map(|(_, event)| event));
 
-        result.into()
+        Ok(result.into())
+    }
+
+    /// Checks that all operations are within bounds, and that none of them conflict.
+    fn validate(&self, events: &[SgmlEvent]) -> Result<(), TransformError> {
+        let len = events.len();
+        let check_index = |index: usize| {
+            if index > len {
+                Err(TransformError::OutOfBounds { index, len })
+            } else {
+                Ok(())
+            }
+        };
+        let check_range = |range: &Range<usize>| {
+            check_index(range.start)?;
+            check_index(range.end)?;
+            if range.start > range.end {
+                return Err(TransformError::InvalidRange(range.clone()));
+            }
+            Ok(())
+        };
+
+        // Ranges whose events are taken out of their position
+        let mut taken = vec![];
+        // Positions where new events are placed
+        let mut points = vec![];
+
+        for (range, replacement) in &self.replacements {
+            check_range(range)?;
+            if depth_change(&events[range.clone()]) != depth_change(replacement) {
+                return Err(TransformError::Unbalanced(range.clone()));
+            }
+            taken.push(range.clone());
+            points.push(range.start);
+        }
+        for (range, to) in &self.moves {
+            check_range(range)?;
+            check_index(*to)?;
+            if !is_balanced(events, range) {
+                return Err(TransformError::Unbalanced(range.clone()));
+            }
+            if range.start < *to && *to < range.end {
+                return Err(TransformError::Conflict(range.clone(), *to..*to));
+            }
+            taken.push(range.clone());
+            points.push(*to);
+        }
+        for &index in &self.unwraps {
+            check_index(index + 1)?;
+            let (start_tag, end_tag) = find_element_in(events, index)?;
+            taken.push(start_tag);
+            taken.extend(end_tag);
+        }
+        let first_deletion = taken.len();
+        for &index in &self.deletions {
+            if index < len {
+                taken.push(index..index + 1);
+            }
+        }
+        for (range, _) in &self.wraps {
+            check_range(range)?;
+            if !is_balanced(events, range) {
+                return Err(TransformError::Unbalanced(range.clone()));
+            }
+        }
+
+        // Plain removals may repeat, so they only conflict with other kinds of operations
+        for (i, a) in taken.iter().enumerate().filter(|(_, r)| !r.is_empty()) {
+            for b in taken[i + 1..].iter().filter(|r| !r.is_empty()) {
+                let both_deletions = i >= first_deletion;
+                if !both_deletions && a.start < b.end && b.start < a.end {
+                    return Err(TransformError::Conflict(a.clone(), b.clone()));
+                }
+            }
+            if let Some(&point) = points.iter().find(|&&p| a.start < p && p < a.end) {
+                return Err(TransformError::Conflict(a.clone(), point..point));
+            }
+            // Wrapped ranges must either contain taken ranges entirely, or be apart from them
+            for (range, _) in &self.wraps {
+                let overlaps = range.start < a.end && a.start < range.end;
+                let contains = range.start <= a.start && a.end <= range.end;
+                if overlaps && !contains {
+                    return Err(TransformError::Conflict(range.clone(), a.clone()));
+                }
+            }
+        }
+        for (i, (a, _)) in self.wraps.iter().enumerate() {
+            for (b, _) in &self.wraps[i + 1..] {
+                let overlaps = a.start < b.end && b.start < a.end;
+                let nested = (a.start <= b.start && b.end <= a.end)
+                    || (b.start <= a.start && a.end <= b.end);
+                if overlaps && !nested {
+                    return Err(TransformError::Conflict(a.clone(), b.clone()));
+                }
+            }
+        }
+
+        Ok(())
     }
 }
 
+/// Returns the net number of elements opened by the events.
+fn depth_change(events: &[SgmlEvent]) -> isize {
+    events
+        .iter()
+        .map(|event| match event {
+            SgmlEvent::OpenStartTag { .. } => 1,
+            SgmlEvent::EndTag { .. } | SgmlEvent::XmlCloseEmptyElement => -1,
+            _ => 0,
+        })
+        .sum()
+}
+
+/// Checks that the range only contains whole elements.
+fn is_balanced(events: &[SgmlEvent], range: &Range<usize>) -> bool {
+    let inside_tag = |index: usize| {
+        matches!(
+            events.get(index),
+            Some(
+                SgmlEvent::Attribute { .. }
+                    | SgmlEvent::CloseStartTag
+                    | SgmlEvent::XmlCloseEmptyElement
+            )
+        )
+    };
+    if inside_tag(range.start) || inside_tag(range.end) {
+        return false;
+    }
+    let mut depth = 0;
+    for event in &events[range.clone()] {
+        depth += depth_change(std::slice::from_ref(event));
+        if depth < 0 {
+            return false;
+        }
+    }
+    depth == 0
+}
+
+/// Finds the start tag and, if any, the end tag of the element starting at `index`.
+fn find_element(
+    events: &[Option<SgmlEvent>],
+    index: usize,
+) -> Result<(Range<usize>, Option<usize>), TransformError> {
+    let events = events
+        .iter()
+        .map(|event| event.as_ref().expect("unwrapped elements cannot be moved"))
+        .collect::<Vec<_>>();
+    find_element_by(&events, index)
+}
+
+fn find_element_in(
+    events: &[SgmlEvent],
+    index: usize,
+) -> Result<(Range<usize>, Option<Range<usize>>), TransformError> {
+    let events = events.iter().collect::<Vec<_>>();
+    let (start_tag, end_tag) = find_element_by(&events, index)?;
+    Ok((start_tag, end_tag.map(|i| i..i + 1)))
+}
+
+fn find_element_by(
+    events: &[&SgmlEvent],
+    index: usize,
+) -> Result<(Range<usize>, Option<usize>), TransformError> {
+    if !matches!(events.get(index), Some(SgmlEvent::OpenStartTag { .. })) {
+        return Err(TransformError::NotAnElement(index));
+    }
+    let mut depth = 0;
+    let mut start_tag_end = None;
+    for (i, event) in events.iter().enumerate().skip(index) {
+        match event {
+            SgmlEvent::OpenStartTag { .. } => depth += 1,
+            SgmlEvent::CloseStartTag if start_tag_end.is_none() => start_tag_end = Some(i + 1),
+            SgmlEvent::XmlCloseEmptyElement if depth == 1 => {
+                return Ok((index..i + 1, None));
+            }
+            SgmlEvent::XmlCloseEmptyElement => depth -= 1,
+            SgmlEvent::EndTag { .. } => {
+                depth -= 1;
+                if depth == 0 {
+                    let start_tag_end = start_tag_end.ok_or(TransformError::NotAnElement(index))?;
+                    return Ok((index..start_tag_end, Some(i)));
+                }
+            }
+            _ => {}
+        }
+    }
+    Err(TransformError::UnclosedElement(index))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -272,4 +680,169 @@ mod tests {
             ])
         );
     }
+
+    fn parse(input: &str) -> SgmlFragment<'_> {
+        crate::parse(input).unwrap()
+    }
+
+    #[test]
+    fn test_replace_at() {
+        let mut transform = Transform::new();
+        transform.replace_at(5, SgmlEvent::Character("two".into()));
+        let result = transform.try_apply(parse("<A>1<B>2</B></A>")).unwrap();
+        assert_eq!(result.to_string(), "<A>1<B>two</B></A>");
+    }
+
+    #[test]
+    fn test_replace_range_must_stay_balanced() {
+        let mut transform = Transform::new();
+        // Drops <B> but not </B>
+        transform.replace_range(1..3, vec![]);
+        assert_eq!(
+            transform.try_apply(parse("<A><B>2</B></A>")),
+            Err(TransformError::Unbalanced(1..3))
+        );
+    }
+
+    #[test]
+    fn test_wrap_nested_ranges() {
+        let mut transform = Transform::new();
+        transform.wrap_range(2..3, "I");
+        transform.wrap_range(0..4, "P");
+        let result = transform.try_apply(parse("<B>x</B>")).unwrap();
+        assert_eq!(result.to_string(), "<P><B><I>x</I></B></P>");
+    }
+
+    #[test]
+    fn test_wrap_empty_element() {
+        let mut transform = Transform::new();
+        transform.wrap_range(0..2, "P");
+        let result = transform.try_apply(parse("<BR/>")).unwrap();
+        assert_eq!(result.to_string(), "<P><BR/></P>");
+    }
+
+    #[test]
+    fn test_wrap_unbalanced_range() {
+        let mut transform = Transform::new();
+        transform.wrap_range(0..3, "P");
+        assert_eq!(
+            transform.try_apply(parse("<B>x</B>")),
+            Err(TransformError::Unbalanced(0..3))
+        );
+
+        // Splitting a start tag
+        let mut transform = Transform::new();
+        transform.wrap_range(1..5, "P");
+        assert_eq!(
+            transform.try_apply(parse("<B X=1>x</B>")),
+            Err(TransformError::Unbalanced(1..5))
+        );
+    }
+
+    #[test]
+    fn test_wrap_overlapping_ranges() {
+        let mut transform = Transform::new();
+        transform.wrap_range(0..2, "P");
+        transform.wrap_range(1..3, "Q");
+        assert_eq!(
+            transform.try_apply(SgmlFragment::from(vec![
+                SgmlEvent::Character("a".into()),
+                SgmlEvent::Character("b".into()),
+                SgmlEvent::Character("c".into()),
+            ])),
+            Err(TransformError::Conflict(0..2, 1..3))
+        );
+    }
+
+    #[test]
+    fn test_unwrap_element() {
+        let mut transform = Transform::new();
+        transform.unwrap_element(5);
+        let result = transform
+            .try_apply(parse("<A><B>x<B>y</B></B></A>"))
+            .unwrap();
+        assert_eq!(result.to_string(), "<A><B>xy</B></A>");
+    }
+
+    #[test]
+    fn test_unwrap_errors() {
+        let mut transform = Transform::new();
+        transform.unwrap_element(1);
+        assert_eq!(
+            transform.try_apply(parse("<A>x</A>")),
+            Err(TransformError::NotAnElement(1))
+        );
+
+        let mut transform = Transform::new();
+        transform.unwrap_element(0);
+        assert_eq!(
+            transform.try_apply(parse("<A>x")),
+            Err(TransformError::UnclosedElement(0))
+        );
+    }
+
+    #[test]
+    fn test_move_range_forward() {
+        let mut transform = Transform::new();
+        transform.move_range(2..6, 10);
+        let result = transform
+            .try_apply(parse("<A><B>1</B><C>2</C></A>"))
+            .unwrap();
+        assert_eq!(result.to_string(), "<A><C>2</C><B>1</B></A>");
+    }
+
+    #[test]
+    fn test_move_range_into_itself() {
+        let mut transform = Transform::new();
+        transform.move_range(2..6, 4);
+        assert_eq!(
+            transform.try_apply(parse("<A><B>1</B></A>")),
+            Err(TransformError::Conflict(2..6, 4..4))
+        );
+    }
+
+    #[test]
+    fn test_move_and_replace_conflict() {
+        let mut transform = Transform::new();
+        transform.move_range(2..6, 10);
+        transform.replace_at(4, SgmlEvent::Character("x".into()));
+        assert_eq!(
+            transform.try_apply(parse("<A><B>1</B><C>2</C></A>")),
+            Err(TransformError::Conflict(4..5, 2..6))
+        );
+
+        let mut transform = Transform::new();
+        transform.move_range(2..6, 10);
+        transform.remove_at(4);
+        assert_eq!(
+            transform.try_apply(parse("<A><B>1</B><C>2</C></A>")),
+            Err(TransformError::Conflict(2..6, 4..5))
+        );
+    }
+
+    #[test]
+    #[allow(clippy::reversed_empty_ranges)]
+    fn test_out_of_bounds() {
+        let mut transform = Transform::new();
+        transform.wrap_range(0..5, "P");
+        assert_eq!(
+            transform.try_apply(parse("<A>x</A>")),
+            Err(TransformError::OutOfBounds { index: 5, len: 4 })
+        );
+
+        let mut transform = Transform::new();
+        transform.replace_range(2..1, vec![]);
+        assert_eq!(
+            transform.try_apply(parse("<A>x</A>")),
+            Err(TransformError::InvalidRange(2..1))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "could not apply transform")]
+    fn test_apply_panics_on_error() {
+        let mut transform = Transform::new();
+        transform.unwrap_element(1);
+        transform.apply(parse("<A>x</A>"));
+    }
 }
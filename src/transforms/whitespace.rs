@@ -0,0 +1,235 @@
+//! Per-element whitespace handling, for documents where some elements (like
+//! free-form memos) have significant whitespace and others only indentation.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use crate::{text, SgmlEvent, SgmlFragment};
+
+/// How whitespace in the text of an element should be treated by a [`WhitespacePolicy`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WhitespaceMode {
+    /// Keep text exactly as it is.
+    Preserve,
+    /// Trim leading and trailing whitespace, dropping text that is only whitespace.
+    Trim,
+    /// Replace each run of whitespace with a single space.
+    Collapse,
+    /// Drop text that is only whitespace, keeping any other text as it is.
+    DropBlank,
+}
+
+/// Applies whitespace handling rules to [`Character`](SgmlEvent::Character) events,
+/// according to the element that contains them.
+///
+/// Each element name can have its own [`WhitespaceMode`]; elements without one
+/// inherit the mode of their parent, and top-level text uses the default mode.
+///
+/// For this to be useful, the fragment should be parsed with whitespace kept
+/// (see [`ParserBuilder::trim_whitespace`]), and have its end tags
+/// normalized beforehand (see [`normalize_end_tags`]).
+/// Element names are compared in a case-sensitive manner.
+///
+/// When deserializing, this is how fields can have their whitespace preserved:
+/// configure the element names of those fields with [`WhitespaceMode::Preserve`],
+/// and either apply the policy to the fragment before calling [`from_fragment`],
+/// or pass it to the deserializer through [`DeserializerOptions::whitespace_policy`].
+///
+/// # Example
+///
+/// ```rust
+/// # use sgmlish::transforms::{normalize_end_tags, WhitespaceMode, WhitespacePolicy};
+/// # fn main() -> sgmlish::Result<()> {
+/// let sgml = sgmlish::Parser::builder().trim_whitespace(false).parse(r##"
+///     <STMTTRN>
+///         <TRNTYPE>  DEBIT
+///         <MEMO>  Dinner   for two
+///     </STMTTRN>
+/// "##)?;
+/// let sgml = normalize_end_tags(sgml)?;
+///
+/// let policy = WhitespacePolicy::new(WhitespaceMode::Trim)
+///     .element("MEMO", WhitespaceMode::Preserve);
+/// let expected = sgmlish::Parser::builder().trim_whitespace(false).parse(
+///     "<STMTTRN><TRNTYPE>DEBIT</TRNTYPE><MEMO>  Dinner   for two\n    </MEMO></STMTTRN>",
+/// )?;
+/// assert_eq!(policy.apply(sgml), expected);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`ParserBuilder::trim_whitespace`]: crate::parser::ParserBuilder::trim_whitespace
+/// [`normalize_end_tags`]: crate::transforms::normalize_end_tags
+/// [`from_fragment`]: crate::from_fragment
+/// [`DeserializerOptions::whitespace_policy`]: crate::de::DeserializerOptions::whitespace_policy
+#[derive(Clone, Debug)]
+pub struct WhitespacePolicy {
+    default: WhitespaceMode,
+    elements: HashMap<String, WhitespaceMode>,
+}
+
+impl WhitespacePolicy {
+    /// Creates a policy that applies the given mode to all elements.
+    pub fn new(default: WhitespaceMode) -> Self {
+        WhitespacePolicy {
+            default,
+            elements: HashMap::new(),
+        }
+    }
+
+    /// Sets the mode for text in elements with the given name, and their descendants.
+    pub fn element(mut self, name: impl Into<String>, mode: WhitespaceMode) -> Self {
+        self.elements.insert(name.into(), mode);
+        self
+    }
+
+    /// Returns the mode explicitly configured for the given element name, if any.
+    pub fn mode_for(&self, name: &str) -> Option<WhitespaceMode> {
+        self.elements.get(name).copied()
+    }
+
+    /// Applies the policy to all text in the fragment.
+    pub fn apply<'a>(&self, fragment: SgmlFragment<'a>) -> SgmlFragment<'a> {
+        let mut stack = vec![];
+        let mut events = fragment.into_vec();
+
+        for event in &mut events {
+            match event {
+                SgmlEvent::OpenStartTag { name } => {
+                    let inherited = stack.last().copied().unwrap_or(self.default);
+                    stack.push(self.mode_for(name).unwrap_or(inherited));
+                }
+                SgmlEvent::EndTag { .. } | SgmlEvent::XmlCloseEmptyElement => {
+                    stack.pop();
+                }
+                SgmlEvent::Character(text) => {
+                    let mode = stack.last().copied().unwrap_or(self.default);
+                    apply_mode(mode, text);
+                }
+                _ => {}
+            }
+        }
+
+        events.retain(|event| !matches!(event, SgmlEvent::Character(text) if text.is_empty()));
+        SgmlFragment::from(events)
+    }
+}
+
+fn apply_mode(mode: WhitespaceMode, text: &mut Cow<str>) {
+    match mode {
+        WhitespaceMode::Preserve => {}
+        WhitespaceMode::Trim => match text {
+            Cow::Borrowed(s) => *s = s.trim_matches(text::is_sgml_whitespace),
+            Cow::Owned(s) => {
+                let trimmed = s.trim_matches(text::is_sgml_whitespace);
+                if trimmed.len() != s.len() {
+                    *s = trimmed.to_owned();
+                }
+            }
+        },
+        WhitespaceMode::Collapse => {
+            if let Some(collapsed) = collapse(text) {
+                *text = collapsed.into();
+            }
+        }
+        WhitespaceMode::DropBlank => {
+            if text::is_blank(text) {
+                *text = "".into();
+            }
+        }
+    }
+}
+
+/// Replaces each run of whitespace with a single space,
+/// or returns `None` if the text would not be changed.
+fn collapse(text: &str) -> Option<String> {
+    let mut prev_whitespace = false;
+    let unchanged = text.chars().all(|c| {
+        let ok = c == ' ' && !prev_whitespace || !text::is_sgml_whitespace(c);
+        prev_whitespace = text::is_sgml_whitespace(c);
+        ok
+    });
+    if unchanged {
+        return None;
+    }
+
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if !text::is_sgml_whitespace(c) {
+            out.push(c);
+        } else if !out.ends_with(' ') {
+            out.push(' ');
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> SgmlFragment<'_> {
+        crate::Parser::builder()
+            .trim_whitespace(false)
+            .parse(input)
+            .unwrap()
+    }
+
+    fn texts<'a>(fragment: &'a SgmlFragment) -> Vec<&'a str> {
+        fragment
+            .iter()
+            .filter_map(|event| match event {
+                SgmlEvent::Character(text) => Some(text.as_ref()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    const SGML: &str = "<A> a  <B> b\t\tb </B> <C>\n  <D> d </D>\n</C> </A>";
+
+    #[test]
+    fn test_default_mode() {
+        let policy = WhitespacePolicy::new(WhitespaceMode::Preserve);
+        assert_eq!(
+            texts(&policy.apply(parse(SGML))),
+            vec![" a  ", " b\t\tb ", " ", "\n  ", " d ", "\n", " "]
+        );
+
+        let policy = WhitespacePolicy::new(WhitespaceMode::Trim);
+        assert_eq!(texts(&policy.apply(parse(SGML))), vec!["a", "b\t\tb", "d"]);
+
+        let policy = WhitespacePolicy::new(WhitespaceMode::Collapse);
+        assert_eq!(
+            texts(&policy.apply(parse(SGML))),
+            vec![" a ", " b b ", " ", " ", " d ", " ", " "]
+        );
+
+        let policy = WhitespacePolicy::new(WhitespaceMode::DropBlank);
+        assert_eq!(
+            texts(&policy.apply(parse(SGML))),
+            vec![" a  ", " b\t\tb ", " d "]
+        );
+    }
+
+    #[test]
+    fn test_element_modes_are_inherited() {
+        let policy = WhitespacePolicy::new(WhitespaceMode::Trim)
+            .element("B", WhitespaceMode::Collapse)
+            .element("C", WhitespaceMode::Preserve);
+        assert_eq!(
+            texts(&policy.apply(parse(SGML))),
+            vec!["a", " b b ", "\n  ", " d ", "\n"]
+        );
+        assert_eq!(policy.mode_for("C"), Some(WhitespaceMode::Preserve));
+        assert_eq!(policy.mode_for("A"), None);
+    }
+
+    #[test]
+    fn test_collapse() {
+        assert_eq!(collapse("a b c"), None);
+        assert_eq!(collapse(" a "), None);
+        assert_eq!(collapse("a  b"), Some("a b".to_owned()));
+        assert_eq!(collapse("\na\tb\r\n"), Some(" a b ".to_owned()));
+        assert_eq!(collapse("\n\n"), Some(" ".to_owned()));
+    }
+}
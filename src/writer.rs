@@ -0,0 +1,516 @@
+//! Incremental generation of SGML documents.
+
+use std::io::{self, Write};
+
+use crate::marked_sections::{MarkedSectionContent, MarkedSectionStatus};
+use crate::parser::raw::{is_name_char, is_name_start_char};
+use crate::{text, SgmlEvent};
+
+/// The error type for problems when writing SGML.
+#[derive(Debug, thiserror::Error)]
+pub enum WriterError {
+    /// An error occurred in the underlying writer.
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    /// An end tag does not match the innermost open element.
+    #[error("mismatched end tag: expected </{expected}>, found </{found}>")]
+    MismatchedEndTag { expected: String, found: String },
+    /// An end tag was written with no open elements.
+    #[error("unpaired end tag: </{0}>")]
+    UnpairedEndTag(String),
+    /// An attribute was written outside of a start tag.
+    #[error("attribute '{0}' written outside of a start tag")]
+    AttributeOutsideStartTag(String),
+    /// An element or attribute name is not a valid SGML name.
+    #[error("invalid name: {0:?}")]
+    InvalidName(String),
+    /// A comment contains `--`, which would end it prematurely.
+    #[error("comment cannot contain '--': {0:?}")]
+    InvalidComment(String),
+    /// A marked section, or text written inside a `CDATA` marked section,
+    /// contains a `]]>` that would end it prematurely.
+    #[error("marked section cannot contain an unmatched ']]>': {0:?}")]
+    InvalidMarkedSection(String),
+    /// The writer was finished while there were still open elements.
+    #[error("element <{0}> was not closed")]
+    UnclosedElement(String),
+}
+
+/// Writes SGML markup to an [`io::Write`], one piece at a time.
+///
+/// The writer keeps track of open elements, so that end tags are checked
+/// against their start tags, and start tags are closed (`>`) automatically
+/// as soon as content is written.
+///
+/// # Example
+///
+/// ```rust
+/// # use sgmlish::SgmlWriter;
+/// # fn main() -> Result<(), sgmlish::writer::WriterError> {
+/// let mut writer = SgmlWriter::new(Vec::new());
+/// writer.start_element("A")?;
+/// writer.attribute("HREF", Some("/search?q=sonic&lang=en"))?;
+/// writer.text("Sonic & Knuckles")?;
+/// writer.end_element("A")?;
+///
+/// let output = writer.finish()?;
+/// assert_eq!(
+///     String::from_utf8(output).unwrap(),
+///     r#"<A HREF="/search?q=sonic&#38;lang=en">Sonic &#38; Knuckles</A>"#,
+/// );
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct SgmlWriter<W: Write> {
+    out: W,
+    stack: Vec<OpenElement>,
+    /// Whether each open marked section is a `CDATA` section, outermost first.
+    marked_sections: Vec<bool>,
+    in_start_tag: bool,
+    omit_end_tags: bool,
+}
+
+#[derive(Debug)]
+struct OpenElement {
+    name: String,
+    has_child_elements: bool,
+}
+
+impl<W: Write> SgmlWriter<W> {
+    /// Creates a new writer that outputs to the given destination.
+    pub fn new(out: W) -> Self {
+        SgmlWriter {
+            out,
+            stack: Vec::new(),
+            marked_sections: Vec::new(),
+            in_start_tag: false,
+            omit_end_tags: false,
+        }
+    }
+
+    /// Changes whether end tags of elements without child elements should be omitted,
+    /// in the style of [OFX] 1.x.
+    ///
+    /// Such output can be restored with [`normalize_end_tags`], as long as
+    /// text only appears in elements without child elements.
+    ///
+    /// [OFX]: https://en.wikipedia.org/wiki/Open_Financial_Exchange
+    /// [`normalize_end_tags`]: crate::transforms::normalize_end_tags
+    pub fn set_omit_end_tags(&mut self, omit_end_tags: bool) {
+        self.omit_end_tags = omit_end_tags;
+    }
+
+    /// Returns the names of all currently open elements, outermost first.
+    pub fn open_elements(&self) -> impl Iterator<Item = &str> {
+        self.stack.iter().map(|element| element.name.as_str())
+    }
+
+    /// Opens a start tag (`<name`).
+    ///
+    /// The start tag remains open for [attributes](SgmlWriter::attribute)
+    /// until any content is written.
+    pub fn start_element(&mut self, name: &str) -> Result<(), WriterError> {
+        check_name(name)?;
+        self.close_start_tag()?;
+        if let Some(parent) = self.stack.last_mut() {
+            parent.has_child_elements = true;
+        }
+        write!(self.out, "<{}", name)?;
+        self.stack.push(OpenElement {
+            name: name.to_owned(),
+            has_child_elements: false,
+        });
+        self.in_start_tag = true;
+        Ok(())
+    }
+
+    /// Writes an attribute in the currently open start tag.
+    ///
+    /// Attributes without a value (`None`) are written with only their name.
+    pub fn attribute(&mut self, name: &str, value: Option<&str>) -> Result<(), WriterError> {
+        if !self.in_start_tag {
+            return Err(WriterError::AttributeOutsideStartTag(name.to_owned()));
+        }
+        check_name(name)?;
+        match value {
+            Some(value) => write!(
+                self.out,
+                " {}={}",
+                name,
+                text::escape_attribute_value(value)
+            )?,
+            None => write!(self.out, " {}", name)?,
+        }
+        Ok(())
+    }
+
+    /// Writes text content, escaping it as necessary.
+    ///
+    /// Inside a `CDATA` marked section, where references are not recognized,
+    /// text is written verbatim instead, and must not contain `]]>`.
+    pub fn text(&mut self, text: &str) -> Result<(), WriterError> {
+        if self.marked_sections.last() == Some(&true) {
+            if text.contains("]]>") {
+                return Err(WriterError::InvalidMarkedSection(text.to_owned()));
+            }
+            self.close_start_tag()?;
+            self.out.write_all(text.as_bytes())?;
+            return Ok(());
+        }
+        self.close_start_tag()?;
+        write!(self.out, "{}", text::escape(text))?;
+        Ok(())
+    }
+
+    /// Closes the innermost open element, which must be named `name`.
+    pub fn end_element(&mut self, name: &str) -> Result<(), WriterError> {
+        let element = match self.stack.last() {
+            Some(element) if element.name == name => self.stack.pop().unwrap(),
+            Some(element) => {
+                return Err(WriterError::MismatchedEndTag {
+                    expected: element.name.clone(),
+                    found: name.to_owned(),
+                })
+            }
+            None => return Err(WriterError::UnpairedEndTag(name.to_owned())),
+        };
+        self.close_start_tag()?;
+        if !self.omit_end_tags || element.has_child_elements {
+            write!(self.out, "</{}>", name)?;
+        }
+        Ok(())
+    }
+
+    /// Writes a processing instruction (`<?instruction>`).
+    pub fn processing_instruction(&mut self, instruction: &str) -> Result<(), WriterError> {
+        self.close_start_tag()?;
+        write!(self.out, "<?{}>", instruction)?;
+        Ok(())
+    }
+
+    /// Writes a markup declaration, like `<!DOCTYPE body>`.
+    pub fn markup_declaration(&mut self, keyword: &str, body: &str) -> Result<(), WriterError> {
+        self.close_start_tag()?;
+        let declaration = SgmlEvent::MarkupDeclaration {
+            keyword: keyword.into(),
+            body: body.into(),
+        };
+        write!(self.out, "{}", declaration)?;
+        Ok(())
+    }
+
+    /// Writes a comment declaration (`<!--comment-->`).
+    pub fn comment(&mut self, comment: &str) -> Result<(), WriterError> {
+        if comment.contains("--") || comment.ends_with('-') {
+            return Err(WriterError::InvalidComment(comment.to_owned()));
+        }
+        self.close_start_tag()?;
+        write!(self.out, "<!--{}-->", comment)?;
+        Ok(())
+    }
+
+    /// Writes a marked section, like `<![CDATA[section]]>`.
+    ///
+    /// The section content is written as-is. It must not contain a `]]>` that would
+    /// end the section early: in `CDATA` and `RCDATA` sections, none at all; in other
+    /// sections, only as the end of a nested `<![...]]>` section.
+    pub fn marked_section(
+        &mut self,
+        status_keywords: &str,
+        section: &str,
+    ) -> Result<(), WriterError> {
+        if !is_valid_marked_section(status_keywords, section) {
+            return Err(WriterError::InvalidMarkedSection(section.to_owned()));
+        }
+        self.close_start_tag()?;
+        write!(self.out, "<![{}[{}]]>", status_keywords, section)?;
+        Ok(())
+    }
+
+    /// Writes the beginning of a marked section whose content is written
+    /// separately, like `<![INCLUDE[`.
+    ///
+    /// Must be followed by a matching [`end_marked_section`](SgmlWriter::end_marked_section).
+    pub fn start_marked_section(&mut self, status_keywords: &str) -> Result<(), WriterError> {
+        self.close_start_tag()?;
+        write!(self.out, "<![{}[", status_keywords)?;
+        self.marked_sections.push(matches!(
+            MarkedSectionStatus::from_keywords(status_keywords),
+            Ok(MarkedSectionStatus::CData)
+        ));
+        Ok(())
+    }
+
+    /// Writes the end of a marked section started by
+    /// [`start_marked_section`](SgmlWriter::start_marked_section).
+    pub fn end_marked_section(&mut self) -> Result<(), WriterError> {
+        self.close_start_tag()?;
+        self.out.write_all(b"]]>")?;
+        self.marked_sections.pop();
+        Ok(())
+    }
+
+    /// Writes a single event.
+    ///
+    /// This is a convenience for writing entire [`SgmlFragment`](crate::SgmlFragment)s,
+    /// or transformed streams of events.
+    pub fn write_event(&mut self, event: &SgmlEvent) -> Result<(), WriterError> {
+        match event {
+            SgmlEvent::MarkupDeclaration { keyword, body } => {
+                self.markup_declaration(keyword, body)
+            }
+            SgmlEvent::ProcessingInstruction(pi) => {
+                self.close_start_tag()?;
+                self.out.write_all(pi.as_bytes())?;
+                Ok(())
+            }
+            SgmlEvent::MarkedSection {
+                status_keywords,
+                section,
+            } => self.marked_section(status_keywords, section),
+            SgmlEvent::ParsedMarkedSection {
+                status_keywords,
+                content,
+                ..
+            } => match content {
+                MarkedSectionContent::Events(events) => {
+                    self.start_marked_section(status_keywords)?;
+                    events
+                        .iter()
+                        .try_for_each(|event| self.write_event(event))?;
+                    self.end_marked_section()
+                }
+                MarkedSectionContent::Text(text) => self.marked_section(status_keywords, text),
+            },
+            SgmlEvent::OpenStartTag { name } => self.start_element(name),
+            SgmlEvent::Attribute { name, value } => self.attribute(name, value.as_deref()),
+            SgmlEvent::CloseStartTag => self.close_start_tag(),
+            SgmlEvent::XmlCloseEmptyElement => {
+                if !self.in_start_tag {
+                    return Err(WriterError::UnpairedEndTag("".to_owned()));
+                }
+                self.stack.pop();
+                self.in_start_tag = false;
+                self.out.write_all(b"/>")?;
+                Ok(())
+            }
+            SgmlEvent::EndTag { name } => self.end_element(name),
+            SgmlEvent::Character(text) => self.text(text),
+            SgmlEvent::Comment(comment) => self.comment(comment),
+        }
+    }
+
+    /// Flushes the underlying writer.
+    pub fn flush(&mut self) -> Result<(), WriterError> {
+        self.out.flush()?;
+        Ok(())
+    }
+
+    /// Checks that all elements were closed, flushes and returns the underlying writer.
+    pub fn finish(mut self) -> Result<W, WriterError> {
+        if let Some(element) = self.stack.pop() {
+            return Err(WriterError::UnclosedElement(element.name));
+        }
+        self.flush()?;
+        Ok(self.out)
+    }
+
+    /// Returns the underlying writer, without checking for open elements.
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    fn close_start_tag(&mut self) -> Result<(), WriterError> {
+        if self.in_start_tag {
+            self.in_start_tag = false;
+            self.out.write_all(b">")?;
+        }
+        Ok(())
+    }
+}
+
+fn check_name(name: &str) -> Result<(), WriterError> {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if is_name_start_char(c) && chars.all(is_name_char) => Ok(()),
+        _ => Err(WriterError::InvalidName(name.to_owned())),
+    }
+}
+
+/// Checks that a marked section would end exactly where intended when read back.
+fn is_valid_marked_section(status_keywords: &str, section: &str) -> bool {
+    if let Ok(MarkedSectionStatus::CData | MarkedSectionStatus::RcData) =
+        MarkedSectionStatus::from_keywords(status_keywords)
+    {
+        return !section.contains("]]>");
+    }
+    let mut depth = 0usize;
+    let mut rest = section.as_bytes();
+    while !rest.is_empty() {
+        if rest.starts_with(b"<![") {
+            depth += 1;
+            rest = &rest[3..];
+        } else if rest.starts_with(b"]]>") {
+            match depth.checked_sub(1) {
+                Some(outer) => depth = outer,
+                None => return false,
+            }
+            rest = &rest[3..];
+        } else {
+            rest = &rest[1..];
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output(writer: SgmlWriter<Vec<u8>>) -> String {
+        String::from_utf8(writer.finish().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_write_document() {
+        let mut writer = SgmlWriter::new(vec![]);
+        writer.markup_declaration("DOCTYPE", "HTML").unwrap();
+        writer.processing_instruction("PI").unwrap();
+        writer.comment(" note ").unwrap();
+        writer.start_element("P").unwrap();
+        writer.attribute("CLASS", Some("a\"b")).unwrap();
+        writer.attribute("HIDDEN", None).unwrap();
+        writer.text("1 < 2").unwrap();
+        writer.marked_section("CDATA", "<&>").unwrap();
+        writer.start_element("BR").unwrap();
+        writer.end_element("BR").unwrap();
+        writer.end_element("P").unwrap();
+
+        assert_eq!(
+            output(writer),
+            r#"<!DOCTYPE HTML><?PI><!-- note --><P CLASS='a"b' HIDDEN>1 &#60; 2<![CDATA[<&>]]><BR></BR></P>"#
+        );
+    }
+
+    #[test]
+    fn test_write_cdata_marked_section() {
+        let mut writer = SgmlWriter::new(vec![]);
+        writer.start_element("P").unwrap();
+        writer.start_marked_section("CDATA").unwrap();
+        writer.text("<&> ]]").unwrap();
+        assert!(matches!(
+            writer.text("a]]>b"),
+            Err(WriterError::InvalidMarkedSection(_))
+        ));
+        writer.end_marked_section().unwrap();
+        writer.start_marked_section("INCLUDE").unwrap();
+        writer.text("<&>").unwrap();
+        writer.end_marked_section().unwrap();
+        writer.end_element("P").unwrap();
+
+        assert_eq!(
+            output(writer),
+            "<P><![CDATA[<&> ]]]]><![INCLUDE[&#60;&#38;&#62;]]></P>"
+        );
+    }
+
+    #[test]
+    fn test_omit_end_tags() {
+        let mut writer = SgmlWriter::new(vec![]);
+        writer.set_omit_end_tags(true);
+        writer.start_element("STMTTRN").unwrap();
+        writer.start_element("TRNTYPE").unwrap();
+        writer.text("DEBIT").unwrap();
+        writer.end_element("TRNTYPE").unwrap();
+        writer.start_element("TRNAMT").unwrap();
+        writer.text("-1.00").unwrap();
+        writer.end_element("TRNAMT").unwrap();
+        writer.end_element("STMTTRN").unwrap();
+
+        let written = output(writer);
+        assert_eq!(written, "<STMTTRN><TRNTYPE>DEBIT<TRNAMT>-1.00</STMTTRN>");
+        assert_eq!(
+            crate::transforms::normalize_end_tags(crate::parse(&written).unwrap()).unwrap(),
+            crate::parse("<STMTTRN><TRNTYPE>DEBIT</TRNTYPE><TRNAMT>-1.00</TRNAMT></STMTTRN>")
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_write_events_roundtrip() {
+        let input = r#"<!DOCTYPE X><X A="1" B><Y/><Z>a&#38;b</Z></X>"#;
+        let fragment = crate::parse(input).unwrap();
+        let mut writer = SgmlWriter::new(vec![]);
+        for event in &fragment {
+            writer.write_event(event).unwrap();
+        }
+        assert_eq!(output(writer), input);
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut writer = SgmlWriter::new(vec![]);
+        assert!(matches!(
+            writer.attribute("A", None),
+            Err(WriterError::AttributeOutsideStartTag(_))
+        ));
+        assert!(matches!(
+            writer.end_element("A"),
+            Err(WriterError::UnpairedEndTag(_))
+        ));
+        assert!(matches!(
+            writer.start_element("1A"),
+            Err(WriterError::InvalidName(_))
+        ));
+        assert!(matches!(
+            writer.start_element("A B"),
+            Err(WriterError::InvalidName(_))
+        ));
+        assert!(matches!(
+            writer.comment("a -- b"),
+            Err(WriterError::InvalidComment(_))
+        ));
+        assert!(matches!(
+            writer.comment("a-"),
+            Err(WriterError::InvalidComment(_))
+        ));
+        assert!(matches!(
+            writer.marked_section("CDATA", "a]]>b"),
+            Err(WriterError::InvalidMarkedSection(_))
+        ));
+        assert!(matches!(
+            writer.marked_section("IGNORE", "a]]><![IGNORE[b"),
+            Err(WriterError::InvalidMarkedSection(_))
+        ));
+        writer
+            .marked_section("IGNORE", "a <![CDATA[b]]> c")
+            .unwrap();
+
+        writer.start_element("A").unwrap();
+        writer.start_element("B").unwrap();
+        assert_eq!(writer.open_elements().collect::<Vec<_>>(), vec!["A", "B"]);
+        match writer.end_element("A") {
+            Err(WriterError::MismatchedEndTag { expected, found }) => {
+                assert_eq!(expected, "B");
+                assert_eq!(found, "A");
+            }
+            result => panic!("expected mismatched end tag, got {:?}", result),
+        }
+
+        assert!(matches!(
+            writer.attribute("C=D", None),
+            Err(WriterError::InvalidName(_))
+        ));
+        writer.text("hello").unwrap();
+        assert!(matches!(
+            writer.attribute("C", None),
+            Err(WriterError::AttributeOutsideStartTag(_))
+        ));
+        writer.end_element("B").unwrap();
+        assert!(matches!(
+            writer.finish(),
+            Err(WriterError::UnclosedElement(name)) if name == "A"
+        ));
+    }
+}
@@ -444,6 +444,311 @@ fn test_ignore_markup_declarations() -> sgmlish::Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_kept_comments_are_skipped() -> sgmlish::Result<()> {
+    init_logger();
+
+    #[derive(Debug, Deserialize)]
+    struct Test {
+        name: String,
+        value: String,
+    }
+
+    let input = r##"
+        <!-- Header -->
+        <test>
+            <!-- Name -->
+            <name>Testing</name>
+            <value>one<!-- inline -->two</value>
+        </test>
+        <!-- Footer -->
+    "##;
+    let sgml = Parser::builder().keep_comments(true).parse(input)?;
+    assert!(sgml
+        .iter()
+        .any(|event| matches!(event, SgmlEvent::Comment(_))));
+
+    let test = sgmlish::from_fragment::<Test>(sgml)?;
+    assert_eq!(test.name, "Testing");
+    assert_eq!(test.value, "onetwo");
+
+    Ok(())
+}
+
+#[test]
+fn test_skip_markup_declarations_in_content() -> sgmlish::Result<()> {
+    init_logger();
+
+    #[derive(Debug, Deserialize)]
+    struct Test {
+        name: String,
+        value: String,
+    }
+
+    let input = r##"
+        <!DOCTYPE test>
+        <test>
+            <!USEMAP map1>
+            <name>Testing</name>
+            <value>one<!ENTITY e "x">two</value>
+        </test>
+    "##;
+    let sgml = sgmlish::parse(input)?;
+
+    let err = sgmlish::from_fragment::<Test>(sgml.clone()).unwrap_err();
+    assert!(matches!(
+        err,
+        DeserializationError::Unsupported(SgmlEvent::MarkupDeclaration { keyword, .. })
+            if keyword == "DOCTYPE"
+    ));
+
+    let options = sgmlish::de::DeserializerOptions {
+        skip_markup_declarations: true,
+        ..Default::default()
+    };
+    let test = sgmlish::de::from_fragment_with_options::<Test>(sgml, options)?;
+    assert_eq!(test.name, "Testing");
+    assert_eq!(test.value, "onetwo");
+
+    Ok(())
+}
+
+#[test]
+fn test_case_insensitive_keys() -> sgmlish::Result<()> {
+    init_logger();
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct StatementTransaction {
+        #[serde(rename = "trnType")]
+        trn_type: String,
+        fitid: String,
+        memo: Option<String>,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct TransactionList {
+        id: String,
+        #[serde(rename = "stmttrn")]
+        transactions: Vec<StatementTransaction>,
+    }
+
+    let input = r##"
+        <BANKTRANLIST Id="list">
+            <STMTTRN><TRNTYPE>DEBIT</TRNTYPE><FITID>1</FITID></STMTTRN>
+            <stmtTrn><trntype>CREDIT</trntype><FitId>2</fitid><MEMO>x</MEMO></StmtTrn>
+        </BANKTRANLIST>
+    "##;
+    let sgml = sgmlish::parse(input)?;
+
+    assert!(sgmlish::from_fragment::<TransactionList>(sgml.clone()).is_err());
+
+    let options = sgmlish::de::DeserializerOptions {
+        case_insensitive_keys: true,
+        ..Default::default()
+    };
+    let list = sgmlish::de::from_fragment_with_options::<TransactionList>(sgml, options)?;
+    assert_eq!(
+        list,
+        TransactionList {
+            id: "list".to_owned(),
+            transactions: vec![
+                StatementTransaction {
+                    trn_type: "DEBIT".to_owned(),
+                    fitid: "1".to_owned(),
+                    memo: None,
+                },
+                StatementTransaction {
+                    trn_type: "CREDIT".to_owned(),
+                    fitid: "2".to_owned(),
+                    memo: Some("x".to_owned()),
+                },
+            ],
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_case_insensitive_keys_keep_map_keys() -> sgmlish::Result<()> {
+    init_logger();
+
+    let sgml = sgmlish::parse("<map><Key>value</KEY></map>")?;
+    let options = sgmlish::de::DeserializerOptions {
+        case_insensitive_keys: true,
+        ..Default::default()
+    };
+    let map = sgmlish::de::from_fragment_with_options::<std::collections::HashMap<String, String>>(
+        sgml, options,
+    )?;
+    assert_eq!(map.get("Key").map(String::as_str), Some("value"));
+
+    Ok(())
+}
+
+#[test]
+fn test_deserializer_builder() -> sgmlish::Result<()> {
+    init_logger();
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(rename_all = "UPPERCASE")]
+    struct Account {
+        acctid: String,
+        nickname: Option<String>,
+        desc: Option<String>,
+        closed: bool,
+        primary: bool,
+    }
+
+    let input = r##"
+        <ACCOUNT>
+            <?experiment>
+            <ACCTID>  123  </ACCTID>
+            <NICKNAME>   </NICKNAME>
+            <DESC/>
+            <CLOSED>N</CLOSED>
+            <PRIMARY>y</PRIMARY>
+        </ACCOUNT>
+    "##;
+    let sgml = sgmlish::Parser::builder()
+        .trim_whitespace(false)
+        .parse(input)?;
+
+    let account = sgmlish::de::DeserializerOptions::builder()
+        .skip_processing_instructions(true)
+        .boolean_spellings(&["Y"], &["N"])
+        .empty_as_none(true)
+        .trim_text(true)
+        .deserialize::<Account>(sgml.clone())?;
+    assert_eq!(
+        account,
+        Account {
+            acctid: "123".to_owned(),
+            nickname: None,
+            desc: None,
+            closed: false,
+            primary: true,
+        }
+    );
+
+    let err = sgmlish::de::DeserializerOptions::builder()
+        .skip_processing_instructions(true)
+        .deserialize::<Account>(sgml)
+        .unwrap_err();
+    assert_eq!(err.path(), Some("ACCOUNT/CLOSED"));
+    assert!(matches!(err.inner(), DeserializationError::Message(_)));
+
+    Ok(())
+}
+
+#[test]
+fn test_empty_as_none_keeps_flags() -> sgmlish::Result<()> {
+    init_logger();
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Img {
+        src: Option<String>,
+        alt: Option<String>,
+        ismap: Option<bool>,
+    }
+
+    let sgml = sgmlish::parse(r#"<img src="a.gif" alt="" ismap></img>"#)?;
+    let img = sgmlish::de::DeserializerOptions::builder()
+        .empty_as_none(true)
+        .deserialize::<Img>(sgml)?;
+    assert_eq!(
+        img,
+        Img {
+            src: Some("a.gif".to_owned()),
+            alt: None,
+            ismap: Some(true),
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_strict_mode() -> sgmlish::Result<()> {
+    init_logger();
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Test {
+        id: String,
+        name: String,
+    }
+
+    let sgml = sgmlish::parse(r#"<test id="1" extra="x"><name>A</name><other>B</other></test>"#)?;
+    let test = sgmlish::from_fragment::<Test>(sgml.clone())?;
+    assert_eq!(test.name, "A");
+
+    let err = sgmlish::de::DeserializerOptions::builder()
+        .strict(true)
+        .deserialize::<Test>(sgml)
+        .unwrap_err();
+    assert_eq!(err.path(), Some("test"));
+    assert!(matches!(err.inner(), DeserializationError::UnknownElement(name) if name == "other"));
+
+    Ok(())
+}
+
+#[test]
+fn test_error_path() {
+    init_logger();
+
+    #[derive(Debug, Deserialize)]
+    #[serde(rename_all = "UPPERCASE")]
+    struct StatementResponse {
+        #[serde(rename = "BANKTRANLIST")]
+        transactions: TransactionList,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct TransactionList {
+        #[serde(rename = "STMTTRN")]
+        transactions: Vec<Transaction>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[serde(rename_all = "UPPERCASE")]
+    struct Transaction {
+        id: Option<u32>,
+        trnamt: Decimal,
+    }
+
+    let input = r##"
+        <STMTRS>
+            <BANKTRANLIST>
+                <STMTTRN><TRNAMT>1.00</TRNAMT></STMTTRN>
+                <STMTTRN ID="2"><TRNAMT>2.00</TRNAMT></STMTTRN>
+                <STMTTRN><TRNAMT>three</TRNAMT></STMTTRN>
+            </BANKTRANLIST>
+        </STMTRS>
+    "##;
+    let err =
+        sgmlish::from_fragment::<StatementResponse>(sgmlish::parse(input).unwrap()).unwrap_err();
+    assert_eq!(err.path(), Some("STMTRS/BANKTRANLIST/STMTTRN[2]/TRNAMT"));
+    assert!(matches!(err.inner(), DeserializationError::Message(_)));
+    assert!(err
+        .to_string()
+        .starts_with("at STMTRS/BANKTRANLIST/STMTTRN[2]/TRNAMT: "));
+
+    let input = r##"
+        <STMTRS>
+            <BANKTRANLIST>
+                <STMTTRN ID="x"><TRNAMT>1.00</TRNAMT></STMTTRN>
+            </BANKTRANLIST>
+        </STMTRS>
+    "##;
+    let err =
+        sgmlish::from_fragment::<StatementResponse>(sgmlish::parse(input).unwrap()).unwrap_err();
+    assert_eq!(err.path(), Some("STMTRS/BANKTRANLIST/STMTTRN[0]/@ID"));
+    assert!(matches!(
+        err.inner(),
+        DeserializationError::ParseIntError { .. }
+    ));
+}
+
 #[test]
 fn test_reject_processing_instructions() {
     init_logger();
@@ -464,11 +769,208 @@ fn test_reject_processing_instructions() {
 
     let err = sgmlish::from_fragment::<Test>(sgml).unwrap_err();
     assert!(matches!(
-        err,
+        err.inner(),
         DeserializationError::Unsupported(SgmlEvent::ProcessingInstruction(pi)) if pi == "<?experiment>"
     ));
 }
 
+#[test]
+fn test_capture_declarations_and_processing_instructions() -> sgmlish::Result<()> {
+    init_logger();
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Test {
+        #[serde(rename = "$declarations")]
+        declarations: Vec<String>,
+        #[serde(rename = "$pi")]
+        processing_instructions: Vec<String>,
+        name: String,
+        item: Item,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Item {
+        #[serde(rename = "$pi", default)]
+        processing_instructions: Vec<String>,
+        #[serde(rename = "$value")]
+        value: String,
+    }
+
+    let input = r##"
+        <!DOCTYPE test SYSTEM "test.dtd">
+        <test>
+            <?experiment>
+            <name>Testing</name>
+            <item>one<?page-break>two</item>
+        </test>
+    "##;
+    let sgml = sgmlish::parse(input)?;
+
+    let test = sgmlish::from_fragment::<Test>(sgml)?;
+    assert_eq!(
+        test,
+        Test {
+            declarations: vec![r#"<!DOCTYPE test SYSTEM "test.dtd">"#.to_owned()],
+            processing_instructions: vec!["<?experiment>".to_owned()],
+            name: "Testing".to_owned(),
+            item: Item {
+                processing_instructions: vec!["<?page-break>".to_owned()],
+                value: "onetwo".to_owned(),
+            },
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_uncaptured_declarations_are_rejected() {
+    init_logger();
+
+    #[derive(Debug, Deserialize)]
+    struct Test {
+        #[serde(rename = "$pi")]
+        processing_instructions: Vec<String>,
+        name: String,
+    }
+
+    let input = r##"
+        <test>
+            <!USEMAP map1>
+            <?experiment>
+            <name>Testing</name>
+        </test>
+    "##;
+    let sgml = sgmlish::parse(input).unwrap();
+
+    let err = sgmlish::from_fragment::<Test>(sgml.clone()).unwrap_err();
+    assert!(matches!(
+        err,
+        DeserializationError::Unsupported(SgmlEvent::MarkupDeclaration { keyword, .. })
+            if keyword == "USEMAP"
+    ));
+
+    let test = sgmlish::de::DeserializerOptions::builder()
+        .skip_markup_declarations(true)
+        .deserialize::<Test>(sgml)
+        .unwrap();
+    assert_eq!(test.processing_instructions, vec!["<?experiment>"]);
+}
+
+#[test]
+fn test_skip_marked_sections() -> sgmlish::Result<()> {
+    init_logger();
+
+    #[derive(Debug, Deserialize)]
+    struct Test {
+        name: String,
+    }
+
+    let sgml = sgmlish::Parser::builder()
+        .marked_section_handling(sgmlish::parser::MarkedSectionHandling::KeepParsed)
+        .parse(
+            "<test><![ INCLUDE [<![ CDATA [x]]><name>Included</name>]]><![ IGNORE [<name>Ignored</name>]]></test>",
+        )?;
+
+    let err = sgmlish::from_fragment::<Test>(sgml.clone()).unwrap_err();
+    assert!(matches!(err.inner(), DeserializationError::Unsupported(_)));
+
+    let test = sgmlish::de::DeserializerOptions::builder()
+        .skip_marked_sections(true)
+        .deserialize::<Test>(sgml)?;
+    assert_eq!(test.name, "Included");
+
+    Ok(())
+}
+
+#[test]
+fn test_mixed_content() -> sgmlish::Result<()> {
+    init_logger();
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Paragraph {
+        class: Option<String>,
+        #[serde(rename = "$value")]
+        content: Vec<Inline>,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(rename_all = "lowercase")]
+    enum Inline {
+        #[serde(rename = "$text")]
+        Text(String),
+        B(String),
+        Br,
+        A(Link),
+        Em(Vec<Inline>),
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Link {
+        href: String,
+        #[serde(rename = "$value")]
+        text: String,
+    }
+
+    let input = r##"
+        <p class="intro">
+            Hello, <b>world</b>!<br/>
+            See <a href="/docs">the docs</a> or <em>ask <b>us</b></em>
+        </p>
+    "##;
+    let sgml = sgmlish::parse(input)?;
+
+    let paragraph = sgmlish::from_fragment::<Paragraph>(sgml)?;
+    assert_eq!(
+        paragraph,
+        Paragraph {
+            class: Some("intro".to_owned()),
+            content: vec![
+                Inline::Text("Hello,".to_owned()),
+                Inline::B("world".to_owned()),
+                Inline::Text("!".to_owned()),
+                Inline::Br,
+                Inline::Text("See".to_owned()),
+                Inline::A(Link {
+                    href: "/docs".to_owned(),
+                    text: "the docs".to_owned(),
+                }),
+                Inline::Text("or".to_owned()),
+                Inline::Em(vec![
+                    Inline::Text("ask".to_owned()),
+                    Inline::B("us".to_owned()),
+                ]),
+            ],
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_mixed_content_text_variant_errors() {
+    init_logger();
+
+    #[derive(Debug, Deserialize)]
+    struct Paragraph {
+        #[serde(rename = "$value")]
+        content: Vec<Inline>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    enum Inline {
+        #[serde(rename = "$text")]
+        Text {
+            value: String,
+        },
+        B(String),
+    }
+
+    let sgml = sgmlish::parse("<p><B>x</B>y</p>").unwrap();
+    let err = sgmlish::from_fragment::<Paragraph>(sgml).unwrap_err();
+    assert_eq!(err.path(), Some("p/$value[1]"));
+}
+
 #[test]
 fn test_recursive_enum() {
     init_logger();
@@ -611,3 +1113,422 @@ fn test_enum_untagged() {
         }
     );
 }
+
+#[test]
+fn test_flatten() {
+    init_logger();
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(rename_all = "UPPERCASE")]
+    struct Transaction {
+        trntype: String,
+        #[serde(flatten)]
+        amount: Amount,
+        #[serde(flatten)]
+        extra: std::collections::BTreeMap<String, String>,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(rename_all = "UPPERCASE")]
+    struct Amount {
+        currency: Option<String>,
+        trnamt: u32,
+        rate: f64,
+    }
+
+    let input = r##"
+        <STMTTRN CURRENCY="EUR">
+            <TRNTYPE>DEBIT</TRNTYPE>
+            <TRNAMT>1234</TRNAMT>
+            <RATE>1.5</RATE>
+            <MEMO>Coffee</MEMO>
+        </STMTTRN>
+    "##;
+    let sgml = sgmlish::parse(input).unwrap();
+    let transaction = sgmlish::de::DeserializerOptions::builder()
+        .infer_numbers(true)
+        .deserialize::<Transaction>(sgml)
+        .unwrap();
+    assert_eq!(
+        transaction,
+        Transaction {
+            trntype: "DEBIT".into(),
+            amount: Amount {
+                currency: Some("EUR".into()),
+                trnamt: 1234,
+                rate: 1.5,
+            },
+            extra: std::iter::once(("MEMO".to_owned(), "Coffee".to_owned())).collect(),
+        }
+    );
+}
+
+#[test]
+fn test_flatten_numeric_text_in_strings() {
+    init_logger();
+
+    #[derive(Debug, Deserialize)]
+    struct Payment {
+        #[serde(flatten)]
+        inner: Inner,
+        #[serde(flatten)]
+        fields: std::collections::BTreeMap<String, String>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Inner {
+        code: String,
+    }
+
+    let input = "<payment><code>123</code><amount>12</amount><memo>Rent</memo></payment>";
+    let sgml = sgmlish::parse(input).unwrap();
+    let payment = sgml.clone().deserialize::<Payment>().unwrap();
+    assert_eq!(payment.inner.code, "123");
+    assert_eq!(payment.fields["amount"], "12");
+    assert_eq!(payment.fields["memo"], "Rent");
+
+    // With numbers inferred, string fields need `from_text`;
+    // errors in buffered content are reported at the enclosing element
+    let err = sgmlish::de::DeserializerOptions::builder()
+        .infer_numbers(true)
+        .deserialize::<Payment>(sgml)
+        .unwrap_err();
+    assert_eq!(err.path(), Some("payment"));
+    assert!(err.to_string().contains("invalid type: integer"), "{}", err);
+}
+
+#[test]
+fn test_enum_tagged_numeric_text_in_strings() {
+    init_logger();
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(tag = "kind")]
+    enum Address {
+        #[serde(rename = "us")]
+        Us { zip: String },
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(untagged)]
+    enum Code {
+        Zip { zip: String },
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Customer {
+        address: Vec<Address>,
+        code: Code,
+    }
+
+    let input = r##"
+        <customer>
+            <address kind="us"><zip>90210</zip></address>
+            <address><kind>us</kind><zip>10001</zip></address>
+            <code><zip>60614</zip></code>
+        </customer>
+    "##;
+    let customer = sgmlish::parse(input)
+        .unwrap()
+        .deserialize::<Customer>()
+        .unwrap();
+    assert_eq!(
+        customer.address,
+        vec![
+            Address::Us {
+                zip: "90210".into()
+            },
+            Address::Us {
+                zip: "10001".into()
+            },
+        ]
+    );
+    assert_eq!(
+        customer.code,
+        Code::Zip {
+            zip: "60614".into()
+        }
+    );
+}
+
+#[test]
+fn test_enum_internal_tag_in_child_element() {
+    init_logger();
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(tag = "kind", rename_all = "lowercase")]
+    enum Shape {
+        Circle {
+            radius: u32,
+        },
+        Rect {
+            width: u32,
+            height: u32,
+            label: String,
+        },
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Drawing {
+        shape: Vec<Shape>,
+    }
+
+    let input = r##"
+        <drawing>
+            <shape><kind>circle</kind><radius>5</radius></shape>
+            <shape kind="rect" width="3"><height>4</height><label>007</label></shape>
+        </drawing>
+    "##;
+    let sgml = sgmlish::parse(input).unwrap();
+    let drawing = sgmlish::de::DeserializerOptions::builder()
+        .infer_numbers(true)
+        .deserialize::<Drawing>(sgml)
+        .unwrap();
+    assert_eq!(
+        drawing.shape,
+        vec![
+            Shape::Circle { radius: 5 },
+            Shape::Rect {
+                width: 3,
+                height: 4,
+                label: "007".into(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_enum_untagged_numeric() {
+    init_logger();
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(untagged)]
+    enum Value {
+        Range {
+            min: i32,
+            max: i32,
+        },
+        Point {
+            x: f64,
+            y: f64,
+            #[serde(deserialize_with = "sgmlish::de::from_text")]
+            name: String,
+        },
+        Code(String),
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Values {
+        value: Vec<Value>,
+    }
+
+    let input = r##"
+        <values>
+            <value min="-1" max="10"></value>
+            <value><x>1.5</x><y>2</y><name>12</name></value>
+            <value>0042</value>
+        </values>
+    "##;
+    let sgml = sgmlish::parse(input).unwrap();
+    let values = sgmlish::de::DeserializerOptions::builder()
+        .infer_numbers(true)
+        .deserialize::<Values>(sgml)
+        .unwrap();
+    assert_eq!(
+        values.value,
+        vec![
+            Value::Range { min: -1, max: 10 },
+            Value::Point {
+                x: 1.5,
+                y: 2.0,
+                name: "12".into(),
+            },
+            Value::Code("0042".into()),
+        ]
+    );
+}
+
+#[test]
+fn test_buffered_numeric_fields_with_from_text() {
+    init_logger();
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Amount {
+        #[serde(deserialize_with = "sgmlish::de::from_text")]
+        cents: u32,
+        currency: String,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(tag = "kind", rename_all = "lowercase")]
+    enum Shape {
+        Circle {
+            #[serde(deserialize_with = "sgmlish::de::from_text")]
+            radius: u32,
+        },
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(untagged)]
+    enum Value {
+        Point {
+            #[serde(deserialize_with = "sgmlish::de::from_text")]
+            x: f64,
+            #[serde(deserialize_with = "sgmlish::de::from_text")]
+            y: i32,
+        },
+        Code(String),
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Document {
+        #[serde(flatten)]
+        amount: Amount,
+        shape: Shape,
+        value: Vec<Value>,
+    }
+
+    // Without `infer_numbers`, buffered numeric fields go through `from_text`
+    let input = r##"
+        <document>
+            <cents>700</cents>
+            <currency>001</currency>
+            <shape kind="circle"><radius>5</radius></shape>
+            <value><x>1.5</x><y>-2</y></value>
+            <value>0042</value>
+        </document>
+    "##;
+    let document = sgmlish::parse(input)
+        .unwrap()
+        .deserialize::<Document>()
+        .unwrap();
+    assert_eq!(
+        document.amount,
+        Amount {
+            cents: 700,
+            currency: "001".into(),
+        }
+    );
+    assert_eq!(document.shape, Shape::Circle { radius: 5 });
+    assert_eq!(
+        document.value,
+        vec![Value::Point { x: 1.5, y: -2 }, Value::Code("0042".into())]
+    );
+}
+
+#[test]
+fn test_attribute_prefix() {
+    init_logger();
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Item {
+        #[serde(rename = "@ID")]
+        id_attr: u32,
+        #[serde(rename = "ID")]
+        id_elem: String,
+        #[serde(rename = "@TYPE")]
+        kind: Option<String>,
+        #[serde(rename = "$text")]
+        text: String,
+        #[serde(rename = "NOTE", default)]
+        notes: Vec<String>,
+    }
+
+    let input = r##"
+        <ITEM ID="7">
+            Widget
+            <ID>W-0007</ID>
+            <NOTE>Fragile</NOTE>
+        </ITEM>
+    "##;
+    let sgml = sgmlish::parse(input).unwrap();
+    let item = sgml.deserialize::<Item>().unwrap();
+    assert_eq!(
+        item,
+        Item {
+            id_attr: 7,
+            id_elem: "W-0007".into(),
+            kind: None,
+            text: "Widget".into(),
+            notes: vec!["Fragile".into()],
+        }
+    );
+
+    let sgml = sgmlish::parse("<ITEM ID=x><ID>1</ID></ITEM>").unwrap();
+    let err = sgml.deserialize::<Item>().unwrap_err();
+    assert_eq!(err.path(), Some("ITEM/@ID"));
+}
+
+#[test]
+fn test_prefix_attributes() {
+    init_logger();
+
+    #[derive(Debug, Deserialize)]
+    struct Item {
+        #[serde(flatten)]
+        fields: std::collections::BTreeMap<String, String>,
+    }
+
+    let sgml = sgmlish::parse("<ITEM ID=x LABEL=y><ID>z</ID></ITEM>").unwrap();
+    let item = sgmlish::de::DeserializerOptions::builder()
+        .prefix_attributes(true)
+        .deserialize::<Item>(sgml)
+        .unwrap();
+    let fields = item
+        .fields
+        .iter()
+        .map(|(key, value)| (key.as_str(), value.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(fields, [("@ID", "x"), ("@LABEL", "y"), ("ID", "z")]);
+}
+
+#[test]
+fn test_whitespace_policy_preserved_field() {
+    init_logger();
+
+    use sgmlish::transforms::{normalize_end_tags, WhitespaceMode, WhitespacePolicy};
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(rename_all = "UPPERCASE")]
+    struct Transaction {
+        trntype: String,
+        trnamt: Decimal,
+        memo: String,
+    }
+
+    let input = r##"
+        <STMTTRN>
+            <TRNTYPE>  DEBIT
+            <TRNAMT> -12.34
+            <MEMO>Dinner:
+  2x pasta
+  1x wine
+        </STMTTRN>
+    "##;
+    let sgml = Parser::builder()
+        .trim_whitespace(false)
+        .parse(input)
+        .unwrap();
+    let sgml = normalize_end_tags(sgml).unwrap();
+    let sgml = WhitespacePolicy::new(WhitespaceMode::Trim)
+        .element("MEMO", WhitespaceMode::Preserve)
+        .apply(sgml);
+
+    let expected = Transaction {
+        trntype: "DEBIT".to_owned(),
+        trnamt: Decimal::from_str("-12.34").unwrap(),
+        memo: "Dinner:\n  2x pasta\n  1x wine\n        ".to_owned(),
+    };
+    assert_eq!(expected, sgmlish::from_fragment(sgml).unwrap());
+
+    let sgml = Parser::builder()
+        .trim_whitespace(false)
+        .parse(input)
+        .unwrap();
+    let transaction = sgmlish::de::DeserializerOptions::builder()
+        .whitespace_policy(
+            WhitespacePolicy::new(WhitespaceMode::Trim).element("MEMO", WhitespaceMode::Preserve),
+        )
+        .deserialize::<Transaction>(normalize_end_tags(sgml).unwrap())
+        .unwrap();
+    assert_eq!(expected, transaction);
+}
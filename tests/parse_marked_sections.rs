@@ -459,3 +459,174 @@ fn test_keep_unmodified_ignore_trim_whitespace() {
     );
     assert_eq!(events.next(), None);
 }
+
+#[test]
+fn test_rcdata_function_characters() {
+    let events = Parser::builder()
+        .trim_whitespace(false)
+        .parse("<X><![RCDATA[a&#TAB;b&#RS;c]]><![CDATA[&#TAB;]]></X>")
+        .unwrap();
+    assert_eq!(
+        events.as_slice()[2..4],
+        [
+            SgmlEvent::Character("a\tb\nc".into()),
+            SgmlEvent::Character("&#TAB;".into()),
+        ]
+    );
+}
+
+#[test]
+fn test_cdata_ends_on_first_marked_section_end() {
+    // CDATA sections do not nest: the first `]]>` ends the section,
+    // and any later `]]>` is plain character data
+    let input = "<X><![CDATA[a]]>b]]></X>";
+    let events = Parser::builder()
+        .marked_section_handling(sgmlish::parser::MarkedSectionHandling::KeepUnmodified)
+        .parse(input)
+        .unwrap();
+    assert_eq!(
+        events.as_slice()[2..4],
+        [
+            SgmlEvent::MarkedSection {
+                status_keywords: "CDATA".into(),
+                section: "a".into(),
+            },
+            SgmlEvent::Character("b]]>".into()),
+        ]
+    );
+
+    let events = sgmlish::parse(input).unwrap();
+    assert_eq!(
+        events.as_slice()[2..4],
+        [
+            SgmlEvent::Character("a".into()),
+            SgmlEvent::Character("b]]>".into()),
+        ]
+    );
+}
+
+#[test]
+fn test_nested_conditional_sections() {
+    const SGML: &str = r##"
+        <DOC>
+        <![ %draft; [ d1
+            <![ %final; [ f1 <![ %undefined; [ <![CDATA[ ]]> ]]> ]]>
+            <![ %draft; [ d2 <![CDATA[ <x> ]]> <![INCLUDE[]]> ]]>
+        ]]>
+        end
+        </DOC>
+    "##;
+    let parser = |mode| {
+        Parser::builder()
+            .marked_section_handling(mode)
+            .expand_parameter_entities(|entity| match entity {
+                "draft" => Some("INCLUDE"),
+                "final" => Some("IGNORE"),
+                _ => None,
+            })
+    };
+
+    let fragment = parser(sgmlish::parser::MarkedSectionHandling::ExpandAll)
+        .parse(SGML)
+        .unwrap();
+    assert_eq!(fragment.to_string(), "<DOC>d1d2&#60;x&#62;end</DOC>");
+
+    let fragment = parser(sgmlish::parser::MarkedSectionHandling::KeepUnmodified)
+        .parse(SGML)
+        .unwrap();
+    assert_eq!(
+        fragment.to_string(),
+        concat!(
+            "<DOC><![INCLUDE[ d1\n",
+            "            <![ %final; [ f1 <![ %undefined; [ <![CDATA[ ]]> ]]> ]]>\n",
+            "            <![ %draft; [ d2 <![CDATA[ <x> ]]> <![INCLUDE[]]> ]]>\n",
+            "        ]]>end</DOC>"
+        )
+    );
+
+    let fragment = parser(sgmlish::parser::MarkedSectionHandling::KeepParsed)
+        .parse(SGML)
+        .unwrap();
+    assert_eq!(fragment.len(), 5);
+    assert_eq!(
+        fragment.to_string(),
+        concat!(
+            "<DOC><![%draft;[d1",
+            "<![%final;[ f1 <![ %undefined; [ <![CDATA[ ]]> ]]> ]]>",
+            "<![%draft;[d2<![CDATA[ <x> ]]><![INCLUDE[]]>]]>",
+            "]]>end</DOC>"
+        )
+    );
+}
+
+#[test]
+fn test_keep_parsed() {
+    use sgmlish::marked_sections::{MarkedSectionContent, MarkedSectionStatus};
+
+    let events = Parser::builder()
+        .marked_section_handling(sgmlish::parser::MarkedSectionHandling::KeepParsed)
+        .expand_parameter_entities(|_| Some("INCLUDE"))
+        .parse(SGML)
+        .unwrap()
+        .into_vec();
+
+    let sections = events
+        .iter()
+        .filter_map(|event| match event {
+            SgmlEvent::ParsedMarkedSection {
+                status,
+                status_keywords,
+                content,
+            } => Some((*status, status_keywords.as_ref(), content)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(sections.len(), 3);
+
+    let (status, status_keywords, content) = sections[0];
+    assert_eq!(status, MarkedSectionStatus::Include);
+    assert_eq!(status_keywords, "%cond");
+    let inner = match content {
+        MarkedSectionContent::Events(events) => events,
+        content => panic!("expected parsed events, got {:?}", content),
+    };
+    assert_eq!(inner[0], SgmlEvent::Character("two".into()));
+    assert_eq!(
+        inner[5],
+        SgmlEvent::ParsedMarkedSection {
+            status: MarkedSectionStatus::Ignore,
+            status_keywords: "RCDATA IGNORE".into(),
+            content: MarkedSectionContent::Text(" four <![INCLUDE[ <BAR> five ]]> ".into()),
+        }
+    );
+    assert!(matches!(
+        &inner[6],
+        SgmlEvent::ParsedMarkedSection {
+            status: MarkedSectionStatus::Include,
+            content: MarkedSectionContent::Events(events),
+            ..
+        } if events[0] == SgmlEvent::Character("six".into())
+    ));
+
+    assert_eq!(
+        sections[1],
+        (
+            MarkedSectionStatus::Ignore,
+            "IGNORE",
+            &MarkedSectionContent::Text(" eight <QUUX> nine ".into())
+        )
+    );
+    assert_eq!(
+        sections[2],
+        (
+            MarkedSectionStatus::RcData,
+            "TEMP RCDATA",
+            &MarkedSectionContent::Text(" <XYZZY> <![[ ten ".into())
+        )
+    );
+
+    let fragment = sgmlish::SgmlFragment::from(events);
+    assert!(fragment
+        .to_string()
+        .contains("<![%cond[two<FOO>three<?page break><![RCDATA IGNORE["));
+}